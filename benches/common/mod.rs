@@ -0,0 +1,110 @@
+//! What every bench shares: the synthetic workloads, laid out like the view would.
+
+#![allow(dead_code)]
+
+use egui::{Pos2, Rect, Vec2, pos2, vec2};
+use egui_cfg::style::NodeStyle;
+use egui_cfg::synthetic::{Block, synthetic_cfg};
+use egui_cfg::{CfgLayout, EdgeKind, LayoutConfig, estimate_block_size, get_cfg_layout};
+use petgraph::graph::NodeIndex;
+use petgraph::stable_graph::StableGraph;
+
+/// How many cells around every block edges are kept away from, same as in the view.
+pub const OBSTACLE_RADIUS: f32 = 5.0;
+
+/// How many blocks the graphs of every bench have.
+pub const SIZES: [usize; 3] = [100, 1_000, 5_000];
+
+/// How far the ports are from their blocks.
+const PORT_OFFSET: f32 = 4.0;
+
+/// A headless context whose fonts are loaded, for measuring blocks without a [`egui::Ui`].
+pub fn context() -> egui::Context {
+    let ctx = egui::Context::default();
+    let _ = ctx.run(egui::RawInput::default(), |_| {});
+    ctx
+}
+
+/// The size of every block, measured like [`estimate_block_size`] does.
+pub fn sizes(
+    ctx: &egui::Context,
+    graph: &StableGraph<Block, EdgeKind>,
+    style: &NodeStyle,
+) -> Vec<Vec2> {
+    let bound = graph
+        .node_indices()
+        .map(|n| n.index() + 1)
+        .max()
+        .unwrap_or(0);
+    let mut sizes = vec![Vec2::ZERO; bound];
+
+    ctx.fonts(|fonts| {
+        for node in graph.node_indices() {
+            sizes[node.index()] = estimate_block_size(&graph[node], style, fonts);
+        }
+    });
+
+    sizes
+}
+
+/// A graph of `blocks` blocks, laid out, along with the rect of every block and the ports
+/// of every edge, like the view would route them.
+pub struct Workload {
+    pub graph: StableGraph<Block, EdgeKind>,
+    pub layout: CfgLayout,
+    pub rects: Vec<Rect>,
+    pub edges: Vec<(Pos2, Pos2)>,
+    /// Everything there is to route in, with room around the outermost blocks.
+    pub scene: Rect,
+}
+
+impl Workload {
+    pub fn new(blocks: usize) -> Self {
+        Self::from_graph(synthetic_cfg(blocks, 4, 0.4, 7))
+    }
+
+    pub fn from_graph(graph: StableGraph<Block, EdgeKind>) -> Self {
+        let ctx = context();
+        let style = NodeStyle::default();
+        let sizes = sizes(&ctx, &graph, &style);
+
+        let layout = get_cfg_layout(
+            &graph,
+            &LayoutConfig::default(),
+            |node: NodeIndex, _: &Block| sizes[node.index()],
+            &[],
+        );
+
+        let mut rects = vec![Rect::NOTHING; sizes.len()];
+
+        for (node, (x, y)) in &layout.coords {
+            rects[node.index()] =
+                Rect::from_center_size(pos2(*x as f32, *y as f32), sizes[node.index()]);
+        }
+
+        // straight out of the bottom of the block, and into the top of the other one.
+        let edges = graph
+            .edge_indices()
+            .filter_map(|edge| graph.edge_endpoints(edge))
+            .filter(|(from, to)| from != to)
+            .map(|(from, to)| {
+                let from = rects[from.index()].center_bottom() + vec2(0.0, PORT_OFFSET);
+                let to = rects[to.index()].center_top() - vec2(0.0, PORT_OFFSET);
+                (from, to)
+            })
+            .collect();
+
+        let scene = rects
+            .iter()
+            .fold(Rect::NOTHING, |scene, rect| scene.union(*rect))
+            .expand(100.0);
+
+        Self {
+            graph,
+            layout,
+            rects,
+            edges,
+            scene,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Laying out synthetic graphs, with the blocks measured headlessly.
+
+mod common;
+
+use common::{SIZES, context, sizes};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use egui_cfg::style::NodeStyle;
+use egui_cfg::synthetic::{Block, synthetic_cfg};
+use egui_cfg::{EdgeKind, LayoutConfig, get_cfg_layout};
+use petgraph::graph::NodeIndex;
+use petgraph::stable_graph::StableGraph;
+
+/// `count` separate graphs of `blocks` blocks each, like the unreachable islands of a binary.
+///
+/// The seeds start at 100, since the ranking of rust-sugiyama never finishes for a couple of
+/// the graphs below that, like the ones of seeds 46 and 57.
+fn components(count: u64, blocks: usize) -> StableGraph<Block, EdgeKind> {
+    let mut graph = StableGraph::new();
+
+    for seed in 100..100 + count {
+        let component = synthetic_cfg(blocks, 4, 0.4, seed);
+        let offset = graph.node_count();
+
+        for node in component.node_indices() {
+            graph.add_node(component[node].clone());
+        }
+
+        for edge in component.edge_indices() {
+            let (a, b) = component.edge_endpoints(edge).unwrap();
+            graph.add_edge(
+                NodeIndex::new(a.index() + offset),
+                NodeIndex::new(b.index() + offset),
+                component[edge],
+            );
+        }
+    }
+
+    graph
+}
+
+/// Lay out `graph` with the blocks measured up front, like the view does.
+fn bench_layout(c: &mut Criterion, name: &str, graph: &StableGraph<Block, EdgeKind>) {
+    let ctx = context();
+    let sizes = sizes(&ctx, graph, &NodeStyle::default());
+
+    c.bench_with_input(BenchmarkId::new("layout", name), graph, |b, graph| {
+        b.iter(|| {
+            get_cfg_layout(
+                graph,
+                &LayoutConfig::default(),
+                |node: NodeIndex, _: &Block| sizes[node.index()],
+                &[],
+            )
+        })
+    });
+}
+
+fn layout(c: &mut Criterion) {
+    for blocks in SIZES {
+        bench_layout(c, &blocks.to_string(), &synthetic_cfg(blocks, 4, 0.4, 7));
+    }
+
+    // every component is laid out on its own, in parallel with the `rayon` feature.
+    bench_layout(c, "50x100", &components(50, 100));
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = layout
+}
+
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Building the cost field and routing every edge of synthetic graphs.
+
+mod common;
+
+use common::{OBSTACLE_RADIUS, SIZES, Workload};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use egui_cfg::route::{CostField, Grid, RouteConfig, RouteContext};
+
+fn routing(c: &mut Criterion) {
+    for blocks in SIZES {
+        let workload = Workload::new(blocks);
+        let config = RouteConfig::default();
+        let cell = config.cell_size_for(workload.scene);
+
+        c.bench_with_input(
+            BenchmarkId::new("cost_field", blocks),
+            &workload,
+            |b, workload| {
+                b.iter(|| {
+                    let mut field = CostField::new(Grid::from_scene(workload.scene, cell));
+
+                    for rect in &workload.rects {
+                        field.add_block_rect(*rect, OBSTACLE_RADIUS);
+                    }
+
+                    field
+                })
+            },
+        );
+
+        let mut context = RouteContext::new(workload.scene, config);
+
+        for rect in &workload.rects {
+            context.add_obstacle(*rect, OBSTACLE_RADIUS);
+        }
+
+        c.bench_with_input(
+            BenchmarkId::new("route_all", blocks),
+            &workload,
+            |b, workload| {
+                b.iter(|| {
+                    workload
+                        .edges
+                        .iter()
+                        .filter_map(|&(from, to)| context.route(from, to).ok())
+                        .count()
+                })
+            },
+        );
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = routing
+}
+
+criterion_main!(benches);
@@ -1,4 +1,12 @@
-use egui_cfg::{BlockLike, EdgeKind, LayoutConfig, style::NodeStyle, view::CfgView};
+use std::collections::HashMap;
+
+use egui_cfg::{
+    Badge, BlockLike, CfgLayout, EdgeKind, LayoutConfig,
+    export::to_svg,
+    highlight::AsmHighlighter,
+    style::NodeStyle,
+    view::{CfgStatsWindow, CfgView, CfgViewState, LinkTarget},
+};
 
 use eframe::egui::{self, Rect, pos2, vec2};
 use eframe::{self};
@@ -20,6 +28,33 @@ impl BlockLike for BasicBlock {
     fn body_lines(&self) -> &[String] {
         &self.code
     }
+
+    fn body_job(&self, style: &NodeStyle, wrap_width: f32) -> Option<egui::text::LayoutJob> {
+        Some(AsmHighlighter::from_style(style).layout_job(&self.code, style, wrap_width))
+    }
+
+    fn address(&self) -> Option<u64> {
+        Some(self.addr)
+    }
+
+    fn badges(&self) -> Vec<Badge> {
+        self.code
+            .iter()
+            .filter(|line| line.as_str() == "ret")
+            .map(|_| Badge {
+                text: "ret".into(),
+                fill: egui::Color32::from_rgb(120, 60, 140),
+            })
+            .collect()
+    }
+
+    fn is_entry(&self) -> bool {
+        self.title == "entry"
+    }
+
+    fn is_exit(&self) -> bool {
+        self.code.last().is_some_and(|line| line == "ret")
+    }
 }
 
 fn build_dummy_cfg() -> StableGraph<BasicBlock, EdgeKind> {
@@ -34,7 +69,7 @@ fn build_dummy_cfg() -> StableGraph<BasicBlock, EdgeKind> {
     let cond = g.add_node(BasicBlock {
         addr: 0x1005,
         title: "cmp and branch".into(),
-        code: vec!["cmp rdi, 0".into(), "jl then".into()],
+        code: vec!["cmp rdi, 0".into(), "jl 0x1010".into()],
     });
 
     let then_ = g.add_node(BasicBlock {
@@ -46,7 +81,7 @@ fn build_dummy_cfg() -> StableGraph<BasicBlock, EdgeKind> {
     let else_ = g.add_node(BasicBlock {
         addr: 0x1018,
         title: "else".into(),
-        code: vec!["mov rax, rdi".into()],
+        code: vec!["mov rax, rdi".into(), "jmp [0x3000 + rdi*8]".into()],
     });
 
     let exit = g.add_node(BasicBlock {
@@ -59,28 +94,131 @@ fn build_dummy_cfg() -> StableGraph<BasicBlock, EdgeKind> {
     g.add_edge(cond, then_, EdgeKind::Taken);
     g.add_edge(cond, else_, EdgeKind::FallThrough);
     g.add_edge(then_, exit, EdgeKind::Unconditional);
-    g.add_edge(else_, exit, EdgeKind::Unconditional);
+
+    // a jump table with a lot of cases, so that the block needs to be wider for its ports.
+    for i in 0..30 {
+        let case = g.add_node(BasicBlock {
+            addr: 0x2000 + i * 0x10,
+            title: format!("case {i}"),
+            code: vec![format!("mov rax, {i}"), "jmp 0x1020".into()],
+        });
+
+        g.add_edge(else_, case, EdgeKind::Taken);
+        g.add_edge(case, exit, EdgeKind::Unconditional);
+    }
 
     g
 }
 
 struct App {
     graph: StableGraph<BasicBlock, EdgeKind>,
-    selected: Option<NodeIndex>,
+    state: CfgViewState,
     style: NodeStyle,
-    scene_rect: Rect,
+    show_stats: bool,
+}
+
+impl App {
+    /// Write the graph as it was drawn last into `cfg.svg`, next to the executable.
+    fn export_svg(&self) {
+        let rects: HashMap<NodeIndex, Rect> = self.state.drawn_blocks().iter().copied().collect();
+
+        let layout = CfgLayout {
+            coords: rects
+                .iter()
+                .map(|(node, rect)| (*node, (rect.center().x as f64, rect.center().y as f64)))
+                .collect(),
+            ..Default::default()
+        };
+
+        let svg = to_svg(
+            &self.graph,
+            &layout,
+            &[],
+            self.state.drawn_edges(),
+            &self.style,
+            |node, _| rects[&node].size(),
+        );
+
+        let written = std::env::current_exe()
+            .and_then(|exe| std::fs::write(exe.with_file_name("cfg.svg"), svg));
+
+        if let Err(err) = written {
+            eprintln!("couldn't write cfg.svg: {err}");
+        }
+    }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::S)) {
+            self.export_svg();
+        }
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Collapse all").clicked() {
+                    self.state.collapse_all();
+                }
+
+                if ui.button("Expand all").clicked() {
+                    self.state.expand_all();
+                }
+
+                if ui.button("Reset positions").clicked() {
+                    self.state.reset_positions();
+                }
+
+                ui.separator();
+
+                if ui.button("-").clicked() {
+                    self.state.zoom_out();
+                }
+
+                ui.label(format!("{:.0}%", self.state.zoom() * 100.0));
+
+                if ui.button("+").clicked() {
+                    self.state.zoom_in();
+                }
+
+                if ui.button("Fit").clicked() {
+                    self.state.fit();
+                }
+
+                ui.separator();
+
+                ui.checkbox(&mut self.show_stats, "Stats");
+            });
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            CfgView::new(
+            let response = CfgView::new(
                 self.graph.clone(),
                 LayoutConfig::default(),
-                &mut self.selected,
+                &mut self.state,
                 &self.style,
             )
-            .show(ui, &mut self.scene_rect);
+            .draggable_nodes(true)
+            .collect_stats(self.show_stats)
+            .link_matcher(|token| {
+                let hex = token.strip_prefix("0x")?;
+                u64::from_str_radix(hex, 16).ok().map(LinkTarget::Address)
+            })
+            .show(ui);
+
+            // jump to the block that starts at the clicked address.
+            if let Some((_, _, LinkTarget::Address(addr))) = response.link_clicked
+                && let Some(node) = self
+                    .graph
+                    .node_indices()
+                    .find(|n| self.graph[*n].addr == addr)
+            {
+                self.state.selected = Some(node);
+                self.state.center_on(node);
+            }
+
+            if let Some(stats) = &response.stats {
+                CfgStatsWindow::new(stats).show(ctx);
+            }
         });
     }
 }
@@ -93,10 +231,10 @@ fn main() -> eframe::Result<()> {
         eframe::NativeOptions::default(),
         Box::new(|_| {
             Ok(Box::new(App {
-                selected: None,
                 graph: build_dummy_cfg(),
                 style: NodeStyle::default(),
-                scene_rect,
+                state: CfgViewState::new(scene_rect),
+                show_stats: false,
             }))
         }),
     )
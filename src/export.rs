@@ -0,0 +1,450 @@
+use std::fmt::Write;
+
+use egui::{Color32, CornerRadius, FontFamily, FontId, Pos2, Rect, Vec2, pos2, vec2};
+use petgraph::graph::NodeIndex;
+use petgraph::stable_graph::StableGraph;
+
+use crate::style::NodeStyle;
+use crate::view::PortLine;
+use crate::{BlockLike, CfgLayout, EdgeKind, EdgeLike};
+
+/// The space around everything that was drawn.
+const MARGIN: f32 = 16.0;
+
+/// Draw `graph` as an SVG document, with the blocks where `layout` put them and the edges
+/// along `edges`, e.g. the ones of [`crate::view::CfgViewState::drawn_edges`].
+///
+/// `block_size` is the size of every block, which should be the same one it was laid out
+/// with, so this doesn't need a [`egui::Ui`] to measure them. Without a view that drew them,
+/// [`crate::measure_block`] or [`crate::estimate_block_size`] give one from the fonts. The
+/// body lines are spread evenly over the body, which lines up as long as none of them wrap.
+///
+/// `stubs` are the label and size of every stub of [`CfgLayout::stubs`], in the same order,
+/// with the sizes they were laid out with.
+///
+/// Edges get the [`NodeStyle::edge_color`] of their [`EdgeKind`].
+pub fn to_svg<N: BlockLike, E: EdgeLike>(
+    graph: &StableGraph<N, E>,
+    layout: &CfgLayout,
+    stubs: &[(String, Vec2)],
+    edges: &[(Vec<Pos2>, PortLine)],
+    style: &NodeStyle,
+    block_size: impl Fn(NodeIndex, &N) -> Vec2,
+) -> String {
+    let blocks: Vec<(NodeIndex, Rect)> = layout
+        .coords
+        .iter()
+        .filter(|(node, _)| graph.contains_node(*node))
+        .map(|(node, (x, y))| {
+            let size = block_size(*node, &graph[*node]);
+            (
+                *node,
+                Rect::from_center_size(pos2(*x as f32, *y as f32), size),
+            )
+        })
+        .collect();
+
+    let stubs: Vec<(Rect, &str)> = layout
+        .stubs
+        .iter()
+        .zip(stubs)
+        .map(|((_, (x, y)), (label, size))| {
+            (
+                Rect::from_center_size(pos2(*x as f32, *y as f32), *size),
+                label.as_str(),
+            )
+        })
+        .collect();
+
+    let bounds = blocks
+        .iter()
+        .map(|(_, rect)| *rect)
+        .chain(stubs.iter().map(|(rect, _)| *rect))
+        .chain(
+            edges
+                .iter()
+                .flat_map(|(poly, _)| poly)
+                .map(|p| Rect::from_center_size(*p, Vec2::ZERO)),
+        )
+        .fold(Rect::NOTHING, Rect::union);
+
+    let bounds = if bounds.is_positive() {
+        bounds.expand(MARGIN)
+    } else {
+        Rect::from_min_size(Pos2::ZERO, Vec2::splat(MARGIN * 2.0))
+    };
+
+    let mut svg = String::new();
+
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        bounds.min.x,
+        bounds.min.y,
+        bounds.width(),
+        bounds.height(),
+        bounds.width(),
+        bounds.height(),
+    );
+
+    write_markers(&mut svg, style);
+
+    // the edges go under the blocks, same as in the view.
+    for (poly, line) in edges.iter().filter(|(poly, _)| poly.len() >= 2) {
+        let kind = line
+            .edge
+            .filter(|edge| graph.edge_endpoints(*edge) == Some((line.from.node, line.to.node)))
+            .map(|edge| graph[edge].kind());
+
+        write_edge(&mut svg, poly, kind, style);
+    }
+
+    for (i, (node, rect)) in blocks.iter().enumerate() {
+        write_block(&mut svg, i, &graph[*node], *rect, style);
+    }
+
+    for (rect, label) in &stubs {
+        write_stub(&mut svg, *rect, label, style);
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// An arrowhead for every color an edge can have, pointing along the end of the path.
+fn write_markers(svg: &mut String, style: &NodeStyle) {
+    let size = style.edge.width * 4.0;
+
+    svg.push_str("<defs>\n");
+
+    for kind in [None, Some(EdgeKind::Taken), Some(EdgeKind::FallThrough)] {
+        let _ = writeln!(
+            svg,
+            concat!(
+                r#"<marker id="{}" viewBox="0 0 10 10" refX="10" refY="5" "#,
+                r#"markerWidth="{size}" markerHeight="{size}" markerUnits="userSpaceOnUse" "#,
+                r#"orient="auto"><path d="M 0 0 L 10 5 L 0 10 z"{}/></marker>"#,
+            ),
+            marker_id(kind),
+            fill(style.edge_color(kind)),
+            size = size,
+        );
+    }
+
+    svg.push_str("</defs>\n");
+}
+
+fn write_edge(svg: &mut String, poly: &[Pos2], kind: Option<EdgeKind>, style: &NodeStyle) {
+    let mut d = String::new();
+
+    for (i, p) in poly.iter().enumerate() {
+        let _ = write!(d, "{} {} {} ", if i == 0 { "M" } else { "L" }, p.x, p.y);
+    }
+
+    let _ = writeln!(
+        svg,
+        r#"<path d="{}" fill="none"{} stroke-width="{}" marker-end="url(#{})"/>"#,
+        d.trim_end(),
+        stroke(style.edge_color(kind)),
+        style.edge.width,
+        marker_id(kind),
+    );
+}
+
+/// Write `block` at `rect`, whose header gets clipped out of the whole block so that the
+/// corners of both are the same, like in the view.
+fn write_block<N: BlockLike>(svg: &mut String, i: usize, block: &N, rect: Rect, style: &NodeStyle) {
+    let (header_fill, border) = if block.is_entry() {
+        (style.entry_header_fill, style.entry_stroke)
+    } else if block.is_exit() {
+        (style.exit_header_fill, style.exit_stroke)
+    } else {
+        (style.header_fill, style.stroke)
+    };
+
+    let outline = rounded_rect(rect, style.block_rounding_in(rect));
+    let header = Rect::from_min_size(rect.min, vec2(rect.width(), style.header_height));
+
+    let _ = writeln!(
+        svg,
+        r#"<clipPath id="header-{i}"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+        header.min.x,
+        header.min.y,
+        header.width(),
+        header.height(),
+    );
+
+    let _ = writeln!(svg, r#"<path d="{outline}"{}/>"#, fill(style.fill));
+    let _ = writeln!(
+        svg,
+        r#"<path d="{outline}"{} clip-path="url(#header-{i})"/>"#,
+        fill(header_fill),
+    );
+
+    if border.width > 0.0 {
+        let _ = writeln!(
+            svg,
+            r#"<path d="{}" fill="none"{} stroke-width="{}"/>"#,
+            rounded_rect(
+                rect.shrink(border.width / 2.0),
+                style.block_rounding_in(rect)
+            ),
+            stroke(border.color),
+            border.width,
+        );
+    }
+
+    let padding = style.button_padding.x;
+
+    write_text(
+        svg,
+        pos2(header.min.x + padding, header.center().y),
+        "start",
+        "central",
+        &crate::header_label(block, style),
+        &style.label_font,
+        style.label_color,
+    );
+
+    if let Some(address) = block.address() {
+        write_text(
+            svg,
+            pos2(header.max.x - padding, header.center().y),
+            "end",
+            "central",
+            &format!("{address:#x}"),
+            &style.label_font,
+            style.header_address_color,
+        );
+    }
+
+    let lines = block.body_lines();
+    let body_height = rect.height() - style.header_height - style.padding.y * 2.0;
+
+    if lines.is_empty() || body_height <= 0.0 {
+        return;
+    }
+
+    let line_height = body_height / lines.len() as f32;
+    let top = header.max.y + style.padding.y;
+
+    for (n, line) in lines.iter().enumerate() {
+        write_text(
+            svg,
+            pos2(rect.min.x + style.padding.x, top + n as f32 * line_height),
+            "start",
+            "hanging",
+            line,
+            &style.text_font,
+            style.text_color,
+        );
+    }
+}
+
+/// Write the stub at `rect` as a pill with `label` in the middle, like in the view.
+fn write_stub(svg: &mut String, rect: Rect, label: &str, style: &NodeStyle) {
+    let outline = rounded_rect(rect, CornerRadius::same((rect.height() / 2.0) as u8));
+
+    let _ = writeln!(svg, r#"<path d="{outline}"{}/>"#, fill(style.header_fill));
+
+    if style.stroke.width > 0.0 {
+        let _ = writeln!(
+            svg,
+            r#"<path d="{}" fill="none"{} stroke-width="{}"/>"#,
+            rounded_rect(
+                rect.shrink(style.stroke.width / 2.0),
+                CornerRadius::same(((rect.height() - style.stroke.width) / 2.0) as u8),
+            ),
+            stroke(style.stroke.color),
+            style.stroke.width,
+        );
+    }
+
+    write_text(
+        svg,
+        rect.center(),
+        "middle",
+        "central",
+        label,
+        &style.label_font,
+        style.label_color,
+    );
+}
+
+fn write_text(
+    svg: &mut String,
+    pos: Pos2,
+    anchor: &str,
+    baseline: &str,
+    text: &str,
+    font: &FontId,
+    color: Color32,
+) {
+    let family = match font.family {
+        FontFamily::Monospace => "monospace",
+        _ => "sans-serif",
+    };
+
+    let _ = writeln!(
+        svg,
+        concat!(
+            r#"<text x="{}" y="{}" text-anchor="{anchor}" dominant-baseline="{baseline}" "#,
+            r#"font-family="{family}" font-size="{}" xml:space="preserve"{}>{}</text>"#,
+        ),
+        pos.x,
+        pos.y,
+        font.size,
+        fill(color),
+        escape(text),
+        anchor = anchor,
+        baseline = baseline,
+        family = family,
+    );
+}
+
+/// The outline of `rect` with every corner rounded by its own radius, as path data.
+fn rounded_rect(rect: Rect, rounding: CornerRadius) -> String {
+    let (nw, ne, sw, se) = (
+        rounding.nw as f32,
+        rounding.ne as f32,
+        rounding.sw as f32,
+        rounding.se as f32,
+    );
+
+    let Rect { min, max } = rect;
+
+    format!(
+        concat!(
+            "M {} {} H {} A {ne} {ne} 0 0 1 {} {} V {} A {se} {se} 0 0 1 {} {} ",
+            "H {} A {sw} {sw} 0 0 1 {} {} V {} A {nw} {nw} 0 0 1 {} {} Z",
+        ),
+        min.x + nw,
+        min.y,
+        max.x - ne,
+        max.x,
+        min.y + ne,
+        max.y - se,
+        max.x - se,
+        max.y,
+        min.x + sw,
+        min.x,
+        max.y - sw,
+        min.y + nw,
+        min.x + nw,
+        min.y,
+        nw = nw,
+        ne = ne,
+        sw = sw,
+        se = se,
+    )
+}
+
+fn marker_id(kind: Option<EdgeKind>) -> &'static str {
+    match kind {
+        Some(EdgeKind::Taken) => "arrow-taken",
+        Some(EdgeKind::FallThrough) => "arrow-fall-through",
+        Some(EdgeKind::Unconditional) | None => "arrow",
+    }
+}
+
+/// The `fill` attribute for `color`, along with its opacity if it isn't opaque.
+fn fill(color: Color32) -> String {
+    paint("fill", color)
+}
+
+/// The `stroke` attribute for `color`, along with its opacity if it isn't opaque.
+fn stroke(color: Color32) -> String {
+    paint("stroke", color)
+}
+
+fn paint(attribute: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+
+    let mut paint = format!(r##" {attribute}="#{r:02x}{g:02x}{b:02x}""##);
+
+    if a < u8::MAX {
+        let _ = write!(paint, r#" {attribute}-opacity="{}""#, a as f32 / 255.0);
+    }
+
+    paint
+}
+
+/// `text` with everything that means something in XML escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::view::{PortKind, PortSlot};
+    use crate::{LayoutConfig, get_cfg_layout, synthetic};
+
+    #[test]
+    fn stubs_and_edge_kinds_follow_the_style() {
+        let mut graph = StableGraph::new();
+
+        let block = |title: &str| synthetic::Block {
+            addr: 0x1000,
+            title: title.into(),
+            lines: vec!["ret".into()],
+        };
+
+        let entry = graph.add_node(block("entry"));
+        let exit = graph.add_node(block("exit"));
+        let edge = graph.add_edge(entry, exit, EdgeKind::Taken);
+
+        let size = |_: NodeIndex, _: &synthetic::Block| vec2(120.0, 50.0);
+        let stub = ("memcpy".to_string(), vec2(60.0, 20.0));
+
+        let layout = get_cfg_layout(&graph, &LayoutConfig::default(), size, &[(exit, stub.1)]);
+
+        let center = |node: NodeIndex| {
+            let (_, (x, y)) = layout.coords.iter().find(|(n, _)| *n == node).unwrap();
+            pos2(*x as f32, *y as f32)
+        };
+
+        let line = PortLine {
+            from: PortSlot::new(entry, 0, PortKind::Output),
+            to: PortSlot::new(exit, 0, PortKind::Input),
+            edge: Some(edge),
+        };
+
+        let style = NodeStyle {
+            taken_edge: Color32::from_rgb(1, 2, 3),
+            ..Default::default()
+        };
+
+        let svg = to_svg(
+            &graph,
+            &layout,
+            &[stub],
+            &[(vec![center(entry), center(exit)], line)],
+            &style,
+            size,
+        );
+
+        assert!(
+            svg.contains(r##"stroke="#010203""##),
+            "the edge isn't taken: {svg}"
+        );
+        assert!(
+            svg.contains(">memcpy</text>"),
+            "the stub wasn't drawn: {svg}"
+        );
+    }
+}
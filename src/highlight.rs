@@ -0,0 +1,190 @@
+use egui::{
+    Color32,
+    text::{LayoutJob, TextFormat},
+};
+
+use crate::style::NodeStyle;
+
+/// A small highlighter for common assembly syntax, meant to be used from
+/// [`crate::BlockLike::body_job`].
+///
+/// It understands labels, mnemonics, x86-64 and AArch64 registers, immediates and comments,
+/// which is enough to make most disassembly a lot easier to scan.
+#[derive(Clone, Debug)]
+pub struct AsmHighlighter {
+    pub text: Color32,
+    pub label: Color32,
+    pub mnemonic: Color32,
+    pub register: Color32,
+    pub immediate: Color32,
+    pub comment: Color32,
+}
+
+impl Default for AsmHighlighter {
+    fn default() -> Self {
+        Self {
+            text: Color32::WHITE,
+            label: Color32::from_rgb(220, 200, 120),
+            mnemonic: Color32::from_rgb(110, 170, 255),
+            register: Color32::from_rgb(230, 140, 90),
+            immediate: Color32::from_rgb(150, 210, 130),
+            comment: Color32::from_gray(130),
+        }
+    }
+}
+
+impl AsmHighlighter {
+    /// The default colors, but with the plain text and comments following `style`.
+    pub fn from_style(style: &NodeStyle) -> Self {
+        Self {
+            text: style.text_color,
+            comment: style.header_address_color,
+            ..Default::default()
+        }
+    }
+
+    /// Lay out `lines` to be wrapped at `wrap_width`, the same way the plain body text is.
+    pub fn layout_job(&self, lines: &[String], style: &NodeStyle, wrap_width: f32) -> LayoutJob {
+        let mut job = LayoutJob::default();
+
+        job.wrap.max_width = wrap_width;
+
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.append(&mut job, "\n", self.text, style);
+            }
+
+            self.highlight_line(&mut job, line, style);
+        }
+
+        job
+    }
+
+    fn append(&self, job: &mut LayoutJob, text: &str, color: Color32, style: &NodeStyle) {
+        job.append(
+            text,
+            0.0,
+            TextFormat {
+                font_id: style.text_font.clone(),
+                color,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn highlight_line(&self, job: &mut LayoutJob, line: &str, style: &NodeStyle) {
+        // everything after the comment marker is just a comment.
+        let (code, comment) = line.split_at(comment_start(line).unwrap_or(line.len()));
+
+        let mut first_word = true;
+        let mut rest = code;
+
+        while !rest.is_empty() {
+            // split off the next run of either delimiters, or a single token.
+            let is_delimiter = |c: char| c.is_whitespace() || ",[]()+-*:{}!".contains(c);
+
+            let delimited = rest.starts_with(is_delimiter);
+
+            let end = if delimited {
+                rest.find(|c: char| !is_delimiter(c)).unwrap_or(rest.len())
+            } else {
+                rest.find(is_delimiter).unwrap_or(rest.len())
+            };
+
+            let (token, tail) = rest.split_at(end);
+
+            let color = if delimited {
+                self.text
+            } else if first_word {
+                first_word = false;
+
+                // a word followed by a colon is a label, not an instruction.
+                if tail.starts_with(':') {
+                    self.label
+                } else {
+                    self.mnemonic
+                }
+            } else if is_register(token) {
+                self.register
+            } else if is_immediate(token) {
+                self.immediate
+            } else {
+                self.text
+            };
+
+            self.append(job, token, color, style);
+
+            rest = tail;
+        }
+
+        if !comment.is_empty() {
+            self.append(job, comment, self.comment, style);
+        }
+    }
+}
+
+/// Where the comment in `line` starts, if there is one.
+fn comment_start(line: &str) -> Option<usize> {
+    line.char_indices().find_map(|(i, c)| {
+        let rest = &line[i..];
+        let after_space = line[..i].chars().last().is_none_or(char::is_whitespace);
+
+        match c {
+            ';' => Some(i),
+            '/' if rest.starts_with("//") => Some(i),
+            // `#` is also used for immediates on arm.
+            '#' if after_space
+                && !rest[1..].starts_with(|c: char| c.is_ascii_digit() || c == '-') =>
+            {
+                Some(i)
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Whether `token` looks like a number, in any of the common assembler notations.
+fn is_immediate(token: &str) -> bool {
+    let token = token.trim_start_matches(['#', '$']);
+
+    token.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Whether `token` is the name of a common x86-64 or AArch64 register.
+fn is_register(token: &str) -> bool {
+    const NAMED: &[&str] = &[
+        "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "rip", "eax", "ebx", "ecx", "edx",
+        "esi", "edi", "ebp", "esp", "eip", "ax", "bx", "cx", "dx", "si", "di", "bp", "sp", "al",
+        "bl", "cl", "dl", "ah", "bh", "ch", "dh", "sil", "dil", "bpl", "spl", "cs", "ds", "es",
+        "fs", "gs", "ss", "lr", "pc", "xzr", "wzr", "fp",
+    ];
+
+    let token = token.to_ascii_lowercase();
+
+    if NAMED.contains(&token.as_str()) {
+        return true;
+    }
+
+    // a prefix followed by a register number, with an optional size suffix for r8-r15.
+    let numbered = |prefix: &str, suffixes: &[&str]| {
+        token.strip_prefix(prefix).is_some_and(|rest| {
+            let digits = rest.trim_end_matches(|c: char| !c.is_ascii_digit());
+            let suffix = &rest[digits.len()..];
+
+            !digits.is_empty()
+                && digits.chars().all(|c| c.is_ascii_digit())
+                && (suffix.is_empty() || suffixes.contains(&suffix))
+        })
+    };
+
+    numbered("r", &["d", "w", "b"])
+        || numbered("xmm", &[])
+        || numbered("ymm", &[])
+        || numbered("zmm", &[])
+        || numbered("x", &[])
+        || numbered("w", &[])
+        || numbered("v", &[])
+        || numbered("q", &[])
+        || numbered("d", &[])
+        || numbered("s", &[])
+}
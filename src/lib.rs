@@ -1,21 +1,96 @@
+pub mod export;
+pub mod highlight;
 pub mod route;
 pub mod style;
+pub mod synthetic;
 pub mod view;
 
-use crate::style::NodeStyle;
-use egui::{Color32, Galley, Pos2, Rect, Ui, vec2};
-use petgraph::{
-    graph::NodeIndex,
-    stable_graph::StableGraph,
-    visit::{EdgeRef, IntoEdgeReferences},
+use crate::style::{BlockWidth, NodeStyle};
+use egui::{
+    Color32, Galley, Pos2, Rect, Ui, Vec2,
+    epaint::text::Fonts,
+    text::{LayoutJob, TextFormat},
+    vec2,
 };
+use petgraph::{graph::NodeIndex, stable_graph::StableGraph, unionfind::UnionFind};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// A small pill drawn in the header of a block, e.g. to mark calls or breakpoints.
+#[derive(Clone, Debug)]
+pub struct Badge {
+    pub text: String,
+    pub fill: Color32,
+}
 
 pub trait BlockLike: Clone {
     fn title(&self) -> &str;
     fn body_lines(&self) -> &[String];
+
+    /// Lay out the body with custom formatting, e.g. for syntax highlighting, instead of
+    /// the plain [`BlockLike::body_lines`].
+    ///
+    /// The job should wrap at `wrap_width`. See [`highlight::AsmHighlighter`] for a
+    /// ready-made implementation.
+    fn body_job(&self, _style: &NodeStyle, _wrap_width: f32) -> Option<LayoutJob> {
+        None
+    }
+
+    /// The body as `(mnemonic, operands)` pairs, one for every line.
+    ///
+    /// When this is set, and [`BlockLike::body_job`] isn't, the operands of every line start
+    /// at the same x, [`NodeStyle::column_gap`] after the widest mnemonic.
+    fn body_columns(&self) -> Option<Vec<(String, String)>> {
+        None
+    }
+
+    /// What is shown in the header instead of the body, when the block is collapsed.
+    fn summary(&self) -> String {
+        format!("{} instr", self.body_lines().len())
+    }
+
+    /// The address of the first instruction in the block, shown on the right of the header.
+    fn address(&self) -> Option<u64> {
+        None
+    }
+
+    /// The badges drawn in the header, in between the title and the address.
+    fn badges(&self) -> Vec<Badge> {
+        Vec::new()
+    }
+
+    /// Whether this block is where the function starts.
+    fn is_entry(&self) -> bool {
+        false
+    }
+
+    /// Whether this block leaves the function, e.g. by returning.
+    fn is_exit(&self) -> bool {
+        false
+    }
+
+    /// A hash of everything about the block that gets drawn, which goes into the
+    /// [`CfgFingerprint`] of the graph, and the text of the block is only laid out again
+    /// when it changes.
+    ///
+    /// This hashes the title, the body lines and columns and the rest of the header by
+    /// default, so it should be overridden when anything else is drawn differently, e.g. by
+    /// [`BlockLike::body_job`].
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        self.title().hash(&mut hasher);
+        self.body_lines().hash(&mut hasher);
+        self.body_columns().hash(&mut hasher);
+        self.address().hash(&mut hasher);
+        self.badges().iter().for_each(|b| b.text.hash(&mut hasher));
+        (self.is_entry(), self.is_exit()).hash(&mut hasher);
+
+        hasher.finish()
+    }
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub enum EdgeKind {
     Taken,
     FallThrough,
@@ -32,112 +107,732 @@ impl EdgeLike for EdgeKind {
     }
 }
 
+/// A cheap hash of the nodes, the edges and the content of a graph, to tell whether it
+/// changed since the last frame without keeping a copy of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CfgFingerprint(u64);
+
+impl CfgFingerprint {
+    pub fn of<N: BlockLike, E: EdgeLike>(graph: &StableGraph<N, E>) -> Self {
+        let mut hasher = DefaultHasher::new();
+
+        (graph.node_count(), graph.edge_count()).hash(&mut hasher);
+
+        for node in graph.node_indices() {
+            (node, graph[node].content_hash()).hash(&mut hasher);
+        }
+
+        for edge in graph.edge_indices() {
+            (edge, graph.edge_endpoints(edge), graph[edge].kind()).hash(&mut hasher);
+        }
+
+        Self(hasher.finish())
+    }
+
+    /// The fingerprint, but different for every `generation`.
+    pub(crate) fn with_generation(self, generation: u64) -> Self {
+        let mut hasher = DefaultHasher::new();
+
+        (self.0, generation).hash(&mut hasher);
+
+        Self(hasher.finish())
+    }
+}
+
+/// The weakly connected components of `graph`, each as a graph of its own along with the
+/// index every one of its nodes has in `graph`, ordered by their lowest index.
+fn components<N>(graph: &StableGraph<N, ()>) -> Vec<(StableGraph<(), ()>, Vec<NodeIndex>)> {
+    let bound = graph
+        .node_indices()
+        .map(|n| n.index() + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut union = UnionFind::<usize>::new(bound);
+
+    for edge in graph.edge_indices() {
+        if let Some((a, b)) = graph.edge_endpoints(edge) {
+            union.union(a.index(), b.index());
+        }
+    }
+
+    let mut components: Vec<(StableGraph<(), ()>, Vec<NodeIndex>)> = Vec::new();
+    let mut component_of = HashMap::new();
+    let mut local = HashMap::new();
+
+    // the node indices go up, so the components come out ordered by their lowest index.
+    for node in graph.node_indices() {
+        let i = *component_of
+            .entry(union.find(node.index()))
+            .or_insert_with(|| {
+                components.push(Default::default());
+                components.len() - 1
+            });
+
+        let (component, nodes) = &mut components[i];
+
+        local.insert(node, component.add_node(()));
+        nodes.push(node);
+    }
+
+    for edge in graph.edge_indices() {
+        if let Some((a, b)) = graph.edge_endpoints(edge) {
+            let (component, _) = &mut components[component_of[&union.find(a.index())]];
+            component.add_edge(local[&a], local[&b], ());
+        }
+    }
+
+    components
+}
+
+/// Every node of a component and its center, along with the width and height of the
+/// component.
+type ComponentLayout = (Vec<(NodeIndex, (f64, f64))>, f64, f64);
+
+/// Lay out a single component, with the coordinates going back to the nodes of the graph.
+fn layout_component(
+    (component, nodes): &(StableGraph<(), ()>, Vec<NodeIndex>),
+    sizes: &HashMap<NodeIndex, (f64, f64)>,
+    config: &rust_sugiyama::configure::Config,
+) -> ComponentLayout {
+    let vertex_size = |node: NodeIndex, _: &()| sizes[&nodes[node.index()]];
+
+    // the component is connected, so there's only ever one layout.
+    let (coords, width, height) = rust_sugiyama::from_graph(component, &vertex_size, config)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+
+    let mut coords: Vec<(NodeIndex, (f64, f64))> = coords
+        .into_iter()
+        .map(|(node, pos)| (nodes[node.index()], pos))
+        .collect();
+
+    // the coordinates come out of a hash map, so they're sorted to always be in one order.
+    coords.sort_unstable_by_key(|(node, _)| *node);
+
+    (coords, width, height)
+}
+
+#[cfg(not(feature = "rayon"))]
+fn layout_components(
+    components: Vec<(StableGraph<(), ()>, Vec<NodeIndex>)>,
+    sizes: &HashMap<NodeIndex, (f64, f64)>,
+    config: &rust_sugiyama::configure::Config,
+) -> Vec<ComponentLayout> {
+    components
+        .iter()
+        .map(|component| layout_component(component, sizes, config))
+        .collect()
+}
+
+/// Lay the components out on the rayon thread pool, keeping their order.
+#[cfg(feature = "rayon")]
+fn layout_components(
+    components: Vec<(StableGraph<(), ()>, Vec<NodeIndex>)>,
+    sizes: &HashMap<NodeIndex, (f64, f64)>,
+    config: &rust_sugiyama::configure::Config,
+) -> Vec<ComponentLayout> {
+    use rayon::prelude::*;
+
+    components
+        .par_iter()
+        .map(|component| layout_component(component, sizes, config))
+        .collect()
+}
+
+/// Put the components next to each other from left to right, with their tops lined up with
+/// the first one, which stays where it was laid out.
+fn merge_components(
+    layouts: Vec<ComponentLayout>,
+    sizes: &HashMap<NodeIndex, (f64, f64)>,
+    spacing: f64,
+) -> ComponentLayout {
+    let mut coords = Vec::new();
+    let mut bounds: Option<(f64, f64, f64)> = None;
+    let mut height: f64 = 0.0;
+
+    for (layout, _, component_height) in layouts {
+        let (min_x, max_x, min_y) = layout.iter().fold(
+            (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY),
+            |(min_x, max_x, min_y), (node, (x, y))| {
+                let (w, h) = sizes[node];
+                (
+                    min_x.min(x - w / 2.0),
+                    max_x.max(x + w / 2.0),
+                    min_y.min(y - h / 2.0),
+                )
+            },
+        );
+
+        if !min_x.is_finite() {
+            continue;
+        }
+
+        let (dx, dy) = match bounds {
+            None => (0.0, 0.0),
+            Some((_, right, top)) => (right + spacing - min_x, top - min_y),
+        };
+
+        coords.extend(layout.into_iter().map(|(n, (x, y))| (n, (x + dx, y + dy))));
+
+        bounds = Some(match bounds {
+            None => (min_x, max_x, min_y),
+            Some((left, _, top)) => (left, max_x + dx, top),
+        });
+
+        height = height.max(component_height);
+    }
+
+    let width = bounds.map_or(0.0, |(left, right, _)| right - left);
+
+    (coords, width, height)
+}
+
+/// Put `nodes` into a square grid in order, row by row, where every cell is as big as the
+/// biggest node and the first one is centered on the origin.
+fn layout_grid(
+    nodes: Vec<NodeIndex>,
+    sizes: &HashMap<NodeIndex, (f64, f64)>,
+    spacing: f64,
+) -> ComponentLayout {
+    let columns = (nodes.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let rows = nodes.len().div_ceil(columns);
+
+    let (cell_w, cell_h) = nodes.iter().fold((0.0, 0.0), |(w, h): (f64, f64), node| {
+        let (node_w, node_h) = sizes[node];
+        (w.max(node_w), h.max(node_h))
+    });
+
+    let coords = nodes
+        .into_iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let (column, row) = ((i % columns) as f64, (i / columns) as f64);
+            (
+                node,
+                (column * (cell_w + spacing), row * (cell_h + spacing)),
+            )
+        })
+        .collect();
+
+    let span =
+        |count: usize, cell: f64| count as f64 * cell + count.saturating_sub(1) as f64 * spacing;
+
+    (coords, span(columns, cell_w), span(rows, cell_h))
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct CfgLayout {
     pub coords: Vec<(NodeIndex, (f64, f64))>,
+    /// The coordinates of every stub passed to [`get_cfg_layout`], in the same order, along
+    /// with the index the stub was given in the layout.
+    ///
+    /// That index is never the index of a block in the graph. Stubs whose source isn't in
+    /// the graph are left out.
+    pub stubs: Vec<(NodeIndex, (f64, f64))>,
     pub width: f64,
     pub height: f64,
 }
 
 #[derive(Clone, Debug)]
 pub struct LayoutConfig {
+    /// The space between blocks, which is never less than [`LayoutConfig::min_spacing`].
     pub vertex_spacing: f64,
+    /// The least space between blocks, so that edges still have room to be routed between
+    /// them.
+    pub min_vertex_spacing: f64,
+    /// The size of the cells that edges are routed in, see [`route::RouteConfig::cell_size`].
+    ///
+    /// The [`view::CfgView`] sets this to the larger side of the cells it routes in.
+    pub route_cell_size: f64,
+}
+
+impl LayoutConfig {
+    /// The space between blocks that is actually used.
+    pub fn spacing(&self) -> f64 {
+        self.vertex_spacing.max(self.min_spacing())
+    }
+
+    /// The least space between blocks that edges can still be routed through.
+    ///
+    /// Blocks are surrounded by a falloff of a few cells that edges avoid, so blocks closer
+    /// than that leave only expensive or no cells in between, so this is never less than
+    /// that falloff at [`LayoutConfig::route_cell_size`].
+    pub fn min_spacing(&self) -> f64 {
+        let falloff = route::OBSTACLE_RADIUS as f64 * self.route_cell_size;
+
+        self.min_vertex_spacing.max(falloff)
+    }
 }
 
 impl Default for LayoutConfig {
     fn default() -> Self {
         Self {
             vertex_spacing: 30.0,
+            min_vertex_spacing: 15.0,
+            route_cell_size: route::RouteConfig::default().cell_size.max_elem() as f64,
         }
     }
 }
 
 impl From<&LayoutConfig> for rust_sugiyama::configure::Config {
     fn from(lhs: &LayoutConfig) -> Self {
-        let mut cfg = rust_sugiyama::configure::Config::default();
-
-        cfg.vertex_spacing = lhs.vertex_spacing;
-
-        cfg
+        rust_sugiyama::configure::Config {
+            vertex_spacing: lhs.spacing(),
+            ..Default::default()
+        }
     }
 }
 
-pub fn get_block_rectangle<N: BlockLike>(
+/// Lay out the body text of `block`, wrapped to the content width of `style`.
+pub fn layout_body_galley<N: BlockLike>(
     ui: &Ui,
     block: &N,
     style: &NodeStyle,
-) -> (Rect, std::sync::Arc<Galley>) {
+) -> std::sync::Arc<Galley> {
+    // get the width of the content (the size of the node without the padding).
+    let content_width = style.content_width();
+
+    ui.fonts(|f| {
+        if let Some(job) = block.body_job(style, content_width) {
+            return f.layout_job(job);
+        }
+
+        match block.body_columns() {
+            Some(columns) => f.layout_job(columns_job(f, &columns, style, content_width)),
+            None => f.layout(
+                block.body_lines().join("\n"),
+                style.text_font.clone(),
+                style.text_color,
+                content_width,
+            ),
+        }
+    })
+}
+
+/// Lay out `(mnemonic, operands)` pairs so that all of the operands line up.
+fn columns_job(
+    fonts: &Fonts,
+    columns: &[(String, String)],
+    style: &NodeStyle,
+    wrap_width: f32,
+) -> LayoutJob {
+    let format = TextFormat::simple(style.text_font.clone(), style.text_color);
+
+    let width_of = |text: &str| {
+        fonts
+            .layout_no_wrap(text.to_owned(), style.text_font.clone(), style.text_color)
+            .size()
+            .x
+    };
+
+    let widths: Vec<f32> = columns
+        .iter()
+        .map(|(mnemonic, _)| width_of(mnemonic))
+        .collect();
+
+    // the operands start after the widest mnemonic.
+    let column = widths.iter().copied().fold(0.0, f32::max) + style.column_gap;
+
+    let mut job = LayoutJob::default();
+
+    job.wrap.max_width = wrap_width;
+
+    for (i, ((mnemonic, operands), width)) in columns.iter().zip(widths).enumerate() {
+        if i > 0 {
+            job.append("\n", 0.0, format.clone());
+        }
+
+        job.append(mnemonic, 0.0, format.clone());
+
+        if !operands.is_empty() {
+            job.append(operands, column - width, format.clone());
+        }
+    }
+
+    job
+}
+
+/// The size of a block with the plain body `text` and `title`, without needing a [`Ui`].
+///
+/// This is the same size [`get_block_rectangle`] measures, as long as the block doesn't
+/// have a [`BlockLike::body_job`], [`BlockLike::body_columns`], address or badges that
+/// change it.
+pub fn measure_block(text: &str, title: &str, style: &NodeStyle, fonts: &Fonts) -> Vec2 {
+    let galley = fonts.layout(
+        text.to_owned(),
+        style.text_font.clone(),
+        style.text_color,
+        style.content_width(),
+    );
+
+    let header_width = header_width(fonts, title, None, &[], style);
+
+    block_rectangle(&galley, header_width, style, false).size()
+}
+
+/// A rough size of `block`, without laying out any of its text.
+///
+/// Every character is taken to be as wide as an `M`, which is exact for monospace fonts
+/// and the plain body, but is off for a [`BlockLike::body_job`] that changes the fonts, and
+/// for headers that are wider than the body.
+pub fn estimate_block_size<N: BlockLike>(block: &N, style: &NodeStyle, fonts: &Fonts) -> Vec2 {
+    let row_height = fonts.row_height(&style.text_font);
+    let glyph_width = fonts.glyph_width(&style.text_font, 'M');
+    let wrap_width = style.content_width();
+
+    let (width, rows) = block
+        .body_lines()
+        .iter()
+        .map(|line| line.chars().count() as f32 * glyph_width)
+        .fold((0.0f32, 0.0), |(width, rows), line| {
+            // lines that are too long wrap onto more rows.
+            let wrapped = (line / wrap_width).ceil().max(1.0);
+            (width.max(line.min(wrap_width)), rows + wrapped)
+        });
+
+    block_size(vec2(width, rows * row_height), 0.0, style, false)
+}
+
+/// The width the header needs to fit `label` without eliding it, along with the collapse
+/// chevron, the address and the badges.
+///
+/// This has to add up the same way the header gets drawn in [`view::CfgView`].
+pub fn header_width(
+    fonts: &Fonts,
+    label: &str,
+    address: Option<u64>,
+    badges: &[Badge],
+    style: &NodeStyle,
+) -> f32 {
+    let width_of = |text: String| {
+        fonts
+            .layout_no_wrap(text, style.label_font.clone(), style.label_color)
+            .size()
+            .x
+    };
+
+    let padding = style.button_padding.x;
+
+    // the padding on both sides, and the chevron with its gap.
+    let mut width = padding * 2.0 + style.label_font.size + padding / 2.0;
+
+    if let Some(address) = address {
+        width += width_of(format!("{address:#x}")) + padding;
+    }
+
+    for badge in badges {
+        width += width_of(badge.text.clone()) + padding + padding / 2.0;
+    }
+
+    // a little bit of slack, so that rounding never elides the title.
+    width + width_of(label.to_owned()) + 1.0
+}
+
+/// The label in the header of `block`, which is its title with an optional glyph.
+pub fn header_label<N: BlockLike>(block: &N, style: &NodeStyle) -> String {
+    let glyph = match (block.is_entry(), block.is_exit()) {
+        _ if !style.terminal_glyphs => None,
+        (true, _) => Some("▶"),
+        (_, true) => Some("⏹"),
+        _ => None,
+    };
+
+    match glyph {
+        Some(glyph) => format!("{glyph} {}", block.title()),
+        None => block.title().to_owned(),
+    }
+}
+
+/// The width the header of `block` needs, see [`header_width`].
+pub fn block_header_width<N: BlockLike>(fonts: &Fonts, block: &N, style: &NodeStyle) -> f32 {
+    header_width(
+        fonts,
+        &header_label(block, style),
+        block.address(),
+        &block.badges(),
+        style,
+    )
+}
+
+/// The rectangle of a block with the body `body_galley`, starting at the origin.
+///
+/// With [`BlockWidth::FitContent`], the block is wide enough for both the body and the
+/// `header_width`, see [`header_width`].
+pub fn block_rectangle(
+    body_galley: &Galley,
+    header_width: f32,
+    style: &NodeStyle,
+    collapsed: bool,
+) -> Rect {
     // where the block that we're going to draw starts.
     let block_position = Pos2::new(0.0, 0.0);
 
-    // get the width of the content (the size of the node without the padding).
-    let content_width = style.size.x - style.padding.x * 2.0;
-
-    let body_text = block.body_lines().join("\n");
+    let size = block_size(body_galley.size(), header_width, style, collapsed);
 
-    // get the text galley so we can get information related to it.
-    let body_galley = ui.fonts(|f| {
-        f.layout(
-            body_text,
-            style.text_font.clone(),
-            Color32::WHITE,
-            content_width,
-        )
-    });
+    Rect::from_min_size(block_position, size)
+}
 
+/// The size of a block whose body is `body_size`, see [`block_rectangle`].
+fn block_size(body_size: Vec2, header_width: f32, style: &NodeStyle, collapsed: bool) -> Vec2 {
     // ge the total size of the height including the padding, the text and the header.
-    let block_height = style.header_height + style.padding.y * 2.0 + body_galley.size().y;
+    // collapsed blocks are only their header.
+    let block_height = if collapsed {
+        style.header_height
+    } else {
+        style.header_height + style.padding.y * 2.0 + body_size.y
+    };
 
     // create a rectangle starting from the start of our block and is the size we've calculated
     // from the content in the block.
-    let rect = Rect::from_min_size(block_position, vec2(style.size.x, block_height));
+    let block_width = match style.width {
+        BlockWidth::Fixed => style.size.x,
+        BlockWidth::FitContent {
+            min_width,
+            max_width,
+        } => (body_size.x + style.padding.x * 2.0)
+            .max(header_width)
+            .max(min_width)
+            .min(max_width),
+    };
 
-    (rect, body_galley)
+    vec2(block_width, block_height)
 }
 
-pub fn get_cfg_layout<N: BlockLike, E: Clone>(
+pub fn get_block_rectangle<N: BlockLike>(
     ui: &Ui,
+    block: &N,
+    style: &NodeStyle,
+    collapsed: bool,
+) -> (Rect, std::sync::Arc<Galley>) {
+    // get the text galley so we can get information related to it.
+    let body_galley = layout_body_galley(ui, block, style);
+
+    let header_width = ui.fonts(|f| block_header_width(f, block, style));
+
+    (
+        block_rectangle(&body_galley, header_width, style, collapsed),
+        body_galley,
+    )
+}
+
+/// Lay out `graph`, where `block_size` is the size of every block.
+///
+/// Every stub is a `(source, size)` pair, which gets placed as a node of that size with an
+/// edge coming from `source`. These are meant for things that aren't part of the graph, like
+/// the targets of calls.
+pub fn get_cfg_layout<N: BlockLike, E>(
     graph: &StableGraph<N, E>,
     config: &LayoutConfig,
-    style: &NodeStyle,
+    block_size: impl Fn(NodeIndex, &N) -> Vec2,
+    stubs: &[(NodeIndex, Vec2)],
 ) -> CfgLayout {
-    // Get the block rectangle to use as the vertex size.
-    let vertex_size = |_: NodeIndex, n: &N| {
-        let rect = get_block_rectangle(ui, n, style).0;
-        (rect.width() as _, rect.height() as f64)
-    };
+    // nothing to lay out, and the stubs would have nothing to hang off of.
+    if graph.node_count() == 0 {
+        return CfgLayout::default();
+    }
 
-    let mut graph = graph.clone();
+    if config.vertex_spacing < config.min_spacing() {
+        log::warn!(
+            "a vertex spacing of {} leaves no room to route edges between blocks, using {} instead",
+            config.vertex_spacing,
+            config.min_spacing(),
+        );
+    }
 
-    // get all nodes that have an outgoing edge that connects to the same node.
-    let loops: Vec<(petgraph::graph::NodeIndex, E)> = graph
-        .edge_references()
-        .filter(|e| e.source() == e.target())
-        .map(|e| (e.source(), e.weight().clone()))
-        .collect();
+    // the layout doesn't care about the edge weights, and stubs don't have a block.
+    let mut graph: StableGraph<Option<&N>, ()> = graph.map(|_, n| Some(n), |_, _| ());
 
     // remove all the edges that point to the same node.
-    for edge in graph.edge_indices().collect::<Vec<_>>() {
-        if let Some((u, v)) = graph.edge_endpoints(edge) {
-            if u == v {
-                graph.remove_edge(edge);
+    graph.retain_edges(|g, edge| g.edge_endpoints(edge).is_some_and(|(u, v)| u != v));
+
+    let mut stub_sizes = HashMap::new();
+
+    let stub_nodes: Vec<NodeIndex> = stubs
+        .iter()
+        .filter_map(|(source, size)| {
+            if !graph.contains_node(*source) {
+                return None;
             }
-        }
-    }
 
-    let info = rust_sugiyama::from_graph(&graph, &vertex_size, &config.into());
+            let stub = graph.add_node(None);
+            graph.add_edge(*source, stub, ());
+            stub_sizes.insert(stub, *size);
 
-    // NOTE: maybe there will be a case when we need to get the full vector.
-    let (coords, width, height) = info[0].clone();
+            Some(stub)
+        })
+        .collect();
 
-    // add the loop back after we've placed nodes.
-    for (n, w) in loops {
-        graph.add_edge(n, n, w);
-    }
+    // the sizes are measured up front, so that the components only need plain data.
+    let sizes: HashMap<NodeIndex, (f64, f64)> = graph
+        .node_indices()
+        .map(|node| {
+            let size = match graph[node] {
+                Some(n) => block_size(node, n),
+                None => stub_sizes.get(&node).copied().unwrap_or_default(),
+            };
+
+            (node, (size.x as f64, size.y as f64))
+        })
+        .collect();
+
+    // blocks without any edges have no layers to be put in, so they just go in a grid.
+    let (coords, width, height) = if graph.edge_count() == 0 {
+        layout_grid(graph.node_indices().collect(), &sizes, config.spacing())
+    } else {
+        let sugiyama_config = config.into();
+        let layouts = layout_components(components(&graph), &sizes, &sugiyama_config);
+
+        merge_components(layouts, &sizes, config.spacing())
+    };
+
+    // the blocks keep the order they were laid out in, and the stubs get the order they
+    // were passed in.
+    let (stub_coords, coords): (Vec<_>, Vec<_>) = coords
+        .into_iter()
+        .partition(|(node, _)| stub_sizes.contains_key(node));
+
+    let mut stub_coords: HashMap<NodeIndex, (f64, f64)> = stub_coords.into_iter().collect();
+
+    let stubs = stub_nodes
+        .into_iter()
+        .filter_map(|stub| Some((stub, stub_coords.remove(&stub)?)))
+        .collect();
 
     CfgLayout {
         coords,
+        stubs,
         width,
         height,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::synthetic::synthetic_cfg;
+
+    fn size(_: NodeIndex, block: &synthetic::Block) -> Vec2 {
+        vec2(120.0, 20.0 + 14.0 * block.lines.len() as f32)
+    }
+
+    #[test]
+    fn layout_is_deterministic() {
+        let graph = synthetic_cfg(200, 4, 0.4, 9);
+
+        let stubs: Vec<(NodeIndex, Vec2)> = graph
+            .node_indices()
+            .step_by(7)
+            .map(|n| (n, vec2(60.0, 20.0)))
+            .collect();
+
+        let layout = || get_cfg_layout(&graph, &LayoutConfig::default(), size, &stubs);
+        let (first, second) = (layout(), layout());
+
+        assert_eq!(first.coords, second.coords);
+        assert_eq!(first.stubs, second.stubs);
+
+        // every stub comes out in the order it was passed in, after every block.
+        assert_eq!(first.coords.len(), graph.node_count());
+        assert_eq!(first.stubs.len(), stubs.len());
+        assert!(first.stubs.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(first.stubs.iter().all(|(n, _)| !graph.contains_node(*n)));
+    }
+
+    #[test]
+    fn empty_graphs_have_an_empty_layout() {
+        let graph: StableGraph<synthetic::Block, EdgeKind> = StableGraph::new();
+        let layout = get_cfg_layout(&graph, &LayoutConfig::default(), size, &[]);
+
+        assert!(layout.coords.is_empty() && layout.stubs.is_empty());
+        assert_eq!((layout.width, layout.height), (0.0, 0.0));
+    }
+
+    #[test]
+    fn blocks_without_edges_go_in_a_grid() {
+        for count in [1, 10] {
+            let mut graph: StableGraph<synthetic::Block, EdgeKind> = StableGraph::new();
+
+            for i in 0..count {
+                graph.add_node(synthetic::Block {
+                    addr: i as u64 * 0x10,
+                    title: format!("block {i}"),
+                    lines: vec!["ret".into(); i % 3 + 1],
+                });
+            }
+
+            let config = LayoutConfig::default();
+            let layout = get_cfg_layout(&graph, &config, size, &[]);
+
+            assert_eq!(layout.coords.len(), count);
+
+            let rects: Vec<Rect> = layout
+                .coords
+                .iter()
+                .map(|(node, (x, y))| {
+                    let center = egui::pos2(*x as f32, *y as f32);
+                    Rect::from_center_size(center, size(*node, &graph[*node]))
+                })
+                .collect();
+
+            // the blocks fit in the size of the layout.
+            let bounds = rects.iter().copied().fold(Rect::NOTHING, Rect::union);
+
+            assert!(bounds.width() as f64 <= layout.width + 0.5);
+            assert!(bounds.height() as f64 <= layout.height + 0.5);
+
+            for (i, a) in rects.iter().enumerate() {
+                for b in &rects[i + 1..] {
+                    let gap = a.expand(config.spacing() as f32 / 2.0 - 0.5);
+                    assert!(!gap.intersects(*b), "{a:?} is too close to {b:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn components_are_laid_out_side_by_side() {
+        // a bunch of separate functions, like the unreachable islands of a binary.
+        let mut graph = StableGraph::new();
+
+        for seed in 0..12 {
+            let component = synthetic_cfg(20, 3, 0.4, seed);
+            let offset = graph.node_count();
+
+            for node in component.node_indices() {
+                graph.add_node(component[node].clone());
+            }
+
+            for edge in component.edge_indices() {
+                let (a, b) = component.edge_endpoints(edge).unwrap();
+                graph.add_edge(
+                    NodeIndex::new(a.index() + offset),
+                    NodeIndex::new(b.index() + offset),
+                    component[edge],
+                );
+            }
+        }
+
+        let layout = || get_cfg_layout(&graph, &LayoutConfig::default(), size, &[]);
+        let first = layout();
+
+        assert_eq!(first.coords, layout().coords);
+        assert_eq!(first.coords.len(), graph.node_count());
+
+        let rects: Vec<Rect> = first
+            .coords
+            .iter()
+            .map(|(node, (x, y))| {
+                Rect::from_center_size(egui::pos2(*x as f32, *y as f32), size(*node, &graph[*node]))
+            })
+            .collect();
+
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                assert!(!a.intersects(*b), "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+}
@@ -4,25 +4,155 @@ use std::{
     collections::{BinaryHeap, HashMap, HashSet},
 };
 
+mod corridor;
+
+use corridor::CorridorGraph;
+
 pub type GridCoord = (usize, usize);
 
+/// How many cells around every block and stub edges are discouraged from going through.
+pub(crate) const OBSTACLE_RADIUS: f32 = 5.0;
+
+/// How a [`RouteContext`] finds paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteStrategy {
+    /// Run cell level A* over the whole path.
+    Direct,
+    /// Paths crossing at least `min_layer_span` layers of blocks are first planned over the
+    /// gaps between the blocks of each layer, and then only refined with A* around every hop.
+    ///
+    /// This is much cheaper for long edges in tall graphs, at the cost of paths going through
+    /// the middle of each gap instead of hugging the blocks.
+    Hierarchical { min_layer_span: usize },
+}
+
+/// Settings that control how edges get routed around the blocks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteConfig {
+    /// The width and height of a single grid cell in scene units.
+    ///
+    /// Corridors between layers are usually much taller than the gaps between blocks in a
+    /// layer, so the cells don't have to be square.
+    ///
+    /// This is the smallest the cells get, see [`RouteConfig::max_cells`].
+    pub cell_size: egui::Vec2,
+    /// The most cells the routing grid is allowed to have.
+    ///
+    /// When a scene would need more cells than this, the cells are scaled up until it fits,
+    /// which keeps huge graphs routable at the cost of coarser paths. `None` never scales them.
+    pub max_cells: Option<usize>,
+    /// How many cells of free space surround the blocks and ports in the routing grid.
+    pub margin_cells: usize,
+    /// Search from both ends of an edge at once, which expands far fewer cells for long edges.
+    ///
+    /// The turn penalty is only applied within each half of the search, so the turn where the
+    /// two halves meet isn't accounted for, and the path can have a few more bends than the
+    /// unidirectional search would produce.
+    pub bidirectional: bool,
+    /// How many cells around the two ends of an edge the search is allowed to explore.
+    ///
+    /// Smaller windows are faster for short edges, and when no path is found within the
+    /// window we search once more without one, which is recorded in [`RouteStats`].
+    /// `None` always searches the whole grid.
+    pub search_window_cells: Option<f32>,
+    /// How paths are searched for, see [`RouteStrategy`].
+    ///
+    /// [`RouteStrategy::Direct`] gives the tightest paths and is fast enough for most graphs.
+    /// [`RouteStrategy::Hierarchical`] is worth it for tall graphs with many long edges,
+    /// where searching cell by cell across hundreds of layers is what routing spends its
+    /// time on.
+    pub strategy: RouteStrategy,
+    /// How strongly a re-routed edge prefers to stay on its previous path, from 0 to 1.
+    ///
+    /// A previous path that is still valid is kept as is, otherwise the cells along it get
+    /// cheaper by this fraction of [`CostField::SEED_BONUS`] while routing, though never
+    /// cheaper than a cell away from every block. 0 disables both.
+    pub stickiness: f32,
+}
+
+impl Default for RouteConfig {
+    fn default() -> Self {
+        Self {
+            cell_size: egui::Vec2::splat(3.0),
+            max_cells: Some(2_000_000),
+            margin_cells: 32,
+            bidirectional: false,
+            search_window_cells: Some(100.0),
+            strategy: RouteStrategy::Direct,
+            stickiness: 0.25,
+        }
+    }
+}
+
+impl RouteConfig {
+    /// The size of the cells of a grid covering `scene`, which is [`RouteConfig::cell_size`]
+    /// scaled up just enough to stay within [`RouteConfig::max_cells`].
+    pub fn cell_size_for(&self, scene: egui::Rect) -> egui::Vec2 {
+        self.cell_size_around(scene, 0.0)
+    }
+
+    /// Like [`RouteConfig::cell_size_for`], but for a grid covering `bounds` along with
+    /// `margin` of its cells on every side.
+    ///
+    /// The scene of `bounds` expanded by `margin` of these cells gets the same cells from
+    /// [`RouteConfig::cell_size_for`], so the margin stays that many cells wide however much
+    /// the cells are scaled up.
+    pub fn cell_size_around(&self, bounds: egui::Rect, margin: f32) -> egui::Vec2 {
+        let min = self.cell_size;
+
+        // a scene that isn't finite would need any number of cells, which can't be scaled.
+        let Some(max_cells) = self.max_cells.filter(|max| *max > 0 && bounds.is_finite()) else {
+            return min;
+        };
+
+        let grid = Grid::from_scene(bounds.expand2(min * margin), min);
+
+        if grid.cols.saturating_mul(grid.rows) <= max_cells {
+            return min;
+        }
+
+        // snapping the origin and rounding up to whole cells adds at most two cells to each
+        // side, so with `w` and `h` being how many of the smallest cells the bounds are
+        // across, the scale `s` has to satisfy `(w / s + k) * (h / s + k) <= max_cells`, where
+        // `k` is those two cells and the margin on both sides.
+        let w = ((bounds.width() / min.x) as f64).max(1.0);
+        let h = ((bounds.height() / min.y) as f64).max(1.0);
+        let k = 2.0 * margin as f64 + 2.0;
+        let m = max_cells as f64;
+
+        let scale = if m > k * k {
+            let u = (-k * (w + h) + (k * k * (w + h).powi(2) + 4.0 * w * h * (m - k * k)).sqrt())
+                / (2.0 * w * h);
+            1.0 / u
+        } else {
+            // the margin alone needs more cells than that, so this is the best there is, a
+            // cell as big as all of the bounds.
+            w.max(h)
+        };
+
+        min * scale.max(1.0) as f32
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Grid {
     pub origin: egui::Pos2,
     pub cols: usize,
     pub rows: usize,
-    pub cell: f32,
+    /// The width and height of a single cell.
+    pub cell: egui::Vec2,
 }
 
 impl Grid {
     /// Create a grid from a rect, whose cell size is `cell`.
-    pub fn from_scene(scene: egui::Rect, cell: f32) -> Self {
-        // maybe return an error if either return 0 after floor.
-        let cols = (scene.width() / cell).floor() as usize;
-        let rows = (scene.height() / cell).floor() as usize;
+    pub fn from_scene(scene: egui::Rect, cell: egui::Vec2) -> Self {
+        let ox = (scene.min.x / cell.x).floor() * cell.x;
+        let oy = (scene.min.y / cell.y).floor() * cell.y;
 
-        let ox = (scene.min.x / cell).floor() * cell;
-        let oy = (scene.min.y / cell).floor() * cell;
+        // we measure from the snapped origin, so that the far edge of the scene is still covered.
+        // maybe return an error if either return 0 after ceil.
+        let cols = ((scene.max.x - ox) / cell.x).ceil() as usize;
+        let rows = ((scene.max.y - oy) / cell.y).ceil() as usize;
 
         Self {
             origin: egui::pos2(ox, oy),
@@ -32,37 +162,57 @@ impl Grid {
         }
     }
 
-    /// Gets all valid 4-direction neighbors of `coords` inside the grid.
-    fn cardinal_neighbors(&self, coords: GridCoord) -> Vec<GridCoord> {
-        let (x, y) = coords;
-        let mut neighbors = Vec::new();
+    /// Create a grid from a rect, whose cells are squares with sides of `cell`.
+    pub fn splat(scene: egui::Rect, cell: f32) -> Self {
+        Self::from_scene(scene, egui::Vec2::splat(cell))
+    }
 
-        if x + 1 < self.cols {
-            neighbors.push((x + 1, y));
-        }
-        if let Some(nx) = x.checked_sub(1) {
-            neighbors.push((nx, y));
-        }
+    /// How much a single step along each axis weighs, relative to the smallest cell side.
+    ///
+    /// This keeps costs proportional to the distance travelled when the cells aren't square.
+    fn axis_weights(&self) -> (f32, f32) {
+        let min = self.cell.min_elem();
+        (self.cell.x / min, self.cell.y / min)
+    }
 
-        if y + 1 < self.rows {
-            neighbors.push((x, y + 1));
-        }
+    /// The weight of a single step between the neighboring cells `a` and `b`.
+    fn step_weight(&self, a: GridCoord, b: GridCoord) -> f32 {
+        let (wx, wy) = self.axis_weights();
+        if a.0 != b.0 { wx } else { wy }
+    }
 
-        if let Some(ny) = y.checked_sub(1) {
-            neighbors.push((x, ny));
-        }
+    /// Weighted manhattan distance that we use for our A* H cost calculation.
+    fn distance(&self, a: GridCoord, b: GridCoord) -> f32 {
+        let (wx, wy) = self.axis_weights();
+        a.0.abs_diff(b.0) as f32 * wx + a.1.abs_diff(b.1) as f32 * wy
+    }
+
+    /// Gets all valid 4-direction neighbors of `coords` inside the grid.
+    ///
+    /// This runs for every expanded cell, so the neighbors come out of an array instead of
+    /// being collected into a `Vec`.
+    fn cardinal_neighbors(&self, coords: GridCoord) -> impl Iterator<Item = GridCoord> + use<> {
+        let (x, y) = coords;
 
-        neighbors
+        [
+            (x + 1 < self.cols).then_some((x + 1, y)),
+            x.checked_sub(1).map(|nx| (nx, y)),
+            (y + 1 < self.rows).then_some((x, y + 1)),
+            y.checked_sub(1).map(|ny| (x, ny)),
+        ]
+        .into_iter()
+        .flatten()
     }
 
-    /// Convert a position to a place in the grid.
-    fn to_cell(&self, p: egui::Pos2) -> GridCoord {
+    /// Convert a position to a place in the grid, clamping positions outside of it onto the
+    /// border cells.
+    pub fn to_cell(self, p: egui::Pos2) -> GridCoord {
         // turn into origin relative coordinates.
         let rel = p - self.origin;
 
         // get the nearest cell.
-        let mut x = (rel.x / self.cell).floor() as isize;
-        let mut y = (rel.y / self.cell).floor() as isize;
+        let mut x = (rel.x / self.cell.x).floor() as isize;
+        let mut y = (rel.y / self.cell.y).floor() as isize;
 
         x = x.clamp(0, self.cols as isize - 1);
         y = y.clamp(0, self.rows as isize - 1);
@@ -70,6 +220,27 @@ impl Grid {
         (x as usize, y as usize)
     }
 
+    /// Like [`Grid::to_cell`], but only clamps positions that are at most a single cell
+    /// outside of the grid, and returns `None` for anything further out.
+    ///
+    /// Clamping those silently would make a path run along the border cells to get there.
+    pub fn try_to_cell(self, p: egui::Pos2) -> Option<GridCoord> {
+        let rel = p - self.origin;
+
+        let x = (rel.x / self.cell.x).floor();
+        let y = (rel.y / self.cell.y).floor();
+
+        let inside = |v: f32, count: usize| (-1.0..=count as f32).contains(&v);
+
+        (inside(x, self.cols) && inside(y, self.rows)).then(|| self.to_cell(p))
+    }
+
+    /// Whether `coords` is inside the grid and not on the border rows or columns.
+    pub const fn is_interior(&self, coords: GridCoord) -> bool {
+        let (x, y) = coords;
+        x > 0 && y > 0 && x + 1 < self.cols && y + 1 < self.rows
+    }
+
     /// Convert 2D `GridCoord`, into 1D index.
     pub const fn to_index(&self, coords: GridCoord) -> usize {
         coords.1 * self.cols + coords.0
@@ -78,7 +249,11 @@ impl Grid {
     /// Return the position of the center of the cell.
     pub fn cell_center(&self, coords: GridCoord) -> egui::Pos2 {
         let (x, y) = coords;
-        self.origin + egui::vec2((x as f32 + 0.5) * self.cell, (y as f32 + 0.5) * self.cell)
+        self.origin
+            + egui::vec2(
+                (x as f32 + 0.5) * self.cell.x,
+                (y as f32 + 0.5) * self.cell.y,
+            )
     }
 
     /// Returns which direction we go to, from `a`, to `b`.
@@ -94,13 +269,43 @@ impl Grid {
 pub struct CostField {
     pub cost: Vec<f32>,
     pub grid: Grid,
+    /// Every block rectangle and its cost radius, in the order they were added.
+    ///
+    /// We keep these around so that a region can be re-rasterized after removing one.
+    obstacles: Vec<(egui::Rect, f32)>,
 }
 
 impl CostField {
+    /// The cost of a cell that isn't near any block, nor on the border of the grid.
+    const BASE_COST: f32 = 1.0;
+
+    /// The most a cell along a previous path gets taken off of its cost, see
+    /// [`RouteConfig::stickiness`].
+    pub const SEED_BONUS: f32 = 1.0;
+
+    /// How much more the border cells cost, so that a path from a port just outside the
+    /// grid leaves the border right away instead of running along it.
+    const BORDER_COST: f32 = 1.0;
+
     pub fn new(grid: Grid) -> Self {
+        let cost = (0..grid.rows)
+            .flat_map(|y| (0..grid.cols).map(move |x| (x, y)))
+            .map(|coords| Self::base_cost(&grid, coords))
+            .collect();
+
         Self {
-            cost: vec![1.0; grid.cols * grid.rows],
+            cost,
             grid,
+            obstacles: Vec::new(),
+        }
+    }
+
+    /// The cost of `coords` without any block near it.
+    fn base_cost(grid: &Grid, coords: GridCoord) -> f32 {
+        if grid.is_interior(coords) {
+            Self::BASE_COST
+        } else {
+            Self::BASE_COST + Self::BORDER_COST
         }
     }
 
@@ -112,84 +317,375 @@ impl CostField {
         self.cost.get(self.grid.to_index(coords)).copied()
     }
 
+    /// How much `block_rectangle` adds onto the cost of `coords`, or `None` if it blocks it.
+    fn contribution(
+        &self,
+        coords: GridCoord,
+        block_rectangle: egui::Rect,
+        radius: f32,
+    ) -> Option<f32> {
+        // we get the position of the center of the current grid cell.
+        let cell = self.grid.cell_center(coords);
+
+        // anything that is inside of the block can't be crossed.
+        if block_rectangle.contains(cell) {
+            return None;
+        }
+
+        // measure the distance in cells, so that the falloff is stretched along with them.
+        let d = (cell.clamp(block_rectangle.min, block_rectangle.max) - cell) / self.grid.cell;
+
+        // get the radius cell distance percentage between current cell and the block.
+        let falloff = (radius - d.length()).max(0.0) / radius;
+
+        Some(3.0 * falloff)
+    }
+
+    /// The range of cells a block rectangle with a cost radius can have an effect on.
+    fn window(&self, block_rectangle: egui::Rect, radius: f32) -> (GridCoord, GridCoord) {
+        let area = block_rectangle.expand2(self.grid.cell * radius);
+
+        (self.grid.to_cell(area.min), self.grid.to_cell(area.max))
+    }
+
     /// Add a rectangle to the cost field, with a cost radius.
     ///
     /// The cost radius isn't a hard block but discourages lines from going through it.
     pub fn add_block_rect(&mut self, block_rectangle: egui::Rect, radius: f32) {
-        // we expand the rectangle by the margin so that we increase the "hitbox".
-        // let block_rectangle = block_rectangle.expand(margin);
+        let (min, max) = self.window(block_rectangle, radius);
 
-        for y in 0..self.grid.rows {
-            for x in 0..self.grid.cols {
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
                 let coords: GridCoord = (x, y);
 
-                // we get the position of the center of the current grid cell.
-                let cell = self.grid.cell_center(coords);
-
-                // anything that is inside of the block increase the cost.
-                if block_rectangle.contains(cell) {
-                    *self.get_cost_cell_mut(coords) = f32::MAX;
-                    continue;
+                match self.contribution(coords, block_rectangle, radius) {
+                    Some(cost) => *self.get_cost_cell_mut(coords) += cost,
+                    None => *self.get_cost_cell_mut(coords) = f32::MAX,
                 }
+            }
+        }
+
+        self.obstacles.push((block_rectangle, radius));
+    }
+
+    /// Remove a rectangle that was previously added with [`CostField::add_block_rect`].
+    ///
+    /// Only the cells around the rectangle are re-rasterized from the remaining obstacles,
+    /// so the field ends up exactly as if the rectangle had never been added. Returns false
+    /// if there was no such rectangle.
+    pub fn remove_block_rect(&mut self, block_rectangle: egui::Rect, radius: f32) -> bool {
+        let Some(index) = self
+            .obstacles
+            .iter()
+            .position(|&(rect, r)| rect == block_rectangle && r == radius)
+        else {
+            return false;
+        };
+
+        self.obstacles.remove(index);
 
-                let d = block_rectangle.distance_to_pos(cell);
+        let (min, max) = self.window(block_rectangle, radius);
 
-                // get the 3 cell distance percentage between current cell and the block.
-                let falloff = (self.grid.cell * radius - d).max(0.0) / (self.grid.cell * radius);
+        // only the obstacles that reach into the window can have an effect on it.
+        let window =
+            egui::Rect::from_min_max(self.grid.cell_center(min), self.grid.cell_center(max));
 
-                *self.get_cost_cell_mut(coords) += 3.0 * falloff;
+        let nearby: Vec<(egui::Rect, f32)> = self
+            .obstacles
+            .iter()
+            .filter(|(rect, r)| rect.expand2(self.grid.cell * *r).intersects(window))
+            .copied()
+            .collect();
+
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                let coords: GridCoord = (x, y);
+
+                // we add the costs in the same order as they were added in originally.
+                let mut cost = Self::base_cost(&self.grid, coords);
+
+                for &(rect, r) in &nearby {
+                    match self.contribution(coords, rect, r) {
+                        Some(c) => cost += c,
+                        None => cost = f32::MAX,
+                    }
+                }
+
+                *self.get_cost_cell_mut(coords) = cost;
             }
         }
+
+        true
     }
 }
 
+/// A fixed-point cost used by the search, which is the real cost multiplied by [`COST_SCALE`].
+///
+/// Integer costs keep the open list ordering (and ties) exact, and all the arithmetic on
+/// them saturates so that huge costs can never wrap around.
+type Cost = u32;
+
+/// How many fixed-point steps make up a cost of 1.0.
+const COST_SCALE: f32 = 16.0;
+
+/// The cost of a cell that can't be entered at all.
+const BLOCKED: Cost = Cost::MAX;
+
+/// The cost of a cell that hasn't been reached yet.
+const UNREACHED: Cost = Cost::MAX;
+
+/// The penalty for changing direction.
+const TURN_PENALTY: Cost = COST_SCALE as Cost;
+
+/// Convert a cost from the [`CostField`] into the fixed-point representation.
+fn to_cost(cost: f32) -> Cost {
+    if cost >= f32::MAX {
+        return BLOCKED;
+    }
+
+    // the `as` cast saturates, so anything too large just becomes the blocked cost.
+    (cost * COST_SCALE).round() as Cost
+}
+
 #[derive(Clone)]
 pub struct CellBase {
-    g: f32,
-    h: f32,
+    g: Cost,
+    h: Cost,
     parent: Option<GridCoord>,
 }
 
 impl CellBase {
     const fn new() -> Self {
         Self {
-            g: f32::INFINITY,
-            h: 0.0,
+            g: UNREACHED,
+            h: 0,
             parent: None,
         }
     }
 
-    const fn f(&self) -> f32 {
-        self.g + self.h
+    const fn f(&self) -> Cost {
+        self.g.saturating_add(self.h)
+    }
+}
+
+/// An entry in the open list of [`AStar`].
+///
+/// Entries are ordered by f cost first, then we prefer cells that keep going straight, and
+/// then cells that are closer to the straight line between the start and goal. The
+/// coordinate is the final tiebreak so that the order is always fully deterministic.
+type OpenEntry = (Reverse<Cost>, Reverse<u8>, Reverse<u64>, Reverse<GridCoord>);
+
+/// Information collected while routing, useful for tuning the [`RouteConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct RouteStats {
+    /// How many cells were taken off of the open list.
+    pub expansions: usize,
+    /// How many routes found nothing within the search window, and had to be retried
+    /// without one.
+    pub fallback_retries: usize,
+    /// The size of the grid cells that were routed on, see [`RouteConfig::max_cells`].
+    pub cell_size: egui::Vec2,
+}
+
+/// One direction of a bidirectional search.
+struct Frontier {
+    pending: BinaryHeap<OpenEntry>,
+    seen: HashSet<GridCoord>,
+    cells: HashMap<GridCoord, CellBase>,
+    /// The cell this half of the search starts from.
+    origin: GridCoord,
+    /// The cell this half of the search heads towards.
+    target: GridCoord,
+}
+
+impl Frontier {
+    fn new(grid: &Grid, origin: GridCoord, target: GridCoord) -> Self {
+        let mut cells = HashMap::new();
+
+        let base = CellBase {
+            g: 0,
+            h: Self::potential(grid, origin, target, origin),
+            parent: None,
+        };
+
+        let mut pending = BinaryHeap::new();
+        pending.push((Reverse(base.f()), Reverse(0), Reverse(0), Reverse(origin)));
+
+        cells.insert(origin, base);
+
+        Self {
+            pending,
+            seen: HashSet::new(),
+            cells,
+            origin,
+            target,
+        }
+    }
+
+    /// The average of the distance left to `target` and the distance already made from
+    /// `origin`, so that both halves of the search use the same heuristic with opposite signs.
+    ///
+    /// Half of the distance between `origin` and `target` is added on top, so that the
+    /// potential can never be negative. This offsets the sum of both potentials by exactly
+    /// that distance, which [`AStar::find_path_bidirectional`] takes into account.
+    fn potential(grid: &Grid, origin: GridCoord, target: GridCoord, coords: GridCoord) -> Cost {
+        let potential = grid.distance(coords, target) - grid.distance(coords, origin)
+            + grid.distance(origin, target);
+
+        to_cost(potential.max(0.0) * 0.5)
+    }
+
+    /// Drop entries of cells we've already expanded, and return the lowest pending f cost.
+    fn min_f(&mut self) -> Option<Cost> {
+        while let Some((_, _, _, Reverse(coords))) = self.pending.peek() {
+            if !self.seen.contains(coords) {
+                return Some(self.cells[coords].f());
+            }
+
+            self.pending.pop();
+        }
+
+        None
+    }
+
+    fn g(&self, coords: GridCoord) -> Option<Cost> {
+        self.cells
+            .get(&coords)
+            .map(|c| c.g)
+            .filter(|&g| g != UNREACHED)
+    }
+
+    /// Walk the parents of `coords` back to the origin of this half of the search.
+    fn chain(&self, mut coords: GridCoord) -> Vec<GridCoord> {
+        let mut chain = vec![coords];
+
+        while let Some(prev) = self.cells.get(&coords).and_then(|c| c.parent) {
+            coords = prev;
+            chain.push(coords);
+        }
+
+        chain
+    }
+}
+
+/// The collections used by a search, kept around so that their allocations can be reused.
+#[derive(Default)]
+struct Scratch {
+    pending: BinaryHeap<OpenEntry>,
+    seen: HashSet<GridCoord>,
+    cells: HashMap<GridCoord, CellBase>,
+    /// The cells of the path that was found, from the goal back to the start.
+    path: Vec<GridCoord>,
+}
+
+impl Scratch {
+    fn clear(&mut self) {
+        self.pending.clear();
+        self.seen.clear();
+        self.cells.clear();
+        self.path.clear();
     }
 }
 
 pub struct AStar<'a> {
     field: &'a CostField,
-    // /// We map grid coordinates to cell information.
+    stats: RouteStats,
+    scratch: Scratch,
+    /// How many cells around the start and goal the search can explore, if limited.
+    window: Option<f32>,
+    /// An explicit area to keep the search within, which takes precedence over the window.
+    area: Option<egui::Rect>,
 }
 
 impl<'a> AStar<'a> {
     pub fn new(field: &'a CostField) -> Self {
-        Self { field }
+        Self::with_scratch(field, Scratch::default())
+    }
+
+    fn with_scratch(field: &'a CostField, scratch: Scratch) -> Self {
+        Self {
+            field,
+            stats: RouteStats::default(),
+            scratch,
+            window: RouteConfig::default().search_window_cells,
+            area: None,
+        }
     }
 
-    /// Manhattan distance that we use for our A* H cost calculation.
-    const fn manhattan(a: GridCoord, b: GridCoord) -> usize {
-        a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+    /// Keep the search within `area`, instead of the window around the start and goal.
+    pub fn set_area(&mut self, area: Option<egui::Rect>) {
+        self.area = area;
     }
 
-    /// Used specifically so that we can have Ord on "floats".
-    const fn float_key(f: f32) -> u32 {
-        let bits = f.to_bits();
-        if bits & 0x8000_0000 == 0 {
-            bits | 0x8000_0000
-        } else {
-            !bits
+    /// Limit the search to `cells` around the start and goal, or search everywhere with `None`.
+    pub fn set_window(&mut self, cells: Option<f32>) {
+        self.window = cells;
+    }
+
+    /// The area we keep our focus within, which is in range of the start and end positions.
+    fn search_area(&self, begin: egui::Pos2, finish: egui::Pos2) -> egui::Rect {
+        if let Some(area) = self.area {
+            return area;
         }
+
+        match self.window {
+            Some(cells) => {
+                egui::Rect::from_two_pos(begin, finish).expand2(cells * self.field.grid.cell)
+            }
+            None => egui::Rect::EVERYTHING,
+        }
+    }
+
+    /// The statistics collected by every search done with this instance.
+    pub fn stats(&self) -> &RouteStats {
+        &self.stats
+    }
+
+    /// How far `coords` strays from the straight line going from `start` to `end`.
+    ///
+    /// This is the magnitude of the cross product, so it's scaled by the length of the line
+    /// but that doesn't matter since we only compare it between cells of the same search.
+    const fn line_bias(start: GridCoord, end: GridCoord, coords: GridCoord) -> u64 {
+        let (dx, dy) = (end.0 as i64 - start.0 as i64, end.1 as i64 - start.1 as i64);
+
+        let (cx, cy) = (
+            coords.0 as i64 - start.0 as i64,
+            coords.1 as i64 - start.1 as i64,
+        );
+
+        (cx * dy - cy * dx).unsigned_abs()
+    }
+
+    /// Whether `b` is one of the cardinal neighbors of `a`.
+    const fn is_step(a: GridCoord, b: GridCoord) -> bool {
+        a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1
+    }
+
+    /// The fixed-point cost of entering the cell at `coords`, if it can be entered at all.
+    fn cell_cost(&self, coords: GridCoord) -> Option<Cost> {
+        self.field.cost_at(coords).map(to_cost)
     }
 
     pub fn find_path(&mut self, begin: egui::Pos2, finish: egui::Pos2) -> Option<Vec<egui::Pos2>> {
+        let mut path = Vec::new();
+
+        self.find_path_into(begin, finish, &mut path)
+            .then_some(path)
+    }
+
+    /// Like [`AStar::find_path`], but writes the path into `out` instead of allocating a new
+    /// one, which makes routing many paths in a row allocation free once every buffer has
+    /// grown large enough.
+    ///
+    /// `out` is cleared first, and stays empty when there's no path.
+    pub fn find_path_into(
+        &mut self,
+        begin: egui::Pos2,
+        finish: egui::Pos2,
+        out: &mut Vec<egui::Pos2>,
+    ) -> bool {
+        out.clear();
+
         // get the starting cell.
         let start = self.field.grid.to_cell(begin);
 
@@ -197,57 +693,61 @@ impl<'a> AStar<'a> {
         let end = self.field.grid.to_cell(finish);
 
         // reject if the goal is in a blocked region.
-        if self.field.cost_at(end)? == f32::MAX {
-            println!("invalid end position");
-            return None;
+        if self.cell_cost(end).is_none_or(|cost| cost == BLOCKED) {
+            return false;
         }
 
         // we create a bounding box that keeps our focus within range of the start and end positions.
-        let bounding_box =
-            egui::Rect::from_two_pos(begin, finish).expand(100.0 * self.field.grid.cell);
-
-        // we use a min heap to keep track of the most ideal pending coordinates.
-        let mut pending: BinaryHeap<(Reverse<u32>, GridCoord)> = BinaryHeap::new();
+        let bounding_box = self.search_area(begin, finish);
 
-        // keep track of all the coordinates we've seen/processed.
-        let mut seen: HashSet<GridCoord> = HashSet::new();
+        self.scratch.clear();
 
-        let mut cells: HashMap<GridCoord, CellBase> = HashMap::new();
+        let Scratch {
+            // we use a min heap to keep track of the most ideal pending coordinates.
+            pending,
+            // keep track of all the coordinates we've seen/processed.
+            seen,
+            cells,
+            path,
+        } = &mut self.scratch;
 
         cells.insert(
             start,
             CellBase {
-                g: 0.0,
-                h: Self::manhattan(start, end) as _,
+                g: 0,
+                h: to_cost(self.field.grid.distance(start, end)),
                 parent: None,
             },
         );
 
         // place the starting coordinate into the pending min heap along with its f cost.
-        pending.push((Reverse(Self::float_key(cells[&start].f())), start));
+        pending.push((
+            Reverse(cells[&start].f()),
+            Reverse(0),
+            Reverse(0),
+            Reverse(start),
+        ));
 
-        while let Some((_, mut current)) = pending.pop() {
+        while let Some((_, _, _, Reverse(mut current))) = pending.pop() {
             if !seen.insert(current) {
                 continue;
             }
 
+            self.stats.expansions += 1;
+
             if current == end {
                 // this will create list of parents of successive cells.
-                let mut path = vec![current];
+                path.push(current);
 
                 while let Some(prev) = cells.get(&current).and_then(|c| c.parent) {
                     current = prev;
                     path.push(current);
                 }
 
-                // reverse the list so that it's children->parent.
-                path.reverse();
+                // the list goes from the goal to the start, so it gets read backwards.
+                out.extend(path.iter().rev().map(|p| self.field.grid.cell_center(*p)));
 
-                return Some(
-                    path.into_iter()
-                        .map(|p| self.field.grid.cell_center(p))
-                        .collect(),
-                );
+                return true;
             }
 
             for neighbor in self.field.grid.cardinal_neighbors(current) {
@@ -256,9 +756,9 @@ impl<'a> AStar<'a> {
                     continue;
                 }
 
-                // take any cost as long as the cost isn't the max, aka a wall.
+                // take any cost as long as the cell isn't a wall.
                 let neighbor_cost = match self.field.cost_at(neighbor) {
-                    Some(c) if c != f32::MAX => c,
+                    Some(c) if to_cost(c) != BLOCKED => c,
                     _ => continue,
                 };
 
@@ -272,28 +772,685 @@ impl<'a> AStar<'a> {
 
                 // if the direction is different from parent to child than child to neighbor, add penalty.
                 let turn_pen = if Some(step_dir) != incoming_dir {
-                    1.0
+                    TURN_PENALTY
                 } else {
-                    0.0
+                    0
                 };
 
                 // get the cost that it would take to go from our current cell to this neighbor.
-                let candidate_cost = cells[&current].g + neighbor_cost + turn_pen;
+                let step = to_cost(neighbor_cost * self.field.grid.step_weight(current, neighbor));
+
+                let candidate_cost = cells[&current]
+                    .g
+                    .saturating_add(step)
+                    .saturating_add(turn_pen);
 
                 let neighbor_cell = cells.entry(neighbor).or_insert_with(CellBase::new);
 
                 if candidate_cost < neighbor_cell.g {
                     neighbor_cell.g = candidate_cost;
-                    neighbor_cell.h = Self::manhattan(neighbor, end) as _;
+                    neighbor_cell.h = to_cost(self.field.grid.distance(neighbor, end));
                     neighbor_cell.parent = Some(current);
 
                     let f = neighbor_cell.f();
 
-                    pending.push((Reverse(Self::float_key(f)), neighbor));
+                    // candidates that keep going in the same direction win ties.
+                    let turned = u8::from(turn_pen > 0);
+
+                    let bias = Self::line_bias(start, end, neighbor);
+
+                    pending.push((
+                        Reverse(f),
+                        Reverse(turned),
+                        Reverse(bias),
+                        Reverse(neighbor),
+                    ));
                 }
             }
         }
 
-        None
+        false
+    }
+
+    /// Like [`AStar::find_path`], but searches from both `begin` and `finish` until the two
+    /// searches meet.
+    ///
+    /// The backward search pays for the cell it leaves rather than the cell it enters, so
+    /// both halves agree on the cost of a path. Turns are only penalized within each half.
+    pub fn find_path_bidirectional(
+        &mut self,
+        begin: egui::Pos2,
+        finish: egui::Pos2,
+    ) -> Option<Vec<egui::Pos2>> {
+        let start = self.field.grid.to_cell(begin);
+        let end = self.field.grid.to_cell(finish);
+
+        // reject if the goal is in a blocked region.
+        if self.cell_cost(end)? == BLOCKED {
+            return None;
+        }
+
+        let bounding_box = self.search_area(begin, finish);
+
+        let mut forward = Frontier::new(&self.field.grid, start, end);
+        let mut backward = Frontier::new(&self.field.grid, end, start);
+
+        // the cheapest known path going through a cell reached by both searches.
+        let mut best: Option<(Cost, GridCoord)> = (start == end).then_some((0, start));
+
+        // the potentials of both halves cancel out, leaving only their combined offset.
+        let offset = to_cost(self.field.grid.distance(start, end));
+
+        while let (Some(forward_f), Some(backward_f)) = (forward.min_f(), backward.min_f()) {
+            // neither search can find anything cheaper than what we already have.
+            if best.is_some_and(|(cost, _)| {
+                forward_f.saturating_add(backward_f) >= cost.saturating_add(offset)
+            }) {
+                break;
+            }
+
+            // always grow the smaller frontier, so the two stay roughly balanced.
+            let is_forward = forward.pending.len() <= backward.pending.len();
+
+            let (side, other) = if is_forward {
+                (&mut forward, &backward)
+            } else {
+                (&mut backward, &forward)
+            };
+
+            let Some((_, _, _, Reverse(current))) = side.pending.pop() else {
+                break;
+            };
+
+            side.seen.insert(current);
+            self.stats.expansions += 1;
+
+            for neighbor in self.field.grid.cardinal_neighbors(current) {
+                if !bounding_box.contains(self.field.grid.cell_center(neighbor)) {
+                    continue;
+                }
+
+                // the start cell can be inside of a block, since that's where the port lives.
+                let neighbor_cost = match self.field.cost_at(neighbor) {
+                    Some(c) if to_cost(c) != BLOCKED || (!is_forward && neighbor == start) => c,
+                    _ => continue,
+                };
+
+                // going backwards, the forward path would be entering `current` instead.
+                let step_cost = if is_forward {
+                    neighbor_cost
+                } else {
+                    self.field.cost_at(current).unwrap_or(neighbor_cost)
+                };
+
+                let incoming_dir = side
+                    .cells
+                    .get(&current)
+                    .and_then(|c| c.parent)
+                    .map(|p| Grid::get_direction(p, current));
+
+                let step_dir = Grid::get_direction(current, neighbor);
+
+                let turn_pen = if Some(step_dir) != incoming_dir {
+                    TURN_PENALTY
+                } else {
+                    0
+                };
+
+                let step = to_cost(step_cost * self.field.grid.step_weight(current, neighbor));
+
+                let candidate_cost = side.cells[&current]
+                    .g
+                    .saturating_add(step)
+                    .saturating_add(turn_pen);
+
+                let (origin, target) = (side.origin, side.target);
+                let neighbor_cell = side.cells.entry(neighbor).or_insert_with(CellBase::new);
+
+                if candidate_cost < neighbor_cell.g {
+                    neighbor_cell.g = candidate_cost;
+                    neighbor_cell.h =
+                        Frontier::potential(&self.field.grid, origin, target, neighbor);
+                    neighbor_cell.parent = Some(current);
+
+                    let f = neighbor_cell.f();
+
+                    let turned = u8::from(turn_pen > 0);
+                    let bias = Self::line_bias(start, end, neighbor);
+
+                    side.pending.push((
+                        Reverse(f),
+                        Reverse(turned),
+                        Reverse(bias),
+                        Reverse(neighbor),
+                    ));
+
+                    // the other search already got here, so we have a complete path.
+                    if let Some(other_g) = other.g(neighbor) {
+                        let total = candidate_cost.saturating_add(other_g);
+
+                        if best.is_none_or(|(cost, _)| total < cost) {
+                            best = Some((total, neighbor));
+                        }
+                    }
+                }
+            }
+        }
+
+        let (_, meeting) = best?;
+
+        // the forward chain goes meeting->start, and the backward chain goes meeting->end.
+        let forward = forward.chain(meeting);
+        let backward = backward.chain(meeting);
+
+        Some(
+            forward
+                .iter()
+                .rev()
+                .chain(backward.iter().skip(1))
+                .map(|p| self.field.grid.cell_center(*p))
+                .collect(),
+        )
+    }
+}
+
+/// The reasons routing a path with a [`RouteContext`] can fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouteError {
+    /// The goal lies inside of an obstacle, so nothing can reach it.
+    GoalBlocked,
+    /// Every path between the two positions is blocked.
+    NoPath,
+    /// One of the positions is too far outside of the routing grid to be routed to.
+    OutOfBounds,
+}
+
+impl std::fmt::Display for RouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GoalBlocked => write!(f, "the goal is inside of an obstacle"),
+            Self::NoPath => write!(f, "there is no path between the two positions"),
+            Self::OutOfBounds => write!(f, "a position is outside of the routing grid"),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Everything needed to route paths through a scene.
+///
+/// This is what the [`crate::view::CfgView`] uses to route its edges, but it can be used on
+/// its own to route any other lines around the same blocks.
+pub struct RouteContext {
+    config: RouteConfig,
+    field: CostField,
+    scratch: Scratch,
+    stats: RouteStats,
+    /// Built the first time a hierarchical route is needed, and dropped when obstacles change.
+    corridors: Option<CorridorGraph>,
+}
+
+impl RouteContext {
+    /// Create a context with no obstacles, whose grid covers `scene`.
+    pub fn new(scene: egui::Rect, config: RouteConfig) -> Self {
+        let grid = Grid::from_scene(scene, config.cell_size_for(scene));
+
+        Self {
+            config,
+            field: CostField::new(grid),
+            scratch: Scratch::default(),
+            stats: RouteStats {
+                cell_size: grid.cell,
+                ..Default::default()
+            },
+            corridors: None,
+        }
+    }
+
+    /// Create a context that routes over a `field` that was already built, e.g. by an
+    /// earlier context that was turned back into its field with [`RouteContext::into_field`].
+    pub fn from_field(field: CostField, config: RouteConfig) -> Self {
+        Self {
+            config,
+            stats: RouteStats {
+                cell_size: field.grid.cell,
+                ..Default::default()
+            },
+            field,
+            scratch: Scratch::default(),
+            corridors: None,
+        }
+    }
+
+    /// Give up the context, keeping the cost field with all of its obstacles.
+    pub fn into_field(self) -> CostField {
+        self.field
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.field.grid
+    }
+
+    pub fn field(&self) -> &CostField {
+        &self.field
+    }
+
+    pub fn config(&self) -> &RouteConfig {
+        &self.config
+    }
+
+    /// The statistics collected by every route since the last [`RouteContext::clear`].
+    pub fn stats(&self) -> &RouteStats {
+        &self.stats
+    }
+
+    /// Add a rectangle that paths can't go through, and are discouraged from going near,
+    /// within `radius` cells.
+    pub fn add_obstacle(&mut self, rect: egui::Rect, radius: f32) {
+        self.field.add_block_rect(rect, radius);
+        self.corridors = None;
+    }
+
+    /// Find a path from `from` to `to` that goes around the obstacles.
+    pub fn route(
+        &mut self,
+        from: egui::Pos2,
+        to: egui::Pos2,
+    ) -> Result<Vec<egui::Pos2>, RouteError> {
+        let (_, goal) = self.cells(from, to)?;
+
+        if self.field.cost_at(goal) == Some(f32::MAX) {
+            return Err(RouteError::GoalBlocked);
+        }
+
+        if let RouteStrategy::Hierarchical { min_layer_span } = self.config.strategy
+            && let Some(path) = self.route_hierarchical(from, to, min_layer_span)
+        {
+            return Ok(path);
+        }
+
+        self.route_direct(from, to)
+    }
+
+    /// The cells of `from` and `to`, as long as both are within the grid.
+    fn cells(
+        &self,
+        from: egui::Pos2,
+        to: egui::Pos2,
+    ) -> Result<(GridCoord, GridCoord), RouteError> {
+        let grid = self.field.grid;
+
+        match (grid.try_to_cell(from), grid.try_to_cell(to)) {
+            (Some(start), Some(goal)) => Ok((start, goal)),
+            _ => Err(RouteError::OutOfBounds),
+        }
+    }
+
+    fn route_direct(
+        &mut self,
+        from: egui::Pos2,
+        to: egui::Pos2,
+    ) -> Result<Vec<egui::Pos2>, RouteError> {
+        let mut astar = AStar::with_scratch(&self.field, std::mem::take(&mut self.scratch));
+
+        astar.set_window(self.config.search_window_cells);
+
+        let search = |astar: &mut AStar| {
+            if self.config.bidirectional {
+                astar.find_path_bidirectional(from, to)
+            } else {
+                astar.find_path(from, to)
+            }
+        };
+
+        let mut path = search(&mut astar);
+
+        // the path might just go outside of the window, so try again without one.
+        if path.is_none() && self.config.search_window_cells.is_some() {
+            astar.set_window(None);
+            path = search(&mut astar);
+            self.stats.fallback_retries += 1;
+        }
+
+        self.stats.expansions += astar.stats.expansions;
+        self.scratch = astar.scratch;
+
+        path.ok_or(RouteError::NoPath)
+    }
+
+    /// Like [`RouteContext::route`], but for a line that was previously routed along `seed`.
+    ///
+    /// If `seed` still connects the two positions without crossing any obstacle it's kept,
+    /// otherwise the new path is nudged towards it, depending on [`RouteConfig::stickiness`].
+    pub fn route_seeded(
+        &mut self,
+        from: egui::Pos2,
+        to: egui::Pos2,
+        seed: &[egui::Pos2],
+    ) -> Result<Vec<egui::Pos2>, RouteError> {
+        let stickiness = self.config.stickiness.clamp(0.0, 1.0);
+
+        if stickiness == 0.0 || seed.is_empty() {
+            return self.route(from, to);
+        }
+
+        let (start, goal) = self.cells(from, to)?;
+
+        let grid = self.field.grid;
+        let cells: Vec<GridCoord> = seed.iter().map(|p| grid.to_cell(*p)).collect();
+
+        let connected = cells.first() == Some(&start)
+            && cells.last() == Some(&goal)
+            && cells.windows(2).all(|w| AStar::is_step(w[0], w[1]));
+
+        // the start cell is allowed to be blocked, same as when we search.
+        let clear = cells
+            .iter()
+            .skip(1)
+            .all(|c| self.field.cost_at(*c).is_some_and(|cost| cost != f32::MAX));
+
+        if connected && clear {
+            return Ok(seed.to_vec());
+        }
+
+        // make the old path slightly cheaper, and put the costs back once we're done. the
+        // heuristic assumes every cell costs at least the base cost, so we never go below it.
+        let mut original = Vec::with_capacity(cells.len());
+
+        for coords in cells {
+            let cost = self.field.get_cost_cell_mut(coords);
+
+            if *cost != f32::MAX {
+                original.push((coords, *cost));
+                *cost = (*cost - stickiness * CostField::SEED_BONUS).max(CostField::BASE_COST);
+            }
+        }
+
+        let path = self.route(from, to);
+
+        // restore in reverse, in case the path visited a cell more than once.
+        for (coords, cost) in original.into_iter().rev() {
+            *self.field.get_cost_cell_mut(coords) = cost;
+        }
+
+        path
+    }
+
+    /// Route over the corridor graph, refining every hop with A* around that hop only.
+    ///
+    /// Returns `None` when the path is too short to be worth it, or a hop can't be refined,
+    /// in which case the caller falls back to a direct route.
+    fn route_hierarchical(
+        &mut self,
+        from: egui::Pos2,
+        to: egui::Pos2,
+        min_layer_span: usize,
+    ) -> Option<Vec<egui::Pos2>> {
+        let grid = self.field.grid;
+
+        let bounds = egui::Rect::from_min_size(
+            grid.origin,
+            egui::vec2(grid.cols as f32, grid.rows as f32) * grid.cell,
+        );
+
+        // a passage has to fit a path with some room on either side.
+        let corridors = self.corridors.get_or_insert_with(|| {
+            CorridorGraph::new(&self.field.obstacles, bounds, grid.cell.x * 4.0)
+        });
+
+        let hops = corridors.plan(from, to, min_layer_span, grid.cell * 4.0)?;
+
+        let mut astar = AStar::with_scratch(&self.field, std::mem::take(&mut self.scratch));
+
+        let mut path: Vec<egui::Pos2> = Vec::new();
+        let mut begin = from;
+
+        for (waypoint, area) in hops {
+            astar.set_area(Some(area));
+
+            let Some(hop) = astar.find_path(begin, waypoint) else {
+                path.clear();
+                break;
+            };
+
+            // every hop starts on the cell the previous one ended on.
+            let skip = usize::from(!path.is_empty());
+            path.extend(hop.into_iter().skip(skip));
+
+            begin = waypoint;
+        }
+
+        self.stats.expansions += astar.stats.expansions;
+        self.scratch = astar.scratch;
+
+        (!path.is_empty()).then_some(path)
+    }
+
+    /// Remove every obstacle and reset the statistics, keeping the same grid.
+    pub fn clear(&mut self) {
+        self.field = CostField::new(self.field.grid);
+        self.corridors = None;
+        self.scratch.clear();
+        self.stats = RouteStats {
+            cell_size: self.field.grid.cell,
+            ..Default::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use egui::{Rect, pos2, vec2};
+
+    fn scene() -> Rect {
+        Rect::from_min_size(pos2(0.0, 0.0), vec2(600.0, 400.0))
+    }
+
+    /// A few blocks in between the two ends of a route, so there's more than one way around.
+    fn blocks() -> Vec<Rect> {
+        vec![
+            Rect::from_min_size(pos2(100.0, 80.0), vec2(120.0, 60.0)),
+            Rect::from_min_size(pos2(300.0, 150.0), vec2(80.0, 120.0)),
+            Rect::from_min_size(pos2(150.0, 260.0), vec2(140.0, 50.0)),
+        ]
+    }
+
+    fn context(config: RouteConfig) -> RouteContext {
+        let mut context = RouteContext::new(scene(), config);
+
+        for rect in blocks() {
+            context.add_obstacle(rect, 3.0);
+        }
+
+        context
+    }
+
+    #[test]
+    fn routing_is_deterministic() {
+        let (from, to) = (pos2(20.0, 20.0), pos2(560.0, 360.0));
+
+        for bidirectional in [false, true] {
+            let config = RouteConfig {
+                bidirectional,
+                ..Default::default()
+            };
+
+            let mut first = context(config.clone());
+            let mut second = context(config);
+
+            let path = first.route(from, to).unwrap();
+
+            assert_eq!(path, second.route(from, to).unwrap());
+
+            // the scratch state left over from the first route can't change the second one.
+            assert_eq!(path, first.route(from, to).unwrap());
+        }
+    }
+
+    #[test]
+    fn removing_every_block_rect_gives_the_pristine_field() {
+        let grid = Grid::splat(scene(), 3.0);
+        let pristine = CostField::new(grid);
+
+        // a small lcg, so that the rects are the same on every run.
+        let mut seed = 0x2545_f491_u32;
+        let mut next = |max: f32| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32 * max
+        };
+
+        for n in [1, 2, 5, 20, 50] {
+            let rects: Vec<(Rect, f32)> = (0..n)
+                .map(|_| {
+                    let min = pos2(next(560.0), next(360.0));
+                    let rect = Rect::from_min_size(min, vec2(next(80.0) + 1.0, next(80.0) + 1.0));
+                    (rect, next(6.0) + 1.0)
+                })
+                .collect();
+
+            let mut field = pristine.clone();
+
+            for &(rect, radius) in &rects {
+                field.add_block_rect(rect, radius);
+            }
+
+            // removing half of them is the same as only ever adding the other half.
+            let (removed, kept): (Vec<_>, Vec<_>) =
+                rects.iter().enumerate().partition(|(i, _)| i % 2 == 0);
+
+            for &(_, &(rect, radius)) in removed.iter().rev() {
+                assert!(field.remove_block_rect(rect, radius));
+            }
+
+            let mut expected = pristine.clone();
+
+            for &(_, &(rect, radius)) in &kept {
+                expected.add_block_rect(rect, radius);
+            }
+
+            assert_eq!(field.cost, expected.cost, "{n} rects, half removed");
+
+            for &(_, &(rect, radius)) in &kept {
+                assert!(field.remove_block_rect(rect, radius));
+            }
+
+            assert_eq!(field.cost, pristine.cost, "{n} rects, all removed");
+            assert!(field.obstacles.is_empty());
+            assert!(!field.remove_block_rect(rects[0].0, rects[0].1));
+        }
+    }
+
+    #[test]
+    fn seeded_routes_avoid_blocks_and_restore_the_field() {
+        let (from, to) = (pos2(20.0, 20.0), pos2(560.0, 360.0));
+
+        let mut context = context(RouteConfig {
+            stickiness: 1.0,
+            ..Default::default()
+        });
+
+        let pristine = context.field().cost.clone();
+
+        // a seed straight through the blocks, which has to be routed around.
+        let seed = vec![from, pos2(340.0, 210.0), to];
+
+        let path = context.route_seeded(from, to, &seed).unwrap();
+        let grid = *context.grid();
+
+        assert!(
+            path.iter()
+                .all(|p| context.field().cost_at(grid.to_cell(*p)) != Some(f32::MAX)),
+            "the seeded route went through a block"
+        );
+
+        assert_eq!(context.field().cost, pristine);
+    }
+
+    #[test]
+    fn ports_just_outside_the_grid_do_not_trace_its_border() {
+        let mut context = context(RouteConfig::default());
+        let grid = *context.grid();
+
+        // a hair above the top of the grid, like the port of the topmost block.
+        let (from, to) = (pos2(40.0, grid.origin.y - 1.0), pos2(560.0, 360.0));
+        let path = context
+            .route(from, to)
+            .expect("there is a way around the blocks");
+
+        let border = grid.origin.y + grid.cell.y;
+
+        // the whole length of the path that is in the top row of cells.
+        let along_border: f32 = path
+            .windows(2)
+            .filter(|w| w[0].y <= border && w[1].y <= border)
+            .map(|w| w[0].distance(w[1]))
+            .sum();
+
+        assert!(
+            along_border <= grid.cell.x * 2.0,
+            "{along_border} along the border of {path:?}"
+        );
+
+        // anything further out isn't clamped at all.
+        let far = pos2(40.0, grid.origin.y - grid.cell.y * 10.0);
+        assert!(matches!(
+            context.route(far, to),
+            Err(RouteError::OutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn cells_are_scaled_to_fit_the_budget() {
+        let config = RouteConfig {
+            cell_size: vec2(3.0, 5.0),
+            max_cells: Some(10_000),
+            ..Default::default()
+        };
+
+        for size in [
+            vec2(600.0, 400.0),
+            vec2(12_345.6, 78.9),
+            vec2(40.0, 90_000.0),
+        ] {
+            let scene = Rect::from_min_size(pos2(-17.3, 4.1), size);
+            let cell = config.cell_size_for(scene);
+            let grid = Grid::from_scene(scene, cell);
+
+            assert!(grid.cols * grid.rows <= 10_000, "{grid:?} for {scene:?}");
+
+            // the bound is loose for narrow scenes, but never by as much as twice the size.
+            let finer = Grid::from_scene(scene, cell * 0.5);
+            assert!(finer.cols * finer.rows > 10_000, "{grid:?} for {scene:?}");
+        }
+
+        // a scene that isn't finite gets the smallest cells, rather than never returning.
+        for scene in [Rect::EVERYTHING, Rect::NAN] {
+            assert_eq!(config.cell_size_for(scene), config.cell_size);
+        }
+    }
+
+    #[test]
+    fn margins_stay_as_many_cells_wide() {
+        let config = RouteConfig {
+            max_cells: Some(2_000),
+            ..Default::default()
+        };
+
+        for margin in [0.0, 2.0, 5.0] {
+            let bounds = Rect::from_min_size(pos2(-40.0, 13.0), vec2(900.0, 2_400.0));
+            let cell = config.cell_size_around(bounds, margin);
+            let scene = bounds.expand2(cell * margin);
+
+            let scaled = config.cell_size_for(scene);
+            let grid = Grid::from_scene(scene, scaled);
+
+            assert!(
+                (scaled - cell).length() < cell.length() * 1e-3,
+                "{scaled:?} {cell:?}"
+            );
+            assert!(grid.cols * grid.rows <= 2_000, "{grid:?}");
+        }
     }
 }
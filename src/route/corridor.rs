@@ -0,0 +1,169 @@
+//! A coarse graph of the free space between the layers of blocks.
+//!
+//! Long edges get planned over this graph first, picking which gap between two blocks of
+//! every layer they cross, and then each hop is refined with cell level A* restricted to
+//! the area around that hop.
+
+/// A horizontal band of blocks, and the free passages that go through it.
+struct Layer {
+    top: f32,
+    bottom: f32,
+    /// The free space in between the blocks of this layer, sorted from left to right.
+    passages: Vec<egui::Rect>,
+}
+
+pub(super) struct CorridorGraph {
+    /// Every layer, sorted from top to bottom.
+    layers: Vec<Layer>,
+}
+
+impl CorridorGraph {
+    /// Build the corridor graph from the obstacles in `bounds`, ignoring passages that are
+    /// narrower than `min_width`.
+    pub(super) fn new(obstacles: &[(egui::Rect, f32)], bounds: egui::Rect, min_width: f32) -> Self {
+        let mut rects: Vec<egui::Rect> = obstacles.iter().map(|(rect, _)| *rect).collect();
+
+        rects.sort_by(|a, b| a.min.y.total_cmp(&b.min.y));
+
+        // blocks whose vertical extents overlap belong to the same layer.
+        let mut bands: Vec<(f32, f32, Vec<egui::Rect>)> = Vec::new();
+
+        for rect in rects {
+            match bands.last_mut() {
+                Some((_, bottom, members)) if rect.min.y <= *bottom => {
+                    *bottom = bottom.max(rect.max.y);
+                    members.push(rect);
+                }
+                _ => bands.push((rect.min.y, rect.max.y, vec![rect])),
+            }
+        }
+
+        let layers = bands
+            .into_iter()
+            .map(|(top, bottom, mut members)| {
+                members.sort_by(|a, b| a.min.x.total_cmp(&b.min.x));
+
+                let mut passages = Vec::new();
+                let mut left = bounds.min.x;
+
+                for rect in members {
+                    if rect.min.x - left >= min_width {
+                        passages.push(egui::Rect::from_x_y_ranges(left..=rect.min.x, top..=bottom));
+                    }
+
+                    left = left.max(rect.max.x);
+                }
+
+                if bounds.max.x - left >= min_width {
+                    passages.push(egui::Rect::from_x_y_ranges(
+                        left..=bounds.max.x,
+                        top..=bottom,
+                    ));
+                }
+
+                Layer {
+                    top,
+                    bottom,
+                    passages,
+                }
+            })
+            .collect();
+
+        Self { layers }
+    }
+
+    /// Plan a path from `from` to `to` over the corridors.
+    ///
+    /// This returns the waypoints after `from`, each with the area the hop leading to it
+    /// should be searched in, or `None` if the path crosses fewer than `min_layer_span`
+    /// layers, or one of the layers has no passage.
+    pub(super) fn plan(
+        &self,
+        from: egui::Pos2,
+        to: egui::Pos2,
+        min_layer_span: usize,
+        margin: egui::Vec2,
+    ) -> Option<Vec<(egui::Pos2, egui::Rect)>> {
+        let (upper, lower) = (from.y.min(to.y), from.y.max(to.y));
+
+        // the layers that lie completely in between the two ends, in the order we cross them.
+        let mut crossed: Vec<&Layer> = self
+            .layers
+            .iter()
+            .filter(|layer| layer.top > upper && layer.bottom < lower)
+            .collect();
+
+        if crossed.len() < min_layer_span.max(1) {
+            return None;
+        }
+
+        if from.y > to.y {
+            crossed.reverse();
+        }
+
+        // for every passage of the current layer, the cheapest horizontal travel to reach it,
+        // and the passage of the previous layer it came from.
+        let mut costs: Vec<Vec<(f32, usize)>> = Vec::with_capacity(crossed.len());
+
+        for (i, layer) in crossed.iter().enumerate() {
+            if layer.passages.is_empty() {
+                return None;
+            }
+
+            let row = layer
+                .passages
+                .iter()
+                .map(|passage| {
+                    let x = passage.center().x;
+
+                    match i.checked_sub(1) {
+                        None => ((x - from.x).abs(), 0),
+                        Some(prev) => crossed[prev]
+                            .passages
+                            .iter()
+                            .enumerate()
+                            .map(|(j, p)| (costs[prev][j].0 + (x - p.center().x).abs(), j))
+                            .min_by(|a, b| a.0.total_cmp(&b.0))
+                            .unwrap_or((f32::INFINITY, 0)),
+                    }
+                })
+                .collect();
+
+            costs.push(row);
+        }
+
+        // pick the passage of the last layer that ends closest to the goal.
+        let last = crossed.len() - 1;
+
+        let mut current = crossed[last]
+            .passages
+            .iter()
+            .enumerate()
+            .map(|(j, p)| (costs[last][j].0 + (to.x - p.center().x).abs(), j))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?
+            .1;
+
+        // walk back through the chosen passages.
+        let mut chosen = vec![crossed[last].passages[current]];
+
+        for i in (1..=last).rev() {
+            current = costs[i][current].1;
+            chosen.push(crossed[i - 1].passages[current]);
+        }
+
+        chosen.reverse();
+
+        let point = |p: egui::Pos2| egui::Rect::from_min_max(p, p);
+
+        let mut hops = Vec::with_capacity(chosen.len() + 1);
+        let mut previous = point(from);
+
+        for passage in chosen.into_iter().chain(std::iter::once(point(to))) {
+            let waypoint = passage.center();
+            hops.push((waypoint, previous.union(passage).expand2(margin)));
+            previous = passage;
+        }
+
+        Some(hops)
+    }
+}
@@ -1,27 +1,193 @@
-use egui::{self, Color32, FontId, Stroke, TextStyle, Vec2, vec2};
+#[cfg(feature = "serde")]
+mod repr;
 
-use crate::BlockLike;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use egui::{self, Color32, CornerRadius, FontId, Stroke, TextStyle, Vec2, emath::easing, vec2};
+
+use crate::EdgeKind;
+
+/// The curve a [`GlowStyle`] grows along.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GlowEasing {
+    Linear,
+    CubicOut,
+    /// Overshoots slightly before settling, which makes the glow feel a bit springy.
+    #[default]
+    BackOut,
+}
+
+impl GlowEasing {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Self::Linear => easing::linear(t),
+            Self::CubicOut => easing::cubic_out(t),
+            Self::BackOut => easing::back_out(t),
+        }
+    }
+}
+
+/// The glow drawn around hovered and selected things.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlowStyle {
+    /// The width the glow grows to, a width of zero disables it.
+    pub max_width: f32,
+    pub easing: GlowEasing,
+    /// The opacity of [`NodeStyle::select`] that the glow is drawn with.
+    pub opacity: f32,
+    /// How long it takes the glow to grow, in seconds.
+    pub duration: f32,
+}
+
+impl Default for GlowStyle {
+    fn default() -> Self {
+        Self {
+            max_width: 4.0,
+            easing: GlowEasing::default(),
+            opacity: 0.5,
+            duration: egui::Style::default().animation_time,
+        }
+    }
+}
+
+/// How wide every block is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockWidth {
+    /// Every block is as wide as [`NodeStyle::size`].
+    #[default]
+    Fixed,
+    /// Every block is as wide as its body or its header, whichever is wider, within these
+    /// bounds. The body wraps at `max_width`.
+    FitContent { min_width: f32, max_width: f32 },
+}
+
+/// The outline drawn on the inside of a block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockBorder {
+    pub stroke: Stroke,
+    pub dashed: bool,
+}
+
+impl From<Stroke> for BlockBorder {
+    fn from(stroke: Stroke) -> Self {
+        Self {
+            stroke,
+            dashed: false,
+        }
+    }
+}
 
 /// This is the style of the Basic Block graph node.
 ///
 /// a.k.a how it actaully appears when rendered.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NodeStyle {
+    #[cfg_attr(feature = "serde", serde(with = "repr::Vec2Def"))]
     pub size: egui::Vec2,
+    pub width: BlockWidth,
     /// The n,w,e,s padding inside of the node.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Vec2Def"))]
     pub padding: egui::Vec2,
+    #[cfg_attr(feature = "serde", serde(with = "repr::Vec2Def"))]
     pub button_padding: egui::Vec2,
-    pub rounding: u8,
+    /// The rounding of the top corners of a block, where the header is.
+    ///
+    /// The bottom corners are only used for blocks that are collapsed to their header.
+    #[cfg_attr(feature = "serde", serde(with = "repr::CornerRadiusDef"))]
+    pub header_rounding: CornerRadius,
+    /// The rounding of the bottom corners of a block, where the body is.
+    #[cfg_attr(feature = "serde", serde(with = "repr::CornerRadiusDef"))]
+    pub body_rounding: CornerRadius,
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
     pub fill: Color32,
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
     pub header_fill: Color32,
+    /// The header fill of blocks where [`crate::BlockLike::is_entry`] is true.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub entry_header_fill: Color32,
+    /// The header fill of blocks where [`crate::BlockLike::is_exit`] is true.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub exit_header_fill: Color32,
+    /// Whether to put a ▶ or ⏹ glyph in front of the title of entry and exit blocks.
+    pub terminal_glyphs: bool,
+    /// The outline of blocks that aren't an entry or an exit.
+    #[cfg_attr(feature = "serde", serde(with = "repr::StrokeDef"))]
     pub stroke: Stroke,
+    /// The outline of blocks where [`crate::BlockLike::is_entry`] is true.
+    #[cfg_attr(feature = "serde", serde(with = "repr::StrokeDef"))]
+    pub entry_stroke: Stroke,
+    /// The outline of blocks where [`crate::BlockLike::is_exit`] is true.
+    #[cfg_attr(feature = "serde", serde(with = "repr::StrokeDef"))]
+    pub exit_stroke: Stroke,
     /// The height of the header, or title box.
     pub header_height: f32,
+    #[cfg_attr(feature = "serde", serde(with = "repr::FontIdDef"))]
     pub label_font: FontId,
+    /// The color of the [`crate::BlockLike::address`] in the header.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub header_address_color: Color32,
+    #[cfg_attr(feature = "serde", serde(with = "repr::FontIdDef"))]
     pub text_font: FontId,
+    /// The space between the mnemonics and the operands of [`crate::BlockLike::body_columns`].
+    pub column_gap: f32,
+    /// The color of the body text, unless [`crate::BlockLike::body_job`] sets its own.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub text_color: Color32,
+    /// The color of the title, and everything else in the header.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub label_color: Color32,
+    #[cfg_attr(feature = "serde", serde(with = "repr::StrokeDef"))]
     pub edge: Stroke,
+    /// The color of [`crate::EdgeKind::Taken`] edges, instead of the [`Self::edge`] color.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub taken_edge: Color32,
+    /// The color of [`crate::EdgeKind::FallThrough`] edges, instead of the [`Self::edge`]
+    /// color.
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub fall_through_edge: Color32,
+    /// The least space between two ports on the same side of a block. Blocks with more
+    /// edges than fit at this spacing get wider.
+    pub port_spacing: f32,
+    #[cfg_attr(feature = "serde", serde(with = "repr::StrokeDef"))]
     pub select: Stroke,
+    /// The glow around hovered and selected blocks.
+    pub glow: GlowStyle,
+    /// The color blended over the fill of the selected block, if any.
+    #[cfg_attr(feature = "serde", serde(with = "repr::color"))]
+    pub selected_fill: Option<Color32>,
+    /// The color blended over the header of the selected block, if any.
+    #[cfg_attr(feature = "serde", serde(with = "repr::color"))]
+    pub selected_header_fill: Option<Color32>,
+    /// How much of [`Self::selected_fill`] and [`Self::selected_header_fill`] hovered blocks
+    /// get, from 0 to 1.
+    pub hover_fill_strength: f32,
+    /// The shadow drawn under every block, if any.
+    #[cfg_attr(feature = "serde", serde(with = "repr::shadow"))]
+    pub shadow: Option<egui::Shadow>,
+    /// Below this zoom factor, the body text is replaced with a placeholder, and only the
+    /// title is left in the header.
+    pub lod_text_zoom: f32,
+    /// Below this zoom factor, not even the title is drawn.
+    pub lod_title_zoom: f32,
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
     pub select_bg: Color32,
+    /// The background of body lines passed to [`crate::view::CfgView::highlighted_lines`].
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub line_highlight: Color32,
+    /// The background of every match of [`crate::view::CfgView::search`].
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub search_highlight: Color32,
+    /// The underline of the links found by [`crate::view::CfgView::link_matcher`].
+    #[cfg_attr(feature = "serde", serde(with = "repr::Color32Def"))]
+    pub link_color: Color32,
+    /// The colors of the coldest and the hottest blocks of [`crate::view::CfgView::heat`],
+    /// which get blended over the fill.
+    #[cfg_attr(feature = "serde", serde(with = "repr::gradient"))]
+    pub heat_gradient: (Color32, Color32),
 }
 
 impl NodeStyle {
@@ -37,20 +203,187 @@ impl NodeStyle {
         let inactive = &visuals.widgets.inactive;
         let spacing = &style.spacing;
 
+        let entry_header_fill = inactive
+            .bg_fill
+            .lerp_to_gamma(Color32::from_rgb(40, 140, 70), 0.5);
+        let exit_header_fill = inactive
+            .bg_fill
+            .lerp_to_gamma(Color32::from_rgb(170, 50, 50), 0.5);
+
+        // the outline has the same color as the header by default.
+        let outline = |color| Stroke {
+            color,
+            ..non_interactive.bg_stroke
+        };
+
         Self {
             size: vec2(260.0, 120.0),
+            width: BlockWidth::default(),
             padding: Vec2::new(10.0, 10.0),
             button_padding: spacing.button_padding,
-            rounding: non_interactive.corner_radius.nw,
+            header_rounding: inactive.corner_radius,
+            body_rounding: non_interactive.corner_radius,
             fill: visuals.code_bg_color,
             header_fill: inactive.bg_fill,
-            stroke: non_interactive.bg_stroke,
+            entry_header_fill,
+            exit_header_fill,
+            terminal_glyphs: true,
+            stroke: outline(inactive.bg_fill),
+            entry_stroke: outline(entry_header_fill),
+            exit_stroke: outline(exit_header_fill),
             header_height: spacing.interact_size.y,
             label_font: mono.clone(),
+            header_address_color: visuals.weak_text_color(),
             text_font: mono,
+            column_gap: spacing.item_spacing.x * 2.0,
+            text_color: visuals.text_color(),
+            label_color: non_interactive.fg_stroke.color,
             edge: non_interactive.fg_stroke,
+            // green and red, like in most disassemblers.
+            taken_edge: Color32::from_rgb(90, 170, 90),
+            fall_through_edge: Color32::from_rgb(200, 90, 90),
+            port_spacing: 16.0,
             select: style.visuals.selection.stroke,
+            glow: GlowStyle {
+                duration: style.animation_time,
+                ..Default::default()
+            },
+            shadow: None,
+            selected_fill: None,
+            selected_header_fill: None,
+            hover_fill_strength: 0.4,
+            lod_text_zoom: 0.4,
+            lod_title_zoom: 0.2,
             select_bg: style.visuals.selection.bg_fill,
+            line_highlight: style.visuals.selection.bg_fill.gamma_multiply(0.4),
+            search_highlight: visuals.warn_fg_color.gamma_multiply(0.4),
+            link_color: visuals.hyperlink_color,
+            heat_gradient: (
+                Color32::from_rgba_unmultiplied(60, 110, 220, 50),
+                Color32::from_rgba_unmultiplied(235, 70, 40, 150),
+            ),
+        }
+    }
+
+    /// The width that the body text wraps at.
+    pub fn content_width(&self) -> f32 {
+        let width = match self.width {
+            BlockWidth::Fixed => self.size.x,
+            BlockWidth::FitContent { max_width, .. } => max_width,
+        };
+
+        width - self.padding.x * 2.0
+    }
+
+    /// A hash of every field that changes the size of a block or its text.
+    ///
+    /// The fills, strokes and colors only change how the blocks are painted, so they're left
+    /// out and changing them never lays the blocks out again.
+    pub fn geometry_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        let lengths = [
+            self.size.x,
+            self.size.y,
+            self.padding.x,
+            self.padding.y,
+            self.button_padding.x,
+            self.button_padding.y,
+            self.header_height,
+            self.column_gap,
+        ];
+
+        for length in lengths {
+            length.to_bits().hash(&mut hasher);
+        }
+
+        match self.width {
+            BlockWidth::Fixed => 0u8.hash(&mut hasher),
+            BlockWidth::FitContent {
+                min_width,
+                max_width,
+            } => (1u8, min_width.to_bits(), max_width.to_bits()).hash(&mut hasher),
+        }
+
+        self.text_font.hash(&mut hasher);
+        self.label_font.hash(&mut hasher);
+        self.terminal_glyphs.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// The style that goes with egui's dark theme.
+    pub fn dark() -> Self {
+        Self::from_visuals(egui::Visuals::dark())
+    }
+
+    /// The style that goes with egui's light theme.
+    pub fn light() -> Self {
+        Self::from_visuals(egui::Visuals::light())
+    }
+
+    /// A dark style with pure black blocks, white text and thick, bright strokes.
+    pub fn high_contrast() -> Self {
+        let select = Color32::from_rgb(255, 210, 0);
+
+        Self {
+            fill: Color32::BLACK,
+            header_fill: Color32::from_gray(45),
+            entry_header_fill: Color32::from_rgb(0, 110, 40),
+            exit_header_fill: Color32::from_rgb(150, 0, 0),
+            stroke: Stroke::new(1.5, Color32::WHITE),
+            entry_stroke: Stroke::new(1.5, Color32::from_rgb(0, 220, 80)),
+            exit_stroke: Stroke::new(1.5, Color32::from_rgb(255, 60, 60)),
+            header_address_color: Color32::from_gray(200),
+            text_color: Color32::WHITE,
+            label_color: Color32::WHITE,
+            edge: Stroke::new(1.5, Color32::WHITE),
+            select: Stroke::new(2.5, select),
+            select_bg: select.gamma_multiply(0.5),
+            line_highlight: select.gamma_multiply(0.3),
+            ..Self::dark()
+        }
+    }
+
+    fn from_visuals(visuals: egui::Visuals) -> Self {
+        Self::from_style(&egui::Style {
+            visuals,
+            ..Default::default()
+        })
+    }
+
+    /// The rounding of the outline of a whole block, the top from the header and the
+    /// bottom from the body.
+    pub fn block_rounding(&self) -> CornerRadius {
+        CornerRadius {
+            nw: self.header_rounding.nw,
+            ne: self.header_rounding.ne,
+            sw: self.body_rounding.sw,
+            se: self.body_rounding.se,
+        }
+    }
+
+    /// The color of an edge of `kind`, with the [`Self::edge`] color for unconditional ones
+    /// and the lines of stubs, which don't have a kind.
+    pub fn edge_color(&self, kind: Option<EdgeKind>) -> Color32 {
+        match kind {
+            Some(EdgeKind::Taken) => self.taken_edge,
+            Some(EdgeKind::FallThrough) => self.fall_through_edge,
+            Some(EdgeKind::Unconditional) | None => self.edge.color,
+        }
+    }
+
+    /// Like [`NodeStyle::block_rounding`], but no corner is rounded more than half of the
+    /// smaller side of `rect`, so that corners of a small block don't run into each other.
+    pub fn block_rounding_in(&self, rect: egui::Rect) -> CornerRadius {
+        let limit = (rect.size().min_elem() / 2.0).clamp(0.0, u8::MAX as f32) as u8;
+        let rounding = self.block_rounding();
+
+        CornerRadius {
+            nw: rounding.nw.min(limit),
+            ne: rounding.ne.min(limit),
+            sw: rounding.sw.min(limit),
+            se: rounding.se.min(limit),
         }
     }
 }
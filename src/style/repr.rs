@@ -0,0 +1,186 @@
+//! Serde representations for the egui types used in [`super::NodeStyle`].
+//!
+//! These are used through `#[serde(with = "..")]`, so that we don't need serde support
+//! in egui itself.
+
+use std::sync::Arc;
+
+use egui::{Color32, CornerRadius, FontFamily, FontId, Shadow, Stroke, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// A color as its premultiplied `[r, g, b, a]`, so that it round-trips exactly.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Color32")]
+pub(super) struct Color32Def(#[serde(getter = "Color32::to_array")] [u8; 4]);
+
+impl From<Color32Def> for Color32 {
+    fn from(def: Color32Def) -> Self {
+        let [r, g, b, a] = def.0;
+        Color32::from_rgba_premultiplied(r, g, b, a)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Vec2")]
+pub(super) struct Vec2Def {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Stroke")]
+pub(super) struct StrokeDef {
+    width: f32,
+    #[serde(with = "Color32Def")]
+    color: Color32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CornerRadius")]
+pub(super) struct CornerRadiusDef {
+    nw: u8,
+    ne: u8,
+    sw: u8,
+    se: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "FontFamily")]
+enum FontFamilyDef {
+    Proportional,
+    Monospace,
+    /// One of the families added with [`egui::FontDefinitions`], by name.
+    Name(Arc<str>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "FontId")]
+pub(super) struct FontIdDef {
+    size: f32,
+    #[serde(with = "FontFamilyDef")]
+    family: FontFamily,
+}
+
+/// A gradient as its two colors, each like [`Color32Def`].
+pub(super) mod gradient {
+    use egui::Color32;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(in crate::style) fn serialize<S: Serializer>(
+        gradient: &(Color32, Color32),
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        (gradient.0.to_array(), gradient.1.to_array()).serialize(serializer)
+    }
+
+    pub(in crate::style) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<(Color32, Color32), D::Error> {
+        let color = |[r, g, b, a]: [u8; 4]| Color32::from_rgba_premultiplied(r, g, b, a);
+        let (from, to) = <([u8; 4], [u8; 4])>::deserialize(deserializer)?;
+
+        Ok((color(from), color(to)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Shadow")]
+struct ShadowDef {
+    offset: [i8; 2],
+    blur: u8,
+    spread: u8,
+    #[serde(with = "Color32Def")]
+    color: Color32,
+}
+
+/// An optional shadow, like [`ShadowDef`].
+pub(super) mod shadow {
+    use egui::Shadow;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "super::ShadowDef")] Shadow);
+
+    pub(in crate::style) fn serialize<S: Serializer>(
+        shadow: &Option<Shadow>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        shadow.map(Wrapper).serialize(serializer)
+    }
+
+    pub(in crate::style) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Shadow>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|w| w.0))
+    }
+}
+
+/// An optional color, like [`Color32Def`].
+pub(super) mod color {
+    use egui::Color32;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "super::Color32Def")] Color32);
+
+    pub(in crate::style) fn serialize<S: Serializer>(
+        color: &Option<Color32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        color.map(Wrapper).serialize(serializer)
+    }
+
+    pub(in crate::style) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Color32>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|w| w.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, CornerRadius, FontFamily, FontId, Shadow, Stroke, vec2};
+
+    use crate::style::{BlockWidth, GlowEasing, GlowStyle, NodeStyle};
+
+    #[test]
+    fn styles_round_trip_through_json() {
+        let custom = NodeStyle {
+            width: BlockWidth::FitContent {
+                min_width: 80.0,
+                max_width: 420.0,
+            },
+            padding: vec2(6.0, 4.0),
+            body_rounding: CornerRadius {
+                nw: 0,
+                ne: 3,
+                sw: 7,
+                se: 255,
+            },
+            // premultiplied, so that a translucent color has to come back exactly.
+            fill: Color32::from_rgba_premultiplied(10, 20, 30, 40),
+            stroke: Stroke::new(1.5, Color32::RED),
+            text_font: FontId::new(15.0, FontFamily::Name("Iosevka".into())),
+            selected_fill: Some(Color32::LIGHT_BLUE),
+            shadow: Some(Shadow {
+                offset: [-2, 3],
+                blur: 8,
+                spread: 1,
+                color: Color32::from_black_alpha(90),
+            }),
+            heat_gradient: (Color32::BLUE, Color32::YELLOW),
+            glow: GlowStyle {
+                easing: GlowEasing::CubicOut,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for style in [NodeStyle::default(), custom] {
+            let json = serde_json::to_string(&style).unwrap();
+            let back: NodeStyle = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(back, style, "{json}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Made up control flow graphs of any size, for benchmarking and trying out the view.
+
+use petgraph::{graph::NodeIndex, stable_graph::StableGraph};
+
+use crate::{BlockLike, EdgeKind};
+
+/// A block of a [`synthetic_cfg`].
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub addr: u64,
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+impl BlockLike for Block {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn body_lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    fn address(&self) -> Option<u64> {
+        Some(self.addr)
+    }
+
+    fn is_entry(&self) -> bool {
+        self.addr == 0
+    }
+}
+
+/// A tiny splitmix64 generator, so the same seed gives the same graph everywhere.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, which has to be more than zero.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A number in `0.0..1.0`.
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// A function of `blocks` blocks with about `avg_lines` instructions each, where every block
+/// ends in a conditional branch with a chance of `branch_prob`.
+///
+/// Most branches go forward, but some go back to make loops, and the last block returns.
+/// The graph only depends on the arguments, so the same `seed` always gives the same graph.
+pub fn synthetic_cfg(
+    blocks: usize,
+    avg_lines: usize,
+    branch_prob: f32,
+    seed: u64,
+) -> StableGraph<Block, EdgeKind> {
+    const MNEMONICS: &[&str] = &["mov", "add", "sub", "xor", "lea", "cmp", "test", "and"];
+    const REGISTERS: &[&str] = &["rax", "rbx", "rcx", "rdx", "rsi", "rdi", "r8", "r9"];
+
+    let mut rng = Rng(seed);
+    let mut graph = StableGraph::new();
+    let mut addr = 0;
+
+    let nodes: Vec<NodeIndex> = (0..blocks)
+        .map(|i| {
+            // anywhere from one line to twice the average.
+            let count = 1 + rng.below(avg_lines.max(1) * 2 - 1);
+
+            let lines = (0..count)
+                .map(|_| {
+                    let mnemonic = MNEMONICS[rng.below(MNEMONICS.len())];
+                    let dst = REGISTERS[rng.below(REGISTERS.len())];
+                    let src = REGISTERS[rng.below(REGISTERS.len())];
+
+                    format!("{mnemonic} {dst}, {src}")
+                })
+                .collect();
+
+            let block = Block {
+                addr,
+                title: format!("block_{i}"),
+                lines,
+            };
+
+            // with room for the branch or return at the end.
+            addr += 4 * (count as u64 + 1);
+
+            graph.add_node(block)
+        })
+        .collect();
+
+    for (i, &node) in nodes.iter().enumerate() {
+        let Some(&next) = nodes.get(i + 1) else {
+            graph[node].lines.push("ret".into());
+            break;
+        };
+
+        if rng.unit() >= branch_prob {
+            graph.add_edge(node, next, EdgeKind::Unconditional);
+            continue;
+        }
+
+        // one in four branches loops back, the rest skip ahead a little.
+        let target = if rng.below(4) == 0 {
+            rng.below(i + 1)
+        } else {
+            (i + 2 + rng.below(8)).min(blocks - 1)
+        };
+
+        let target_addr = graph[nodes[target]].addr;
+
+        graph[node].lines.push(format!("jne {target_addr:#x}"));
+        graph.add_edge(node, nodes[target], EdgeKind::Taken);
+        graph.add_edge(node, next, EdgeKind::FallThrough);
+    }
+
+    graph
+}
@@ -1,22 +1,58 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::BlockLike;
+use crate::CfgFingerprint;
 use crate::CfgLayout;
 use crate::EdgeKind;
 use crate::EdgeLike;
 use crate::LayoutConfig;
 use crate::get_cfg_layout;
-use crate::route::{AStar, CostField, Grid};
-use crate::style::NodeStyle;
-use egui::emath::easing;
-use egui::{Align2, Color32, CornerRadius, Pos2, Rect, Stroke, StrokeKind, Ui, pos2, vec2};
-use petgraph::graph::NodeIndex;
+use crate::route::{OBSTACLE_RADIUS, RouteConfig, RouteContext};
+use crate::style::{BlockBorder, NodeStyle};
+use egui::containers::DragPanButtons;
+use egui::text::{LayoutJob, TextWrapping};
+use egui::{
+    Align2, Color32, CornerRadius, Galley, Pos2, Rangef, Rect, Stroke, StrokeKind, Ui, Vec2, pos2,
+    vec2,
+};
+use petgraph::Direction;
+use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::prelude::StableGraph;
 use petgraph::visit::EdgeRef;
 
+mod galleys;
+mod spatial;
+mod state;
+mod stats;
+#[cfg(test)]
+mod tests;
+
+pub use spatial::Hit;
+pub use state::CfgViewState;
+pub use stats::{CfgStats, CfgStatsWindow};
+
+use spatial::SpatialIndex;
+use state::{
+    Animations, BoxSelection, EdgeMesh, FieldCache, HoverTrack, Peek, PendingRoutes, PortCache,
+    Slice, TextSelection,
+};
+
 /// The offset from the port to the basic block rectangle.
 const PORT_OFFSET: f32 = 4.0;
 
+/// The smallest a block is on the screen to hit it, however far the view is zoomed out.
+const MIN_HIT_SIZE: f32 = 12.0;
+
+/// How much of the size of the layout the scene has around it, on every side together.
+const SCENE_MARGIN_FRACTION: f32 = 0.5;
+
+/// How long, in seconds, zooming to a block and back takes with [`CfgView::peek_zoom`].
+const PEEK_DURATION: f64 = 0.15;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PortKind {
     Input,
@@ -40,467 +76,3747 @@ impl PortSlot {
 pub struct PortLine {
     pub from: PortSlot,
     pub to: PortSlot,
+    /// The edge of the graph the line is for, which stubs don't have.
+    pub edge: Option<EdgeIndex>,
 }
 
-pub struct CfgView<'a, N: BlockLike, E: EdgeLike> {
-    graph: StableGraph<N, E>,
-    layout_config: LayoutConfig,
-    block_rects: HashMap<NodeIndex, Rect>,
-    port_positions: HashMap<PortSlot, Pos2>,
-    port_lines: Vec<PortLine>,
-    pub style: &'a NodeStyle,
-    selected: &'a mut Option<NodeIndex>,
+/// What happened in the view during a single [`CfgView::show`].
+#[derive(Clone, Debug)]
+pub struct CfgResponse {
+    /// The block and the index into its [`BlockLike::body_lines`] that was clicked, if any.
+    pub line_clicked: Option<(NodeIndex, usize)>,
+    /// The block and the index into its [`BlockLike::badges`] that was clicked, if any.
+    pub badge_clicked: Option<(NodeIndex, usize)>,
+    /// The index into [`CfgView::external_targets`] of the stub that was clicked, if any.
+    pub external_target_clicked: Option<usize>,
+    /// The lowest and highest [`CfgView::heat`] values, which the heat of every block is
+    /// normalized between.
+    pub heat_range: Option<Rangef>,
+    /// How many times the [`CfgView::search`] query occurs in every block that has it,
+    /// sorted by node.
+    pub search_matches: Vec<(NodeIndex, usize)>,
+    /// Whether the selected block was deselected by clicking on the background, by
+    /// pressing Escape, or because it was removed from the graph.
+    pub selection_cleared: bool,
+    /// Whether anything else about blocks that were removed from the graph since the last
+    /// frame had to be dropped, like them being pinned, collapsed or flashing.
+    pub state_dropped: bool,
+    /// Whether Escape deselected [`CfgViewState::selected_edge`].
+    pub edge_selection_cleared: bool,
+    /// Whether Escape hid the highlights of [`CfgView::search`]. They stay hidden until
+    /// the query changes, so the app will usually want to clear it too.
+    pub search_cleared: bool,
+    /// Whether Escape dropped the block that Tab cycled to.
+    pub candidate_cleared: bool,
+    /// The port under the pointer, if any.
+    pub port_hovered: Option<PortSlot>,
+    /// The block that was Alt+clicked, along with every block that can reach it, or that it
+    /// can reach with Shift+Alt, sorted by node. This stays until
+    /// [`CfgViewState::clear_slice`].
+    pub slice: Vec<NodeIndex>,
+    /// The part of the scene that is visible, in the same coordinates as the blocks.
+    pub visible_rect: Rect,
+    /// Every block that is at least partly inside of [`Self::visible_rect`], sorted by node.
+    pub visible_nodes: Vec<NodeIndex>,
+    /// Whether [`Self::visible_rect`] changed since the last frame, by panning, zooming or
+    /// resizing the view.
+    pub viewport_changed: bool,
+    /// Where the background was right clicked, away from any block or edge, in the same
+    /// coordinates as the blocks.
+    pub background_right_clicked: Option<Pos2>,
+    /// The block under the pointer, if any. This is never the [`CfgView::external_hover`].
+    pub hovered: Option<NodeIndex>,
+    /// The block, the index into its [`BlockLike::body_lines`] and the target of the
+    /// [`CfgView::link_matcher`] link that was clicked, if any.
+    pub link_clicked: Option<(NodeIndex, usize, LinkTarget)>,
+    /// How long every phase of the frame took, if [`CfgView::collect_stats`] is on.
+    pub stats: Option<CfgStats>,
 }
 
-impl<'a, N: BlockLike, E: EdgeLike> CfgView<'a, N, E> {
-    pub fn new(
-        graph: StableGraph<N, E>,
-        config: LayoutConfig,
-        selected: &'a mut Option<NodeIndex>,
-        style: &'a NodeStyle,
-    ) -> Self {
+impl Default for CfgResponse {
+    fn default() -> Self {
         Self {
-            graph,
-            layout_config: config,
-            style,
-            block_rects: HashMap::new(),
-            port_lines: Vec::new(),
-            port_positions: HashMap::new(),
-            selected,
+            line_clicked: None,
+            badge_clicked: None,
+            external_target_clicked: None,
+            heat_range: None,
+            search_matches: Vec::new(),
+            selection_cleared: false,
+            state_dropped: false,
+            edge_selection_cleared: false,
+            search_cleared: false,
+            candidate_cleared: false,
+            port_hovered: None,
+            slice: Vec::new(),
+            visible_rect: Rect::NOTHING,
+            visible_nodes: Vec::new(),
+            viewport_changed: false,
+            background_right_clicked: None,
+            hovered: None,
+            link_clicked: None,
+            stats: None,
         }
     }
+}
 
-    /// Get a rectangle the encompasses every block node placed.
-    fn get_world_rect(&self, expand: Option<f32>) -> Rect {
-        let mut bounds = egui::Rect::NOTHING;
-
-        // unionize all of the rects we created.
-        for rects in self.block_rects.values() {
-            bounds = bounds.union(*rects);
-        }
+/// When a phase of the frame started, if `stats` are collected.
+fn stopwatch(stats: &Option<CfgStats>) -> Option<Instant> {
+    stats.is_some().then(Instant::now)
+}
 
-        bounds.expand(expand.unwrap_or(100.0))
+/// Add the time since `start` to the `phase` of the `stats`.
+fn lap(
+    stats: &mut Option<CfgStats>,
+    start: Option<Instant>,
+    phase: fn(&mut CfgStats) -> &mut Duration,
+) {
+    if let (Some(stats), Some(start)) = (stats, start) {
+        *phase(stats) += start.elapsed();
     }
+}
 
-    fn handle_block_interaction(&mut self, ui: &mut Ui, rect: &Rect, node: &NodeIndex) {
-        let id = ui.make_persistent_id(("node", node.index()));
+/// The vertical extent of every logical line in `galley`, relative to the galley.
+///
+/// A line that wraps spans several rows, but only the rows ending with a newline (or the
+/// last row) end a logical line.
+fn logical_line_spans(galley: &Galley) -> Vec<Rangef> {
+    let mut spans: Vec<Rangef> = Vec::new();
+    let mut current: Option<Rangef> = None;
 
-        let response = ui.interact(*rect, id, egui::Sense::click());
+    for row in &galley.rows {
+        let rect = row.rect();
+        let span = Rangef::new(rect.min.y, rect.max.y);
 
-        // if we clicked on something that wasn't a rectangle.
-        if ui.input(|i| i.pointer.any_pressed()) && !response.hovered() {
-            *self.selected = None;
-        }
+        current = Some(current.map_or(span, |c| Rangef::new(c.min, span.max)));
 
-        if response.clicked() {
-            *self.selected = Some(*node)
+        if row.ends_with_newline {
+            spans.extend(current.take());
         }
+    }
 
-        let glow_on = response.hovered() || *self.selected == Some(*node);
+    spans.extend(current);
 
-        // goes from 0 to 1 over time, once we've hovered or selected.
-        let t = ui.ctx().animate_bool(id, glow_on) * 0.4;
+    spans
+}
 
-        if t > 0.0 {
-            // we will increase the outline over time.
-            let outline_width = 4.0 * easing::back_out(t);
+/// How much of every block gets drawn, depending on how far the scene is zoomed out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Detail {
+    Full,
+    /// Only the title in the header, and a placeholder for the body text.
+    Title,
+    /// Not even the title, just the shapes of the blocks.
+    Outline,
+}
 
-            ui.painter().rect(
-                *rect,
-                CornerRadius::same(self.style.rounding),
-                Color32::TRANSPARENT,
-                Stroke::new(outline_width, self.style.select.color.gamma_multiply(0.50)),
-                StrokeKind::Outside,
-            );
+impl Detail {
+    fn from_zoom(zoom: f32, style: &NodeStyle) -> Self {
+        if zoom < style.lod_title_zoom {
+            Self::Outline
+        } else if zoom < style.lod_text_zoom {
+            Self::Title
+        } else {
+            Self::Full
         }
     }
+}
 
-    /// This will draw blocks in the egui ui panel, and also push the position on the
-    /// block rectangle to a hashmap, so that we can use it later.
-    fn assign_and_draw_blocks(&mut self, ui: &mut Ui, layout: &CfgLayout) {
-        for (node, coords) in &layout.coords {
-            let (x, y) = (coords.0 as f32, coords.1 as f32);
+/// Draw everything that isn't close to the selected or hovered block at a lower opacity,
+/// see [`CfgView::focus_mode`].
+#[derive(Clone, Copy, Debug)]
+pub struct FocusMode {
+    /// How many edges away from the selected or hovered block a block can be, and still be
+    /// in focus. The direction of the edges doesn't matter.
+    pub hops: usize,
+    /// The opacity everything outside of the focus gets multiplied by.
+    pub dim: f32,
+}
 
-            // get the target basic block from the graph.
-            let block = self.graph[*node].clone();
+impl Default for FocusMode {
+    fn default() -> Self {
+        Self { hops: 1, dim: 0.25 }
+    }
+}
 
-            let style = self.style;
+/// When hovering a block turns its glow on and off, see [`CfgView::hover_config`].
+#[derive(Clone, Copy, Debug)]
+pub struct HoverConfig {
+    /// How long, in seconds, the pointer has to stay on a block before it glows, so that
+    /// moving across the graph doesn't make every block on the way flash.
+    pub delay: f32,
+    /// How long, in seconds, the glow stays on after the pointer left the block, so that
+    /// it doesn't start over when crossing the gap to one of its ports and back.
+    pub grace: f32,
+}
 
-            // get the rectangle of our basic block or just node.
-            let (mut block_rectangle, body_galley) = crate::get_block_rectangle(ui, &block, style);
+impl Default for HoverConfig {
+    fn default() -> Self {
+        Self {
+            delay: 0.05,
+            grace: 0.1,
+        }
+    }
+}
 
-            // give the rectangle the correct position.
-            block_rectangle.set_center(Pos2::new(x, y));
+/// How a block pulses when it gets [`CfgViewState::flash`]ed.
+#[derive(Clone, Copy, Debug)]
+pub struct FlashStyle {
+    pub color: Color32,
+    /// How many times the glow goes in and out.
+    pub cycles: u32,
+    /// How long a single cycle takes, in seconds.
+    pub period: f32,
+}
 
-            // TODO: have a setting that disables interaction somehow.
-            self.handle_block_interaction(ui, &block_rectangle, node);
+impl Default for FlashStyle {
+    fn default() -> Self {
+        Self {
+            color: Color32::from_rgb(255, 210, 0),
+            cycles: 2,
+            period: 0.5,
+        }
+    }
+}
 
-            // draw the entire node block.
-            ui.painter().rect(
-                block_rectangle,
-                CornerRadius::same(style.rounding),
-                style.fill,
-                egui::Stroke {
-                    color: style.header_fill,
-                    ..style.stroke
-                },
-                StrokeKind::Inside,
-            );
+/// Something in the view that keeps changing by itself, and so needs repaints to go on,
+/// see [`CfgViewState::animations`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Animation {
+    /// A block glowing in or out, for being hovered or selected.
+    Glow,
+    /// A block pulsing after [`CfgViewState::flash`].
+    Flash,
+    /// The view zooming to the [`CfgView::peek_zoom`] block, or back from it.
+    Peek,
+    /// The [`HoverConfig::delay`] or [`HoverConfig::grace`] of a block running out.
+    Hover,
+    /// Blocks that were just laid out replacing their estimated size, with
+    /// [`CfgView::lazy_text`].
+    LazyText,
+    /// Edges that are still being routed, with the [`CfgView::routing_budget`].
+    Routing,
+}
 
-            // the header rectangle, width is the size of the block, then we just add the header height.
-            let header_rectangle = Rect::from_min_max(
-                block_rectangle.min,
-                pos2(
-                    block_rectangle.max.x,
-                    block_rectangle.min.y + style.header_height,
-                ),
-            );
+/// Where a token in the body of a block links to, see [`CfgView::link_matcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkTarget {
+    Address(u64),
+    Node(NodeIndex),
+}
 
-            ui.painter().rect(
-                header_rectangle,
-                CornerRadius {
-                    nw: style.rounding,
-                    ne: style.rounding,
-                    se: 0,
-                    sw: 0,
-                },
-                style.header_fill,
-                Stroke::NONE,
-                StrokeKind::Inside,
-            );
+/// A small node standing in for something outside of the graph, see
+/// [`CfgView::external_targets`].
+#[derive(Clone, Copy, Debug)]
+struct Stub {
+    /// The index the stub got in the layout, which is never a node in the graph.
+    node: NodeIndex,
+    source: NodeIndex,
+    rect: Rect,
+}
 
-            // block title, could be empty or not.
-            let label = format!("{}", block.title());
-            // NOTE: have an option to put the title in the middle of the header rectangle.
-            let label_pos = header_rectangle.left_center() + vec2(style.button_padding.x, 0.0);
+/// How wide `node` has to be for its ports to be [`NodeStyle::port_spacing`] apart, on the
+/// side that has the most of them.
+fn ports_width<N, E>(
+    graph: &StableGraph<N, E>,
+    targets: &[(NodeIndex, String)],
+    style: &NodeStyle,
+    node: NodeIndex,
+) -> f32 {
+    let inputs = graph.edges_directed(node, petgraph::Incoming).count();
+
+    // stubs count as outgoing edges too.
+    let outputs = graph.edges_directed(node, petgraph::Outgoing).count()
+        + targets.iter().filter(|(source, _)| *source == node).count();
+
+    // the ports are spread out with a gap at both ends, see `layout_ports_on_rect`.
+    (inputs.max(outputs) + 1) as f32 * style.port_spacing
+}
 
-            ui.painter().text(
-                label_pos,
-                Align2::LEFT_CENTER,
-                label,
-                style.label_font.clone(),
-                Color32::WHITE,
-            );
+/// Where the pointer was pressed, in the coordinates of the layer of `ui`.
+fn press_origin(ui: &Ui) -> Option<Pos2> {
+    let origin = ui.input(|i| i.pointer.press_origin())?;
 
-            let text_pos = pos2(
-                block_rectangle.min.x + style.padding.x,
-                header_rectangle.max.y + style.padding.y,
-            );
+    Some(match ui.ctx().layer_transform_from_global(ui.layer_id()) {
+        Some(from_global) => from_global * origin,
+        None => origin,
+    })
+}
 
-            ui.painter().galley(text_pos, body_galley, Color32::WHITE);
+/// Where the pointer is, in the coordinates of the layer of `ui`.
+fn pointer_pos(ui: &Ui) -> Option<Pos2> {
+    let pos = ui.input(|i| i.pointer.hover_pos())?;
 
-            // add our newly created block rectangle.
-            self.block_rects.insert(*node, block_rectangle);
-        }
-    }
+    Some(match ui.ctx().layer_transform_from_global(ui.layer_id()) {
+        Some(from_global) => from_global * pos,
+        None => pos,
+    })
+}
 
-    /// This will get the position at the point of a rect, either the top
-    /// or bottom, where the next port should be placed depending on `count`.
-    fn layout_ports_on_rect(rect: Rect, kind: PortKind, count: usize) -> Vec<Pos2> {
-        let (left, right) = match kind {
-            PortKind::Input => (rect.left_top(), rect.right_top()),
-            PortKind::Output => (rect.left_bottom(), rect.right_bottom()),
-        };
+/// How far `pos` is from the closest point on the line going through `points`.
+fn distance_to_polyline(pos: Pos2, points: &[Pos2]) -> f32 {
+    points
+        .windows(2)
+        .map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let ab = b - a;
+
+            // how far along the segment the closest point is, from 0 to 1.
+            let t = if ab.length_sq() > 0.0 {
+                ((pos - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
 
-        (0..count)
-            .map(|i| {
-                let t = (i as f32 + 1.0) / (count as f32 + 1.0);
-                left.lerp(right, t)
-            })
-            .collect()
-    }
+            pos.distance(a + ab * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
 
-    fn assign_port_positions(&mut self) {
-        for node in self.graph.node_indices() {
-            let graph = &self.graph;
+/// The size of the stub of an external target, with `galley` as its label.
+fn stub_size(galley: &Galley, style: &NodeStyle) -> Vec2 {
+    vec2(
+        galley.size().x + style.button_padding.x * 2.0,
+        style.header_height,
+    )
+}
 
-            // get the indegree of hte current node.
-            let inputs = graph.neighbors_directed(node, petgraph::Incoming).count();
-            // get the outdegree of the current node.
-            let outputs = graph.neighbors_directed(node, petgraph::Outgoing).count();
+/// A tint for a block, with the block index and its weight.
+type NodeTint<'a, N> = Box<dyn Fn(NodeIndex, &N) -> Option<Color32> + 'a>;
 
-            if let Some(&rect) = self.block_rects.get(&node) {
-                for (i, mut pos) in Self::layout_ports_on_rect(rect, PortKind::Input, inputs)
-                    .into_iter()
-                    .enumerate()
-                {
-                    let port = PortSlot::new(node, i, PortKind::Input);
-                    // we offset so the ports don't overlap with the basic block rectangles.
-                    pos.y += -PORT_OFFSET;
-                    self.port_positions.insert(port, pos);
-                }
+/// A border for a block, with the block index and its weight.
+type NodeStroke<'a, N> = Box<dyn Fn(NodeIndex, &N) -> Option<BlockBorder> + 'a>;
 
-                for (i, mut pos) in Self::layout_ports_on_rect(rect, PortKind::Output, outputs)
-                    .into_iter()
-                    .enumerate()
-                {
-                    let port = PortSlot::new(node, i, PortKind::Output);
-                    // we offset so the ports don't overlap with the basic block rectangles.
-                    pos.y += PORT_OFFSET;
-                    self.port_positions.insert(port, pos);
-                }
-            }
-        }
+/// Finds where a token in the body of a block links to.
+type LinkMatcher<'a> = Box<dyn Fn(&str) -> Option<LinkTarget> + 'a>;
+
+/// A context menu for the background, with where it was opened in scene coordinates.
+type BackgroundMenu<'a> = Box<dyn FnMut(&mut Ui, Pos2) + 'a>;
+
+/// Draw `border` on the inside of `rect`.
+fn draw_border(ui: &Ui, rect: Rect, rounding: CornerRadius, border: BlockBorder) {
+    if !border.dashed {
+        ui.painter()
+            .rect_stroke(rect, rounding, border.stroke, StrokeKind::Inside);
+
+        return;
     }
 
-    fn draw_arrow_tip(
-        &self,
-        ui: &mut egui::Ui,
-        tip: egui::Pos2,
-        dir: Option<egui::Vec2>,
-        selected: bool,
-    ) {
-        let size = self.style.edge.width * 4.0;
+    let mut points = Vec::new();
 
-        // get the unit direction of the arrow
-        let dir = dir.unwrap_or(egui::vec2(0.0, 1.0)).normalized();
+    // the path goes through the middle of the stroke, which should stay inside of the rect.
+    egui::epaint::tessellator::path::rounded_rectangle(
+        &mut points,
+        rect.shrink(border.stroke.width / 2.0),
+        rounding.into(),
+    );
 
-        // get the base of the triangle.
-        let base = tip - dir * size;
+    points.extend(points.first().copied());
 
-        // set the vector perpendicular to tip->base, half the size of the base.
-        let perp = egui::vec2(-dir.y, dir.x) * (size * 0.5);
+    let dash = border.stroke.width * 4.0;
 
-        let p1 = base + perp;
-        let p2 = base - perp;
+    ui.painter()
+        .extend(egui::Shape::dashed_line(&points, border.stroke, dash, dash));
+}
 
-        let (color, edge) = {
-            if selected {
-                (self.style.select.color, self.style.select)
-            } else {
-                (self.style.edge.color, self.style.edge)
-            }
-        };
+/// Darken a premultiplied `color` by `factor`, leaving its alpha alone.
+fn darken(color: Color32, factor: f32) -> Color32 {
+    let [r, g, b, a] = color.to_array();
+    let scale = |c: u8| (c as f32 * factor) as u8;
 
-        ui.painter()
-            .add(egui::Shape::convex_polygon(vec![tip, p1, p2], color, edge));
+    Color32::from_rgba_premultiplied(scale(r), scale(g), scale(b), a)
+}
+
+/// The char ranges in `text` where `query` occurs, ignoring ascii case.
+fn find_matches(text: &str, query: &str) -> Vec<std::ops::Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
     }
 
-    fn draw_ports(&mut self, ui: &mut egui::Ui) {
-        let target_ports: Vec<_> = self.selected.map_or(Vec::new(), |target| {
-            self.port_lines
-                .iter()
-                .filter_map(|l| (l.from.node == target).then_some(l.to))
-                .collect()
-        });
+    // lowering ascii keeps every byte offset the same.
+    let haystack = text.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
 
-        for (slot, mut pos) in self.port_positions.clone() {
-            match slot.kind {
-                PortKind::Output => {
-                    // draw the port closer to the block.
-                    pos.y -= PORT_OFFSET - 2.0;
+    let query_chars = needle.chars().count();
 
-                    let radius = self.style.edge.width * 3.0;
+    haystack
+        .match_indices(&needle)
+        .map(|(byte, _)| {
+            let start = haystack[..byte].chars().count();
+            start..start + query_chars
+        })
+        .collect()
+}
 
-                    ui.painter().circle_stroke(pos, radius, self.style.edge);
-                    ui.painter().circle_filled(pos, radius, self.style.fill);
-                }
+/// The rects covering the glyphs of every char range in `matches`, relative to `galley`.
+///
+/// A match that wraps onto the next row gets a rect for each row it's on.
+fn match_rects(galley: &Galley, matches: &[std::ops::Range<usize>]) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    let mut row_start = 0;
 
-                PortKind::Input => {
-                    // draw the port closer to the block.
-                    pos.y += PORT_OFFSET;
+    for row in &galley.rows {
+        let row_rect = row.rect();
 
-                    self.draw_arrow_tip(ui, pos, None, target_ports.contains(&slot));
+        for range in matches {
+            let mut x_range: Option<Rangef> = None;
+
+            for (i, glyph) in row.glyphs.iter().enumerate() {
+                if range.contains(&(row_start + i)) {
+                    let min = row.pos.x + glyph.pos.x;
+                    let glyph_x = Rangef::new(min, min + glyph.advance_width);
+
+                    x_range = Some(x_range.map_or(glyph_x, |x| Rangef::new(x.min, glyph_x.max)));
                 }
             }
-        }
-    }
 
-    /// This will assign a port "edge", from one port to another.
-    ///
-    /// For every node in the in the graph connect each outgoing to port to an incoming port.
-    fn assign_port_lines(&mut self) {
-        let center_x = |n: NodeIndex| {
-            self.block_rects
-                .get(&n)
-                .map(|r| r.center().x)
-                .unwrap_or(0.0)
-        };
+            if let Some(x_range) = x_range {
+                rects.push(Rect::from_x_y_ranges(x_range, row_rect.y_range()));
+            }
+        }
 
-        let sorted_ports = |node: NodeIndex, kind: PortKind| -> Vec<PortSlot> {
-            let mut target_ports: Vec<(PortSlot, f32)> = self
-                .port_positions
-                .iter()
-                .filter_map(|(slot, pos)| {
-                    // get the target slots from the target node, which are `kind`.
-                    (slot.node == node && slot.kind == kind).then_some((*slot, pos.x))
-                })
-                .collect();
+        row_start += row.char_count_including_newline();
+    }
 
-            // yes, it's super weird to sort f32s, but whatever.
-            target_ports.sort_by(|a, b| a.1.total_cmp(&b.1));
+    rects
+}
 
-            target_ports.into_iter().map(|(slot, _)| slot).collect()
-        };
+/// The char range in `galley` of every token in `lines` that `matcher` links somewhere,
+/// along with the line it's on.
+///
+/// The tokens are looked up in the text of the galley, so that they're still found when
+/// it doesn't have the body lines as they are, e.g. with [`BlockLike::body_columns`].
+fn find_links(
+    galley: &Galley,
+    lines: &[String],
+    matcher: &dyn Fn(&str) -> Option<LinkTarget>,
+) -> Vec<(usize, std::ops::Range<usize>, LinkTarget)> {
+    let mut links = Vec::new();
+    let mut line_start = 0;
+
+    for (i, galley_line) in galley.text().split('\n').enumerate() {
+        let tokens = lines.get(i).into_iter().flat_map(|line| {
+            line.split(|c: char| c.is_whitespace() || ",[]()".contains(c))
+                .filter(|token| !token.is_empty())
+        });
 
-        for node in self.graph.node_indices() {
-            let ports = sorted_ports(node, PortKind::Output);
+        // where in the galley line we're looking for the next token from.
+        let mut from = 0;
 
-            if ports.is_empty() {
+        for token in tokens {
+            let Some(start) = galley_line[from..].find(token).map(|start| start + from) else {
                 continue;
-            }
+            };
 
-            let mut sorted_out_edges: Vec<(petgraph::graph::EdgeIndex, NodeIndex)> = self
-                .graph
-                .edges_directed(node, petgraph::Direction::Outgoing)
-                .map(|e| (e.id(), e.target()))
-                .collect();
+            from = start + token.len();
 
-            if sorted_out_edges.is_empty() {
-                continue;
+            if let Some(target) = matcher(token) {
+                let start = line_start + galley_line[..start].chars().count();
+                links.push((i, start..start + token.chars().count(), target));
             }
+        }
 
-            // yes, it's super weird to sort f32s, but whatever.
-            sorted_out_edges
-                .sort_by(|(_, lhs), (_, rhs)| center_x(*lhs).total_cmp(&center_x(*rhs)));
+        line_start += galley_line.chars().count() + 1;
+    }
 
-            // we associate each outgoing edge with an outgoing port.
-            for (n, (edge, target_node)) in sorted_out_edges.iter().enumerate() {
-                let Some(&from_port) = ports.get(n) else {
-                    continue;
-                };
+    links
+}
 
-                let target_ports = sorted_ports(*target_node, PortKind::Input);
+/// Every edge cache in the memory of the context, in the order they were last used in,
+/// for [`CfgView::cache_budget`].
+#[derive(Clone, Default)]
+struct CacheRegistry {
+    used: Vec<egui::Id>,
+}
 
-                if target_ports.is_empty() {
-                    continue;
-                }
+impl CacheRegistry {
+    fn id() -> egui::Id {
+        egui::Id::new("cfg_cache_registry")
+    }
+}
 
-                // collect and sort the incoming edges from the target node.
-                let mut incoming: Vec<(petgraph::graph::EdgeIndex, NodeIndex)> = self
-                    .graph
-                    .edges_directed(*target_node, petgraph::Direction::Incoming)
-                    .map(|e| (e.id(), e.source()))
-                    .collect();
+/// Remove the edge cache `id` from the memory.
+fn evict_cache(data: &mut egui::util::IdTypeMap, id: egui::Id) {
+    data.remove::<Arc<EdgeCache>>(id);
 
-                incoming.sort_by(|(_, lhs), (_, rhs)| center_x(*lhs).total_cmp(&center_x(*rhs)));
+    data.get_temp_mut_or_default::<CacheRegistry>(CacheRegistry::id())
+        .used
+        .retain(|used| *used != id);
+}
 
-                // we want to get the port offset at the same index of the edge.
-                let target_port = incoming.iter().position(|(e, _)| e == edge).unwrap_or(0);
+/// The routed edges, along with the block rects they were routed around.
+///
+/// This is kept behind an [`Arc`], so that getting it out of the memory every frame
+/// doesn't copy every point of every edge.
+#[derive(Clone, Default)]
+struct EdgeCache {
+    fingerprint: Option<CfgFingerprint>,
+    rects: Vec<(NodeIndex, Rect)>,
+    lines: Arc<[(Vec<Pos2>, PortLine)]>,
+}
 
-                let Some(&to_port) = target_ports.get(target_port) else {
-                    continue;
-                };
+pub struct CfgView<'a, N: BlockLike, E: EdgeLike> {
+    graph: StableGraph<N, E>,
+    layout_config: LayoutConfig,
+    route_config: RouteConfig,
+    block_rects: HashMap<NodeIndex, Rect>,
+    port_positions: Arc<HashMap<PortSlot, Pos2>>,
+    port_lines: Arc<Vec<PortLine>>,
+    /// The line that starts or ends at every port, see [`Self::port_line`].
+    lines_by_port: Arc<HashMap<PortSlot, PortLine>>,
+    pub style: &'a NodeStyle,
+    state: &'a mut CfgViewState,
+    highlighted_lines: Option<&'a HashSet<(NodeIndex, usize)>>,
+    node_tint: Option<NodeTint<'a, N>>,
+    node_stroke: Option<NodeStroke<'a, N>>,
+    background_context_menu: Option<BackgroundMenu<'a>>,
+    link_matcher: Option<LinkMatcher<'a>>,
+    external_targets: &'a [(NodeIndex, String)],
+    search: &'a str,
+    measured_sizes: Option<&'a HashMap<NodeIndex, Vec2>>,
+    heat: Option<&'a HashMap<NodeIndex, f32>>,
+    heat_log_scale: bool,
+    /// Whether the pointer is panning this frame, so nothing else senses it.
+    panning: bool,
+    /// Whether a touch screen is being used this frame.
+    touch: bool,
+    focus_mode: Option<FocusMode>,
+    external_hover: Option<NodeIndex>,
+    hover_config: HoverConfig,
+    deselect_on_background: bool,
+    escape_clears: bool,
+    peek_zoom: Option<egui::Key>,
+    draggable_nodes: bool,
+    lazy_text: bool,
+    animation_fps: f32,
+    /// The stats of this frame, if they're collected.
+    stats: Option<CfgStats>,
+    cache_budget: Option<usize>,
+    routing_budget: Option<Duration>,
+    zoom_range: RangeInclusive<f32>,
+    scene_margin: f32,
+    placeholder: &'a str,
+    /// What the ids of everything in the view are made from.
+    id: egui::Id,
+    snap_to_grid: bool,
+    /// The blocks that are in focus this frame, if anything is.
+    focus: Option<HashSet<NodeIndex>>,
+    /// The block that is hit by the pointer outside of its rect, because it's smaller than
+    /// [`MIN_HIT_SIZE`] on the screen.
+    hit_target: Option<NodeIndex>,
+    /// The fingerprint of the graph, and of [`CfgViewState::mark_dirty`].
+    fingerprint: CfgFingerprint,
+    stubs: Vec<Stub>,
+    /// The routed edges that were drawn this frame.
+    edge_lines: Arc<[(Vec<Pos2>, PortLine)]>,
+    detail: Detail,
+    response: CfgResponse,
+}
 
-                self.port_lines.push(PortLine {
-                    from: from_port,
-                    to: to_port,
-                });
-            }
+impl<'a, N: BlockLike, E: EdgeLike> CfgView<'a, N, E> {
+    pub fn new(
+        graph: StableGraph<N, E>,
+        config: LayoutConfig,
+        state: &'a mut CfgViewState,
+        style: &'a NodeStyle,
+    ) -> Self {
+        let fingerprint = CfgFingerprint::of(&graph).with_generation(state.generation);
+        let id = state.id;
+
+        Self {
+            graph,
+            layout_config: config,
+            route_config: RouteConfig::default(),
+            style,
+            block_rects: HashMap::new(),
+            port_lines: Arc::default(),
+            port_positions: Arc::default(),
+            lines_by_port: Arc::default(),
+            state,
+            highlighted_lines: None,
+            node_tint: None,
+            node_stroke: None,
+            background_context_menu: None,
+            link_matcher: None,
+            external_targets: &[],
+            search: "",
+            measured_sizes: None,
+            heat: None,
+            heat_log_scale: false,
+            panning: false,
+            touch: false,
+            focus_mode: None,
+            external_hover: None,
+            hover_config: HoverConfig::default(),
+            deselect_on_background: true,
+            escape_clears: true,
+            peek_zoom: None,
+            draggable_nodes: false,
+            lazy_text: false,
+            animation_fps: 60.0,
+            stats: None,
+            cache_budget: None,
+            routing_budget: None,
+            zoom_range: 0.1..=2.0,
+            scene_margin: 200.0,
+            placeholder: "",
+            id,
+            snap_to_grid: false,
+            focus: None,
+            hit_target: None,
+            fingerprint,
+            stubs: Vec::new(),
+            edge_lines: Arc::new([]),
+            detail: Detail::Full,
+            response: CfgResponse::default(),
         }
     }
 
-    fn build_field(&self, scene: egui::Rect) -> CostField {
-        let grid = Grid::from_scene(scene, 3.0);
-
-        let mut field = CostField::new(grid);
-
-        // we just want to hard block pathfinding from going through block rects.
-        for rect in self.block_rects.values() {
-            field.add_block_rect(*rect, 5.0);
-        }
+    /// Draw a highlight strip behind each of these `(node, line)` body lines.
+    pub fn highlighted_lines(mut self, lines: &'a HashSet<(NodeIndex, usize)>) -> Self {
+        self.highlighted_lines = Some(lines);
+        self
+    }
 
-        field
+    /// Tint blocks with the color returned by `tint`, which gets blended over the fill of
+    /// the body, and a slightly darker version of it over the header.
+    ///
+    /// The outline is never tinted, so that hovering and selecting stay readable.
+    pub fn node_tint(mut self, tint: impl Fn(NodeIndex, &N) -> Option<Color32> + 'a) -> Self {
+        self.node_tint = Some(Box::new(tint));
+        self
     }
 
-    fn draw_edges(&mut self, ui: &mut egui::Ui, scene_rect: egui::Rect) {
-        let mut field = self.build_field(scene_rect);
+    /// Override the outline of blocks with the border returned by `border`, instead of the
+    /// stroke from the style.
+    ///
+    /// The outline is drawn on the inside of the block, so it stays clear of the glow.
+    pub fn node_stroke(
+        mut self,
+        border: impl Fn(NodeIndex, &N) -> Option<BlockBorder> + 'a,
+    ) -> Self {
+        self.node_stroke = Some(Box::new(border));
+        self
+    }
 
-        let id = ui.make_persistent_id("cfg_edge_cache_v1");
-        let mut routed_polylines: Vec<(Vec<egui::Pos2>, PortLine)> = Vec::new();
+    /// Draw a small stub below each of these `(node, label)` pairs, with an edge going to it
+    /// from the node, e.g. for calls or jumps to something that isn't in the graph.
+    ///
+    /// Clicking a stub sets [`CfgResponse::external_target_clicked`].
+    pub fn external_targets(mut self, targets: &'a [(NodeIndex, String)]) -> Self {
+        self.external_targets = targets;
+        self
+    }
 
-        if let Some(lines) = ui
-            .ctx()
-            .data_mut(|d| d.get_persisted::<Vec<(Vec<egui::Pos2>, PortLine)>>(id))
-        {
-            for (poly, pl) in lines {
-                let edge_kind = self
-                    .graph
-                    .find_edge(pl.from.node, pl.to.node)
-                    .and_then(|e| self.graph.edge_weight(e))
-                    .map(|e| e.kind());
-
-                let should_dash = match edge_kind {
-                    Some(EdgeKind::FallThrough) => true,
-                    _ => false,
-                };
+    /// Highlight every occurrence of `query` in the block bodies, ignoring ascii case.
+    ///
+    /// How many matches every block has ends up in [`CfgResponse::search_matches`].
+    pub fn search(mut self, query: &'a str) -> Self {
+        self.search = query;
+        self
+    }
 
-                let is_selected = match self.selected.clone() {
-                    Some(node) if pl.from.node == node => true,
-                    _ => false,
-                };
+    /// Color blocks along [`NodeStyle::heat_gradient`] by these values, e.g. how many times
+    /// every block was executed.
+    ///
+    /// The values get normalized between the lowest and the highest one, and blocks that
+    /// aren't in `heat` keep their normal fill.
+    pub fn heat(mut self, heat: &'a HashMap<NodeIndex, f32>) -> Self {
+        self.heat = Some(heat);
+        self
+    }
 
-                if should_dash && is_selected {
-                    let stroke = Stroke::new(2.0, self.style.select_bg);
+    /// Draw `node` as if it was hovered, e.g. because its address is hovered somewhere else
+    /// in the app. This doesn't change the selection.
+    pub fn external_hover(mut self, node: Option<NodeIndex>) -> Self {
+        self.external_hover = node;
+        self
+    }
 
-                    ui.painter().add(egui::Shape::dotted_line(
-                        &poly,
-                        self.style.select.color.gamma_multiply(0.5),
-                        12.0,
-                        2.0,
-                    ));
+    /// When hovering a block turns its glow on and off. Setting both to zero makes it
+    /// follow the pointer exactly.
+    pub fn hover_config(mut self, config: HoverConfig) -> Self {
+        self.hover_config = config;
+        self
+    }
 
-                    continue;
+    /// Whether `node` glows for being hovered, after the [`HoverConfig::delay`] and until
+    /// the [`HoverConfig::grace`] is over.
+    fn hover_glow(&mut self, ui: &Ui, node: NodeIndex, hovered: bool) -> bool {
+        let now = ui.input(|i| i.time);
+        let config = self.hover_config;
+
+        let track = &mut self.state.hover_track;
+
+        if hovered {
+            match track {
+                // still on the same block, or back on it in time.
+                Some(t) if t.node == node && now - t.seen <= config.grace as f64 => t.seen = now,
+                _ => {
+                    *track = Some(HoverTrack {
+                        node,
+                        since: now,
+                        seen: now,
+                    })
                 }
+            }
+        }
 
-                if is_selected {
-                    let stroke = Stroke::new(
-                        self.style.select.width,
-                        self.style.select.color.gamma_multiply(0.5),
-                    );
-
-                    ui.painter()
-                        .add(egui::Shape::line(poly.clone(), self.style.select));
+        let Some(track) = *track else {
+            return false;
+        };
 
-                    continue;
-                }
+        if track.node != node {
+            return false;
+        }
 
-                ui.painter()
-                    .add(egui::Shape::line(poly.clone(), self.style.edge));
-            }
+        let shown = now - track.since;
+        let gone = now - track.seen;
 
-            return;
+        // nothing else changes when the delay or the grace runs out, so it needs a repaint.
+        if shown < config.delay as f64 {
+            self.animate(Animation::Hover, (config.delay as f64 - shown) as f32);
+        } else if !hovered && gone <= config.grace as f64 {
+            self.animate(Animation::Hover, (config.grace as f64 - gone) as f32);
         }
 
-        for pl in &self.port_lines {
-            let Some(&from) = self.port_positions.get(&pl.from) else {
-                continue;
-            };
+        shown >= config.delay as f64 && gone <= config.grace as f64
+    }
+
+    /// Dim every block and edge that isn't near the selected block, or the hovered one, so
+    /// that its neighborhood stands out.
+    ///
+    /// Nothing gets dimmed while no block is selected or hovered.
+    pub fn focus_mode(mut self, focus: FocusMode) -> Self {
+        self.focus_mode = Some(focus);
+        self
+    }
+
+    /// Whether clicking on the background, away from any block, clears
+    /// [`CfgViewState::selected`]. This is on by default.
+    ///
+    /// Turn it off when the app manages the selection itself.
+    pub fn deselect_on_background(mut self, deselect: bool) -> Self {
+        self.deselect_on_background = deselect;
+        self
+    }
+
+    /// How far the view can be zoomed out and in, where 1.0 is 100%. This is `0.1..=2.0`
+    /// by default.
+    pub fn zoom_range(mut self, range: RangeInclusive<f32>) -> Self {
+        self.zoom_range = range;
+        self
+    }
+
+    /// Tell apart views that are shown in the same ui, which is only needed when they share
+    /// a [`CfgViewState`], since every state already gets its own id.
+    pub fn id_salt(mut self, salt: impl std::hash::Hash) -> Self {
+        self.id = egui::Id::new(salt);
+        self
+    }
+
+    /// Draw `text` in the middle of the view while the graph is empty, e.g. "No function
+    /// loaded". Nothing is drawn for an empty graph otherwise.
+    pub fn placeholder(mut self, text: &'a str) -> Self {
+        self.placeholder = text;
+        self
+    }
+
+    /// The least space the scene has around the graph, which sets how far it can be panned.
+    ///
+    /// The scene gets more space than this around big graphs, and never less than the margin
+    /// edges are routed in, see [`RouteConfig::margin_cells`]. This is 200 by default.
+    pub fn scene_margin(mut self, margin: f32) -> Self {
+        self.scene_margin = margin;
+        self
+    }
+
+    /// Clear the first of the selected blocks, the selected edge, the search highlights and
+    /// the Tab candidate that there is.
+    fn clear_on_escape(&mut self) {
+        let state = &mut *self.state;
+
+        if state.selected.is_some() || !state.multi_selection.is_empty() {
+            state.selected = None;
+            state.multi_selection.clear();
+            self.response.selection_cleared = true;
+        } else if state.selected_edge.is_some() {
+            state.selected_edge = None;
+            self.response.edge_selection_cleared = true;
+        } else if !self.search.is_empty() && state.dismissed_search.is_none() {
+            state.dismissed_search = Some(self.search.to_owned());
+            self.response.search_cleared = true;
+        } else if state.candidate.is_some() {
+            state.candidate = None;
+            self.response.candidate_cleared = true;
+        }
+    }
+
+    /// Zoom with `+` and `-`, go back to 100% with `0`, and fit the whole graph with `f`.
+    fn handle_zoom_keys(&mut self, ui: &mut Ui) {
+        use egui::{Key, Modifiers};
+
+        ui.input_mut(|i| {
+            if i.consume_key(Modifiers::NONE, Key::Plus)
+                || i.consume_key(Modifiers::NONE, Key::Equals)
+            {
+                self.state.zoom_in();
+            }
+
+            if i.consume_key(Modifiers::NONE, Key::Minus) {
+                self.state.zoom_out();
+            }
+
+            if i.consume_key(Modifiers::NONE, Key::Num0) {
+                self.state.reset_zoom();
+            }
+
+            if i.consume_key(Modifiers::NONE, Key::F) {
+                self.state.fit();
+            }
+        });
+    }
+
+    /// Turn the tokens in the body that `matcher` finds a target for into links, which get
+    /// underlined and are reported in [`CfgResponse::link_clicked`] when clicked.
+    ///
+    /// The tokens are split on whitespace, commas and brackets, e.g. `0x1440` in
+    /// `jmp 0x1440`.
+    pub fn link_matcher(mut self, matcher: impl Fn(&str) -> Option<LinkTarget> + 'a) -> Self {
+        self.link_matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Show a context menu when right clicking on the background, where `menu` gets where
+    /// it was clicked, in the same coordinates as the blocks.
+    pub fn background_context_menu(mut self, menu: impl FnMut(&mut Ui, Pos2) + 'a) -> Self {
+        self.background_context_menu = Some(Box::new(menu));
+        self
+    }
+
+    /// Let blocks be dragged around by their header.
+    ///
+    /// Where they were dragged to is kept in [`CfgViewState::pinned_positions`], which they
+    /// stay at when the graph is laid out again, and the edges get routed again once the
+    /// drag ends.
+    pub fn draggable_nodes(mut self, draggable: bool) -> Self {
+        self.draggable_nodes = draggable;
+        self
+    }
+
+    /// Round where dragged blocks end up to multiples of [`LayoutConfig::spacing`].
+    pub fn snap_to_grid(mut self, snap: bool) -> Self {
+        self.snap_to_grid = snap;
+        self
+    }
+
+    /// Whether pressing Escape over the view clears the selection, then the selected edge,
+    /// then the search highlights and then the Tab candidate, one for every press. This is
+    /// on by default.
+    pub fn escape_clears(mut self, clears: bool) -> Self {
+        self.escape_clears = clears;
+        self
+    }
+
+    /// Zoom to the hovered block for as long as `key` is held, and back to where the view
+    /// was once it's released.
+    pub fn peek_zoom(mut self, key: egui::Key) -> Self {
+        self.peek_zoom = Some(key);
+        self
+    }
+
+    /// Start, end and animate zooming to the hovered block with the [`Self::peek_zoom`]
+    /// key, where `view` is the rect the scene is shown in.
+    fn handle_peek(
+        &mut self,
+        ui: &Ui,
+        view: Rect,
+        layout: &CfgLayout,
+        sizes: &HashMap<NodeIndex, Vec2>,
+        scene_rect: &mut Rect,
+    ) {
+        let Some(key) = self.peek_zoom else {
+            return;
+        };
+
+        let held = ui.input(|i| i.key_down(key))
+            && ui.rect_contains_pointer(view)
+            && !ui.ctx().wants_keyboard_input();
+
+        let now = ui.input(|i| i.time);
+        let fingerprint = self.fingerprint;
+
+        match &mut self.state.peek {
+            None if held => {
+                let Some(node) = self.state.hovered else {
+                    return;
+                };
+
+                let Some((_, (x, y))) = layout.coords.iter().find(|(n, _)| *n == node) else {
+                    return;
+                };
+
+                let center = self
+                    .state
+                    .pinned_pos(node)
+                    .unwrap_or(pos2(*x as f32, *y as f32));
+                let size = sizes[&node] + self.style.padding * 2.0;
+
+                // 100%, unless the block doesn't fit in the view at that zoom.
+                let zoom = (view.width() / size.x)
+                    .min(view.height() / size.y)
+                    .min(1.0)
+                    .clamp(*self.zoom_range.start(), *self.zoom_range.end());
+
+                self.state.peek = Some(Peek {
+                    restore: *scene_rect,
+                    from: *scene_rect,
+                    to: Rect::from_center_size(center, view.size() / zoom),
+                    start: now,
+                    returning: false,
+                    fingerprint,
+                });
+            }
+
+            // the block could be gone, so it goes straight back.
+            Some(peek) if peek.fingerprint != fingerprint => {
+                *scene_rect = peek.restore;
+                self.state.peek = None;
+                return;
+            }
+
+            Some(peek) if !held && !peek.returning => {
+                *peek = Peek {
+                    from: *scene_rect,
+                    to: peek.restore,
+                    start: now,
+                    returning: true,
+                    ..*peek
+                };
+            }
+
+            _ => {}
+        }
+
+        let Some(peek) = self.state.peek else {
+            return;
+        };
+
+        let t = ((now - peek.start) / PEEK_DURATION).min(1.0) as f32;
+
+        // once it got there, the view can be panned and zoomed as usual.
+        if t < 1.0 || peek.returning {
+            let t = egui::emath::easing::cubic_out(t);
+
+            *scene_rect = Rect::from_min_max(
+                peek.from.min.lerp(peek.to.min, t),
+                peek.from.max.lerp(peek.to.max, t),
+            );
+        }
+
+        if t < 1.0 {
+            self.animate(Animation::Peek, self.frame_time());
+        } else if peek.returning {
+            self.state.peek = None;
+        }
+    }
+
+    /// The blocks within [`FocusMode::hops`] of the selected and the hovered block.
+    fn focus_set(&self) -> Option<HashSet<NodeIndex>> {
+        let focus = self.focus_mode?;
+
+        let mut frontier: Vec<NodeIndex> =
+            [self.state.selected, self.state.hovered, self.external_hover]
+                .into_iter()
+                .flatten()
+                .filter(|n| self.graph.contains_node(*n))
+                .collect();
+
+        if frontier.is_empty() {
+            return None;
+        }
+
+        let mut visited: HashSet<NodeIndex> = frontier.iter().copied().collect();
+
+        for _ in 0..focus.hops {
+            frontier = frontier
+                .into_iter()
+                .flat_map(|n| self.graph.neighbors_undirected(n))
+                .filter(|n| visited.insert(*n))
+                .collect();
+
+            if frontier.is_empty() {
+                break;
+            }
+        }
+
+        Some(visited)
+    }
+
+    /// What the opacity of everything belonging to `node` gets multiplied by.
+    fn focus_opacity(&self, node: NodeIndex) -> f32 {
+        match (&self.focus, self.focus_mode) {
+            (Some(focus), Some(mode)) if !focus.contains(&node) => mode.dim,
+            _ => 1.0,
+        }
+    }
+
+    /// Normalize the [`Self::heat`] values on a log scale, so that a few very hot blocks
+    /// don't make every other block look cold.
+    pub fn heat_log_scale(mut self, log: bool) -> Self {
+        self.heat_log_scale = log;
+        self
+    }
+
+    /// The color of the heat of `node`, if it has one.
+    fn heat_color(&self, node: NodeIndex) -> Option<Color32> {
+        let value = *self.heat?.get(&node)?;
+        let range = self.response.heat_range?;
+
+        let span = range.max - range.min;
+
+        let t = if span <= 0.0 {
+            1.0
+        } else if self.heat_log_scale {
+            (value - range.min).ln_1p() / span.ln_1p()
+        } else {
+            (value - range.min) / span
+        };
+
+        let (cold, hot) = self.style.heat_gradient;
+
+        Some(cold.lerp_to_gamma(hot, t.clamp(0.0, 1.0)))
+    }
+
+    /// Use these sizes for the blocks instead of measuring them every frame, e.g. from
+    /// [`crate::measure_block`].
+    ///
+    /// The body text still gets wrapped at the content width of the style, so the sizes
+    /// should be measured with the same style.
+    pub fn measured_sizes(mut self, sizes: &'a HashMap<NodeIndex, Vec2>) -> Self {
+        self.measured_sizes = Some(sizes);
+        self
+    }
+
+    /// Only lay out the body text of the blocks that are on the screen.
+    ///
+    /// The other blocks get sized by [`crate::estimate_block_size`] until they're first
+    /// scrolled into view, which makes the first frame of a large graph a lot cheaper. The
+    /// trade-off is that a block whose estimate was off changes size once it shows up, and
+    /// the layout around it moves a little to make room.
+    pub fn lazy_text(mut self, lazy: bool) -> Self {
+        self.lazy_text = lazy;
+        self
+    }
+
+    /// Repaint at most `fps` times a second for the animations, like the glow of a flashing
+    /// block, instead of on every frame the screen can show.
+    ///
+    /// Delays, like the [`HoverConfig::delay`], still repaint once right when they run out.
+    pub fn animation_fps(mut self, fps: f32) -> Self {
+        self.animation_fps = fps;
+        self
+    }
+
+    /// How long an animation waits for its next repaint, for [`Self::animation_fps`].
+    fn frame_time(&self) -> f32 {
+        1.0 / self.animation_fps.max(1.0)
+    }
+
+    /// Keep `animation` going, with a repaint in `after` seconds.
+    fn animate(&mut self, animation: Animation, after: f32) {
+        self.state.animations.run(animation, Some(after));
+    }
+
+    /// Keep the routed edges of at most `views` views in the memory of the context, and drop
+    /// the ones of the views that were shown the longest ago once there are more.
+    ///
+    /// Views whose edges were dropped just route them again when they're shown. Everything
+    /// else that is cached lives in the [`CfgViewState`], see [`CfgViewState::clear_caches`].
+    pub fn cache_budget(mut self, views: usize) -> Self {
+        self.cache_budget = Some(views);
+        self
+    }
+
+    /// Only route edges for about `budget` every frame, and the rest of them on the frames
+    /// after, starting with the ones on the screen and then the ones closest to it.
+    ///
+    /// Until an edge gets routed it's drawn where it was routed before, or straight between
+    /// its ports. Without a budget every edge gets routed in the frame that needs it.
+    pub fn routing_budget(mut self, budget: Duration) -> Self {
+        self.routing_budget = Some(budget);
+        self
+    }
+
+    /// Time every phase of the frame, and report it in [`CfgResponse::stats`], e.g. to show
+    /// it in a [`CfgStatsWindow`]. Nothing gets timed otherwise.
+    pub fn collect_stats(mut self, collect: bool) -> Self {
+        self.stats = collect.then(CfgStats::default);
+        self
+    }
+
+    /// Set the configuration used when routing edges between blocks.
+    pub fn route_config(mut self, config: RouteConfig) -> Self {
+        self.route_config = config;
+        self
+    }
+
+    /// The layout config, with the cells the edges are routed in, so that blocks are spaced
+    /// far enough apart for them.
+    fn layout_config(&self) -> LayoutConfig {
+        LayoutConfig {
+            route_cell_size: self.route_config.cell_size.max_elem() as f64,
+            ..self.layout_config.clone()
+        }
+    }
+
+    /// Get a rectangle that encompasses every block node and port placed, expanded by the
+    /// routing margin so that paths have room to go around the outermost blocks.
+    fn get_routing_rect(&self) -> Rect {
+        match &self.state.ports {
+            Some(ports) => self.routing_rect(ports.world_rect, ports.offset),
+            None => Rect::NOTHING,
+        }
+    }
+
+    /// The routing rect around the blocks in `world`, with ports `offset` out of them.
+    fn routing_rect(&self, world: Rect, offset: f32) -> Rect {
+        // ports sit slightly above and below the blocks, so they have to be included too.
+        let bounds = world.expand2(vec2(0.0, offset));
+
+        // the margin is in the cells that are actually routed in, which get bigger than the
+        // smallest ones for large graphs.
+        let margin = self.routing_margin_cells();
+        let cell = self.route_config.cell_size_around(bounds, margin);
+
+        bounds.expand2(cell * margin)
+    }
+
+    /// How many cells edges have to be routed in, on each side of the blocks and ports.
+    ///
+    /// This is two more than [`RouteConfig::margin_cells`], so that the outermost ports never
+    /// fall into the border cells of the grid, even when they're right on the edge between
+    /// two cells.
+    fn routing_margin_cells(&self) -> f32 {
+        self.route_config.margin_cells as f32 + 2.0
+    }
+
+    /// How much space the scene has around a layout of `size`, on every side together.
+    fn scene_margin_for(&self, size: Vec2) -> Vec2 {
+        let routing = self.route_config.cell_size * self.routing_margin_cells();
+
+        (size * SCENE_MARGIN_FRACTION)
+            .max(routing * 2.0)
+            .max(Vec2::splat(self.scene_margin))
+    }
+
+    /// Handle clicks on the block, and draw the glow around it.
+    ///
+    /// This also returns how far along the glow animation is, from 0 to 1.
+    fn handle_block_interaction(
+        &mut self,
+        ui: &mut Ui,
+        rect: &Rect,
+        node: &NodeIndex,
+    ) -> (egui::Response, f32) {
+        let id = ui.make_persistent_id(("node", node.index()));
+
+        // only the painted rect is hit, unless it's too small to hit on the screen.
+        let hit_rect = match self.hit_target {
+            Some(target) if target == *node => self.hit_rect(*rect),
+            _ => *rect,
+        };
+
+        // dragging is used for selecting text in the body.
+        let response = ui.interact(hit_rect, id, self.sense(egui::Sense::click_and_drag()));
+
+        // pressing anywhere else drops the selected text, but not the selected block.
+        if ui.input(|i| i.pointer.any_pressed())
+            && !response.hovered()
+            && self.state.text_selection.is_some_and(|s| s.node == *node)
+        {
+            self.state.text_selection = None;
+        }
+
+        let (alt, shift) = ui.input(|i| (i.modifiers.alt, i.modifiers.shift));
+
+        // alt clicking picks out everything that can reach the block instead of selecting it,
+        // or everything it can reach with shift.
+        if response.clicked() && alt {
+            self.state.slice = Some(Slice {
+                node: *node,
+                direction: if shift {
+                    Direction::Outgoing
+                } else {
+                    Direction::Incoming
+                },
+                fingerprint: None,
+                nodes: HashSet::new(),
+            });
+        } else if response.clicked() {
+            self.state.selected = Some(*node);
+            self.state.multi_selection.clear();
+        }
+
+        if response.hovered() {
+            self.state.hovered = Some(*node);
+            self.response.hovered = Some(*node);
+        }
+
+        let glow = &self.style.glow;
+
+        let hover_glow = self.hover_glow(ui, *node, response.hovered());
+
+        let glow_on =
+            hover_glow || self.external_hover == Some(*node) || self.state.is_selected(*node);
+
+        // goes from 0 to 1 over time, once we've hovered or selected.
+        // a block that replaced another one at the same index starts without a glow.
+        let t = ui
+            .ctx()
+            .animate_bool_with_time(id.with(self.fingerprint), glow_on, glow.duration);
+
+        // egui already repaints for as long as the glow is animating.
+        if t > 0.0 && t < 1.0 {
+            self.state.animations.run(Animation::Glow, None);
+        }
+
+        // a glow without a width wouldn't be visible anyway.
+        if t > 0.0 && glow.max_width > 0.0 {
+            // we will increase the outline over time.
+            let outline_width = glow.max_width * glow.easing.apply(t);
+
+            ui.painter().rect(
+                *rect,
+                self.style.block_rounding_in(*rect),
+                Color32::TRANSPARENT,
+                Stroke::new(
+                    outline_width,
+                    self.style.select.color.gamma_multiply(glow.opacity),
+                ),
+                StrokeKind::Outside,
+            );
+        }
+
+        self.draw_flash(ui, rect, *node);
+
+        (response, t)
+    }
+
+    /// Pulse the glow around the block, if it was [`CfgViewState::flash`]ed.
+    fn draw_flash(&mut self, ui: &Ui, rect: &Rect, node: NodeIndex) {
+        let Some(flash) = self.state.flashes.get_mut(&node) else {
+            return;
+        };
+
+        let now = ui.input(|i| i.time);
+
+        // the flash starts once the block actually gets drawn.
+        let start = *flash.start.get_or_insert(now);
+
+        let style = flash.style;
+
+        let t = ((now - start) / style.period as f64) as f32;
+
+        if style.period <= 0.0 || t >= style.cycles as f32 {
+            self.state.flashes.remove(&node);
+            return;
+        }
+
+        // goes from 0 to 1 and back in every cycle.
+        let pulse = (t * std::f32::consts::PI).sin().powi(2);
+
+        ui.painter().rect(
+            *rect,
+            self.style.block_rounding_in(*rect),
+            Color32::TRANSPARENT,
+            Stroke::new(
+                self.style.glow.max_width.max(2.0) * pulse,
+                style.color.gamma_multiply(pulse),
+            ),
+            StrokeKind::Outside,
+        );
+
+        self.animate(Animation::Flash, self.frame_time());
+    }
+
+    /// Select every block that touches the rubber band dragged out over `background`, while
+    /// holding shift, or ctrl to add to the blocks that are already selected.
+    fn handle_box_selection(&mut self, ui: &Ui, background: &egui::Response) {
+        let (shift, add) = ui.input(|i| (i.modifiers.shift, i.modifiers.command));
+
+        if background.drag_started_by(egui::PointerButton::Primary)
+            && (shift || add)
+            && let Some(origin) = press_origin(ui)
+        {
+            let base = if add {
+                self.state.multi_selection.clone()
+            } else {
+                HashSet::new()
+            };
+
+            self.state.box_selection = Some(BoxSelection { origin, base });
+        }
+
+        let Some(selection) = &self.state.box_selection else {
+            return;
+        };
+
+        if let Some(pos) = background.interact_pointer_pos() {
+            let band = Rect::from_two_pos(selection.origin, pos);
+
+            let hits = self
+                .state
+                .spatial
+                .iter()
+                .flat_map(|index| index.nodes_in(band));
+
+            self.state.multi_selection = selection.base.iter().copied().chain(hits).collect();
+
+            ui.painter().rect(
+                band,
+                CornerRadius::ZERO,
+                self.style.select_bg.gamma_multiply(0.3),
+                self.style.select,
+                StrokeKind::Inside,
+            );
+        }
+
+        if !background.dragged() {
+            self.state.box_selection = None;
+        }
+    }
+
+    /// Cycle through the successors of the selected block with Tab, or its predecessors
+    /// with Shift+Tab, and select and center the one cycled to with Enter.
+    fn handle_candidate_keys(&mut self, ui: &mut Ui) {
+        // the candidate has to stay next to the selected block, which could have changed.
+        let Some(selected) = self.state.selected else {
+            self.state.candidate = None;
+            return;
+        };
+
+        if let Some(candidate) = self.state.candidate
+            && !self
+                .graph
+                .neighbors_undirected(selected)
+                .any(|n| n == candidate)
+        {
+            self.state.candidate = None;
+        }
+
+        // extra modifiers don't stop keys from matching, so Shift+Tab has to come first.
+        let direction = ui.input_mut(|i| {
+            if i.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab) {
+                Some(Direction::Incoming)
+            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::Tab) {
+                Some(Direction::Outgoing)
+            } else {
+                None
+            }
+        });
+
+        if let Some(direction) = direction {
+            let mut neighbors: Vec<NodeIndex> = self
+                .graph
+                .neighbors_directed(selected, direction)
+                .filter(|n| *n != selected && self.block_rects.contains_key(n))
+                .collect();
+
+            neighbors.sort_by_key(|n| n.index());
+            neighbors.dedup();
+
+            // go from left to right, the same way they're laid out.
+            neighbors.sort_by(|a, b| {
+                self.block_rects[a]
+                    .center()
+                    .x
+                    .total_cmp(&self.block_rects[b].center().x)
+            });
+
+            let next = match self
+                .state
+                .candidate
+                .and_then(|c| neighbors.iter().position(|n| *n == c))
+            {
+                Some(i) => neighbors.get((i + 1) % neighbors.len()),
+                None => neighbors.first(),
+            };
+
+            self.state.candidate = next.copied();
+        }
+
+        let Some(candidate) = self.state.candidate else {
+            return;
+        };
+
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) {
+            self.state.selected = Some(candidate);
+            self.state.multi_selection.clear();
+            self.state.candidate = None;
+            self.state.center_on(candidate);
+        }
+    }
+
+    /// Center on the end of the edge under a double click that is farther away from it,
+    /// and flash the glow of the block there.
+    fn handle_edge_double_click(&mut self, background: &egui::Response) {
+        if !background.double_clicked() {
+            return;
+        }
+
+        let Some(pos) = background.interact_pointer_pos() else {
+            return;
+        };
+
+        let Some(line) = self.edge_at(pos) else {
+            return;
+        };
+
+        let distance = |node: NodeIndex| {
+            self.node_rect(node)
+                .map_or(f32::INFINITY, |rect| rect.distance_to_pos(pos))
+        };
+
+        let (from, to) = (line.from.node, line.to.node);
+
+        let target = if distance(from) > distance(to) {
+            from
+        } else {
+            to
+        };
+
+        self.state.center_on(target);
+
+        self.state.flash(
+            target,
+            FlashStyle {
+                color: self.style.select.color,
+                cycles: 1,
+                period: 0.6,
+            },
+        );
+    }
+
+    /// `rect` grown to at least [`MIN_HIT_SIZE`] on the screen.
+    fn hit_rect(&self, rect: Rect) -> Rect {
+        let min = Vec2::splat(MIN_HIT_SIZE / self.state.zoom);
+
+        rect.expand2(((min - rect.size()) / 2.0).max(Vec2::ZERO))
+    }
+
+    /// The block the pointer hits only because it's grown by [`Self::hit_rect`], where the
+    /// one whose center is the closest wins when several of them are grown over the pointer.
+    ///
+    /// This goes by where the blocks were drawn last frame, since those are what was seen.
+    fn small_block_at(&self, ui: &Ui) -> Option<NodeIndex> {
+        let pos = pointer_pos(ui)?;
+        let spatial = self.state.spatial.as_ref()?;
+
+        let area = Rect::from_center_size(pos, Vec2::splat(MIN_HIT_SIZE / self.state.zoom));
+        let blocks: Vec<(NodeIndex, Rect)> = spatial.blocks_in(area).collect();
+
+        // a block right under the pointer is hit as it is.
+        if blocks.iter().any(|(_, rect)| rect.contains(pos)) {
+            return None;
+        }
+
+        blocks
+            .into_iter()
+            .filter(|(_, rect)| self.hit_rect(*rect).contains(pos))
+            .min_by(|(_, a), (_, b)| {
+                a.center()
+                    .distance_sq(pos)
+                    .total_cmp(&b.center().distance_sq(pos))
+            })
+            .map(|(node, _)| node)
+    }
+
+    /// The edge closest to `pos`, if it's close enough to hit.
+    fn edge_at(&self, pos: Pos2) -> Option<&PortLine> {
+        self.state.spatial.as_ref()?.edge_at(pos)
+    }
+
+    /// How far away from an edge still hits it.
+    fn edge_tolerance(&self) -> f32 {
+        // the edges are thin, so they get a few points on the screen to hit.
+        (self.style.edge.width + 6.0) / self.state.zoom
+    }
+
+    /// How big the area around a port is that hits it.
+    fn port_hit_size(&self) -> f32 {
+        // the ports are tiny, so they get a bigger area to hit, which grows as the view gets
+        // zoomed out so that they stay about as easy to hit.
+        let radius = self.style.edge.width * 3.0;
+
+        radius * 6.0 / self.state.zoom.min(1.0)
+    }
+
+    /// Build the [`SpatialIndex`] again if any block moved or the edges were routed again,
+    /// and keep it up to date with the zoom.
+    fn update_spatial_index(&mut self) {
+        let mut rects: Vec<(NodeIndex, Rect)> =
+            self.block_rects.iter().map(|(n, r)| (*n, *r)).collect();
+
+        rects.sort_by_key(|(n, _)| *n);
+
+        let (edge_tolerance, port_size) = (self.edge_tolerance(), self.port_hit_size());
+
+        let mut index = match self.state.spatial.take() {
+            Some(index) if index.rects == rects && Arc::ptr_eq(&index.edges, &self.edge_lines) => {
+                index
+            }
+            _ => {
+                let ports = self
+                    .port_positions
+                    .iter()
+                    .map(|(slot, pos)| (*slot, self.port_pos(*slot, *pos)))
+                    .collect();
+
+                SpatialIndex::new(rects, ports, self.edge_lines.clone())
+            }
+        };
+
+        index.edge_tolerance = edge_tolerance;
+        index.port_size = port_size;
+
+        self.state.spatial = Some(index);
+    }
+
+    /// Report right clicks on the background that didn't hit an edge, and open the
+    /// [`Self::background_context_menu`] for them.
+    fn handle_background_context_menu(&mut self, background: &egui::Response) {
+        if background.secondary_clicked()
+            && let Some(pos) = background.interact_pointer_pos()
+            && self.edge_at(pos).is_none()
+        {
+            self.response.background_right_clicked = Some(pos);
+            self.state.context_menu_pos = Some(pos);
+        }
+
+        let (Some(menu), Some(pos)) = (
+            self.background_context_menu.as_mut(),
+            self.state.context_menu_pos,
+        ) else {
+            return;
+        };
+
+        // the menu stays open after the click, so it needs to remember where it was.
+        if background.context_menu(|ui| menu(ui, pos)).is_none() {
+            self.state.context_menu_pos = None;
+        }
+    }
+
+    /// The rect `node` was drawn at, which could also be a stub.
+    fn node_rect(&self, node: NodeIndex) -> Option<Rect> {
+        self.block_rects.get(&node).copied().or_else(|| {
+            self.stubs
+                .iter()
+                .find(|stub| stub.node == node)
+                .map(|stub| stub.rect)
+        })
+    }
+
+    /// Draw the secondary outline around the block that Tab cycled to.
+    fn draw_candidate(&self, ui: &Ui) {
+        if let Some(rect) = self.state.candidate.and_then(|c| self.block_rects.get(&c)) {
+            draw_border(
+                ui,
+                rect.expand(PORT_OFFSET),
+                self.style.block_rounding_in(*rect),
+                BlockBorder {
+                    stroke: self.style.select,
+                    dashed: true,
+                },
+            );
+        }
+    }
+
+    /// Find the blocks in the visible rect, and whether it changed by more than a fraction of
+    /// a point on the screen.
+    fn update_viewport(&mut self) {
+        let visible = self.response.visible_rect;
+
+        let mut nodes: Vec<NodeIndex> = self
+            .block_rects
+            .iter()
+            .filter(|(_, rect)| rect.intersects(visible))
+            .map(|(node, _)| *node)
+            .collect();
+
+        nodes.sort();
+
+        self.response.visible_nodes = nodes;
+
+        let epsilon = 0.5 / self.state.zoom;
+
+        self.response.viewport_changed = self.state.visible_rect.is_none_or(|last| {
+            (last.min - visible.min).abs().max_elem() > epsilon
+                || (last.max - visible.max).abs().max_elem() > epsilon
+        });
+
+        if self.response.viewport_changed {
+            self.state.visible_rect = Some(visible);
+        }
+    }
+
+    /// `sense`, unless the view is being panned, since panning always goes to the scene no
+    /// matter what is under the pointer.
+    ///
+    /// With touch, blocks can only be tapped, so that dragging a finger over them pans.
+    fn sense(&self, sense: egui::Sense) -> egui::Sense {
+        if self.panning {
+            egui::Sense::hover()
+        } else if self.touch && sense.senses_drag() {
+            egui::Sense::click()
+        } else {
+            sense
+        }
+    }
+
+    /// Pinch to zoom about the middle of the fingers, and pan with all of them, when a
+    /// gesture is happening over `view`.
+    ///
+    /// This returns the zoom the scene should be at after the gesture, if there is one.
+    fn handle_touch(&self, ui: &Ui, view: Rect, scene_rect: &mut Rect) -> Option<f32> {
+        let touch = ui.input(|i| i.multi_touch())?;
+
+        if !view.contains(touch.center_pos) {
+            return None;
+        }
+
+        let zoom = self.state.zoom;
+        let range = Rangef::new(*self.zoom_range.start(), *self.zoom_range.end());
+
+        let new_zoom = range.clamp(zoom * touch.zoom_delta);
+
+        // the scene is centered in the view, so this is the point under the fingers.
+        let center = scene_rect.center() + (touch.center_pos - view.center()) / zoom;
+
+        // scaling about the center keeps that point under the fingers.
+        let scale = zoom / new_zoom;
+        let min = center + (scene_rect.min - center) * scale;
+        let max = center + (scene_rect.max - center) * scale;
+
+        *scene_rect = Rect::from_min_max(min, max).translate(-touch.translation_delta / new_zoom);
+
+        Some(new_zoom)
+    }
+
+    /// Draw the pin on the top right corner of a hovered or pinned block, which pins or
+    /// unpins it when clicked.
+    fn draw_pin(&mut self, ui: &mut Ui, response: &egui::Response, node: NodeIndex, rect: Rect) {
+        if !self.draggable_nodes || self.detail == Detail::Outline {
+            return;
+        }
+
+        let style = self.style;
+        let pinned = self.state.is_pinned(node);
+
+        let size = style.header_height * 0.6;
+        let area = Rect::from_center_size(rect.right_top(), Vec2::splat(size));
+
+        // the pin hangs over the corner, so the block isn't hovered on all of it.
+        let visible = pinned || response.hovered() || ui.rect_contains_pointer(area);
+
+        if !visible {
+            return;
+        }
+
+        // this goes on top of the block, so it gets the click instead of it.
+        let id = ui.make_persistent_id(("pin", node.index()));
+        let pin = ui.interact(area, id, self.sense(egui::Sense::click()));
+
+        if pin.clicked() {
+            self.state.set_pinned(node, !pinned);
+        }
+
+        let color = if pin.hovered() {
+            style.select.color
+        } else if response.hovered() {
+            style.label_color
+        } else {
+            style.label_color.gamma_multiply(0.5)
+        };
+
+        // the head of the pin, with the needle going down and to the left.
+        let head = area.center() + vec2(size * 0.15, -size * 0.15);
+        let tip = area.center() - vec2(size * 0.35, -size * 0.35);
+
+        ui.painter()
+            .line_segment([head, tip], Stroke::new(style.edge.width, color));
+        ui.painter().circle(
+            head,
+            size * 0.25,
+            if pinned { color } else { style.header_fill },
+            Stroke::new(style.edge.width, color),
+        );
+    }
+
+    /// Move the block by dragging its header, which takes effect in the next frame.
+    fn handle_block_drag(&mut self, ui: &Ui, response: &egui::Response, node: NodeIndex) {
+        if !self.draggable_nodes {
+            return;
+        }
+
+        let mut header = response.rect;
+        header.max.y = header.min.y + self.style.header_height;
+
+        let dragging = self.state.dragging == Some(node);
+
+        if dragging || response.hover_pos().is_some_and(|pos| header.contains(pos)) {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Move);
+        }
+
+        if response.drag_started() && press_origin(ui).is_some_and(|pos| header.contains(pos)) {
+            self.state.dragging = Some(node);
+        }
+
+        if self.state.dragging != Some(node) {
+            return;
+        }
+
+        self.state
+            .move_by(node, response.rect.center(), response.drag_delta());
+
+        if response.drag_stopped() {
+            self.state.dragging = None;
+
+            if self.snap_to_grid && self.layout_config().spacing() > 0.0 {
+                self.state.snap(node, self.layout_config().spacing() as f32);
+            }
+        }
+    }
+
+    /// This will draw blocks in the egui ui panel, and also push the position on the
+    /// block rectangle to a hashmap, so that we can use it later.
+    fn assign_and_draw_blocks(
+        &mut self,
+        ui: &mut Ui,
+        layout: &CfgLayout,
+        sizes: &HashMap<NodeIndex, Vec2>,
+    ) {
+        self.hit_target = self.small_block_at(ui);
+
+        // drawing a block needs the view mutably, so the graph is moved out while the blocks
+        // borrow from it, instead of every block getting cloned.
+        let graph = std::mem::take(&mut self.graph);
+
+        for (node, coords) in &layout.coords {
+            // everything outside of the focus gets dimmed, including the text and the glow.
+            let opacity = ui.opacity();
+
+            ui.multiply_opacity(self.focus_opacity(*node));
+
+            self.draw_block(ui, node, &graph[*node], *coords, sizes);
+
+            ui.set_opacity(opacity);
+        }
+
+        self.graph = graph;
+
+        for (node, _) in &layout.coords {
+            debug_assert!(
+                self.block_rects.contains_key(node),
+                "the block {node:?} in the layout has no rect, or one that isn't finite"
+            );
+        }
+    }
+
+    /// Fill the view for an empty graph, with the [`Self::placeholder`] in the middle.
+    fn show_placeholder(&self, ui: &mut Ui) {
+        let rect = ui.available_rect_before_wrap();
+        ui.allocate_rect(rect, egui::Sense::hover());
+
+        if !self.placeholder.is_empty() {
+            ui.painter().text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                self.placeholder,
+                self.style.label_font.clone(),
+                ui.visuals().weak_text_color(),
+            );
+        }
+    }
+
+    /// Draw the block `node` centered at `coords`.
+    fn draw_block(
+        &mut self,
+        ui: &mut Ui,
+        node: &NodeIndex,
+        block: &N,
+        coords: (f64, f64),
+        sizes: &HashMap<NodeIndex, Vec2>,
+    ) {
+        // dragged blocks stay where they were dropped, wherever the layout put them.
+        let center = self
+            .state
+            .pinned_pos(*node)
+            .unwrap_or(pos2(coords.0 as f32, coords.1 as f32));
+
+        let style = self.style;
+
+        let collapsed = self.state.is_collapsed(*node);
+
+        let Some(&size) = sizes.get(node) else {
+            return;
+        };
+
+        // get the rectangle of our basic block or just node, at the correct position.
+        let block_rectangle = Rect::from_center_size(center, size);
+
+        // a block that isn't anywhere can't be drawn, and would only mess up the order of the
+        // ports and everything else that gets sorted by position.
+        if !block_rectangle.is_finite() {
+            return;
+        }
+
+        // the body text is only laid out once it's big enough to be read, and with lazy
+        // text, once it can be seen.
+        let readable = self.detail == Detail::Full;
+        let visible = !self.lazy_text || ui.clip_rect().intersects(block_rectangle);
+
+        let body_galley =
+            (readable && visible).then(|| self.state.galleys.get(ui, *node, block, style).galley);
+
+        // matches get counted even if the block isn't showing its text.
+        let matches = match &body_galley {
+            Some(galley) => find_matches(galley.text(), self.search),
+            None if self.search.is_empty() => Vec::new(),
+            None => find_matches(&self.state.galleys.body_text(*node, block), self.search),
+        };
+
+        if !matches.is_empty() {
+            self.response.search_matches.push((*node, matches.len()));
+        }
+
+        let rounding = style.block_rounding_in(block_rectangle);
+
+        // the shadow is only painted, under the glow, it isn't part of the block rect.
+        if let Some(shadow) = style.shadow
+            && self.detail == Detail::Full
+        {
+            ui.painter().add(shadow.as_shape(block_rectangle, rounding));
+        }
+
+        // TODO: have a setting that disables interaction somehow.
+        let (response, glow_t) = self.handle_block_interaction(ui, &block_rectangle, node);
+
+        self.handle_block_drag(ui, &response, *node);
+
+        // entry and exit blocks get their own header color and outline.
+        let (header_fill, stroke) = if block.is_entry() {
+            (style.entry_header_fill, style.entry_stroke)
+        } else if block.is_exit() {
+            (style.exit_header_fill, style.exit_stroke)
+        } else {
+            (style.header_fill, style.stroke)
+        };
+
+        let border = self
+            .node_stroke
+            .as_ref()
+            .and_then(|border| border(*node, block))
+            .unwrap_or(stroke.into());
+
+        let tint = self.node_tint.as_ref().and_then(|tint| tint(*node, block));
+
+        // the heat goes on top of the tint, both are a bit darker on the header.
+        let (fill, tinted_header_fill) = tint
+            .into_iter()
+            .chain(self.heat_color(*node))
+            .fold((style.fill, header_fill), |(fill, header), color| {
+                (fill.blend(color), header.blend(darken(color, 0.8)))
+            });
+
+        // the selected block shifts toward the selection color, and hovered blocks do so
+        // a little, along with the glow.
+        let selection = if self.state.is_selected(*node) {
+            1.0
+        } else {
+            glow_t * style.hover_fill_strength
+        };
+
+        let shift = |base: Color32, color: Option<Color32>| match color {
+            Some(color) if selection > 0.0 => base.blend(color.gamma_multiply(selection)),
+            _ => base,
+        };
+
+        let fill = shift(fill, style.selected_fill);
+        let tinted_header_fill = shift(tinted_header_fill, style.selected_header_fill);
+
+        // draw the entire node block.
+        ui.painter().rect_filled(block_rectangle, rounding, fill);
+
+        // the header rectangle, width is the size of the block, then we just add the header height.
+        let header_rectangle = Rect::from_min_max(
+            block_rectangle.min,
+            pos2(
+                block_rectangle.max.x,
+                block_rectangle.min.y + style.header_height,
+            ),
+        );
+
+        // the header is the top of the block, so that its corners are exactly the same as the
+        // ones of the body underneath, even when the block is just the header.
+        ui.painter().with_clip_rect(header_rectangle).rect_filled(
+            block_rectangle,
+            rounding,
+            tinted_header_fill,
+        );
+
+        // the outline goes on top of the header, so that it isn't covered by it.
+        draw_border(ui, block_rectangle, rounding, border);
+
+        if self.in_slice(*node) {
+            draw_border(ui, block_rectangle, rounding, style.select.into());
+        }
+
+        let elided = self.draw_header_contents(ui, *node, block, header_rectangle, collapsed);
+
+        self.draw_pin(ui, &response, *node, block_rectangle);
+
+        // the full title is still available when it didn't fit.
+        if elided
+            && response
+                .hover_pos()
+                .is_some_and(|pos| header_rectangle.contains(pos))
+        {
+            response
+                .clone()
+                .on_hover_text_at_pointer(block.title().to_owned());
+        }
+
+        if response.double_clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+            && header_rectangle.contains(pos)
+        {
+            self.state.toggle_collapsed(*node);
+        }
+
+        // add our newly created block rectangle.
+        self.block_rects.insert(*node, block_rectangle);
+
+        if collapsed {
+            return;
+        }
+
+        let text_pos = pos2(
+            block_rectangle.min.x + style.padding.x,
+            header_rectangle.max.y + style.padding.y,
+        );
+
+        // the text would be too small to read anyway, so we don't bother laying it out.
+        if !readable {
+            ui.painter().rect_filled(
+                Rect::from_min_max(text_pos, block_rectangle.max - style.padding),
+                CornerRadius::ZERO,
+                tint.unwrap_or(style.text_color).gamma_multiply(0.2),
+            );
+
+            return;
+        }
+
+        let Some(body_galley) = body_galley else {
+            return;
+        };
+
+        let line_spans = logical_line_spans(&body_galley);
+
+        // the strips go across the whole content width, even if the line is shorter.
+        let content_x = Rangef::new(text_pos.x, block_rectangle.max.x - style.padding.x);
+
+        if let Some(lines) = self.highlighted_lines {
+            for (i, span) in line_spans.iter().enumerate() {
+                if lines.contains(&(*node, i)) {
+                    ui.painter().rect_filled(
+                        Rect::from_x_y_ranges(
+                            content_x,
+                            (span.min + text_pos.y)..=(span.max + text_pos.y),
+                        ),
+                        CornerRadius::ZERO,
+                        style.line_highlight,
+                    );
+                }
+            }
+        }
+
+        self.handle_text_selection(ui, &response, *node, &body_galley, text_pos, content_x);
+
+        if let Some(selection) = self.state.text_selection
+            && selection.node == *node
+        {
+            for rect in match_rects(&body_galley, &[selection.range()]) {
+                ui.painter().rect_filled(
+                    rect.translate(text_pos.to_vec2()),
+                    CornerRadius::ZERO,
+                    style.select_bg,
+                );
+            }
+        }
+
+        for rect in match_rects(&body_galley, &matches) {
+            ui.painter().rect_filled(
+                rect.translate(text_pos.to_vec2()),
+                CornerRadius::same(2),
+                style.search_highlight,
+            );
+        }
+
+        self.handle_links(ui, &response, *node, block, &body_galley, text_pos);
+
+        if response.clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+            && content_x.contains(pos.x)
+        {
+            let y = pos.y - text_pos.y;
+
+            if let Some(line) = line_spans.iter().position(|span| span.contains(y)) {
+                self.response.line_clicked = Some((*node, line));
+            }
+        }
+
+        // a line that can't be wrapped could still be wider than the block.
+        ui.painter().with_clip_rect(block_rectangle).galley(
+            text_pos,
+            body_galley,
+            style.text_color,
+        );
+    }
+
+    /// Underline the links in the body of `node`, and report them when clicked.
+    fn handle_links(
+        &mut self,
+        ui: &Ui,
+        response: &egui::Response,
+        node: NodeIndex,
+        block: &N,
+        galley: &Galley,
+        text_pos: Pos2,
+    ) {
+        let Some(matcher) = &self.link_matcher else {
+            return;
+        };
+
+        let links = find_links(galley, block.body_lines(), matcher);
+
+        let hover = response.hover_pos();
+        let stroke = Stroke::new(1.0, self.style.link_color);
+
+        for (line, range, target) in links {
+            // a link that wraps is split into a rect for every row.
+            let rects: Vec<Rect> = match_rects(galley, &[range])
+                .into_iter()
+                .map(|rect| rect.translate(text_pos.to_vec2()))
+                .collect();
+
+            for rect in &rects {
+                ui.painter()
+                    .line_segment([rect.left_bottom(), rect.right_bottom()], stroke);
+            }
+
+            if !hover.is_some_and(|pos| rects.iter().any(|rect| rect.contains(pos))) {
+                continue;
+            }
+
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+
+            if response.clicked() {
+                self.response.link_clicked = Some((node, line, target));
+            }
+        }
+    }
+
+    /// Select text in the body of `node` by dragging over it, and copy it on Ctrl+C.
+    fn handle_text_selection(
+        &mut self,
+        ui: &Ui,
+        response: &egui::Response,
+        node: NodeIndex,
+        galley: &Galley,
+        text_pos: Pos2,
+        content_x: Rangef,
+    ) {
+        let cursor_at = |pos: Pos2| galley.cursor_from_pos(pos - text_pos).index;
+
+        if response.drag_started() {
+            // the drag only starts after moving a bit, so we want where the press was.
+            self.state.text_selection = press_origin(ui)
+                .filter(|pos| content_x.contains(pos.x) && pos.y >= text_pos.y)
+                .map(|pos| TextSelection {
+                    node,
+                    anchor: cursor_at(pos),
+                    cursor: cursor_at(pos),
+                });
+        }
+
+        let Some(selection) = self.state.text_selection.as_mut() else {
+            return;
+        };
+
+        if selection.node != node {
+            return;
+        }
+
+        if response.dragged()
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            selection.cursor = cursor_at(pos);
+        }
+
+        if response.clicked() {
+            self.state.text_selection = None;
+            return;
+        }
+
+        let range = selection.range();
+
+        if !range.is_empty() && ui.input(|i| i.events.contains(&egui::Event::Copy)) {
+            let text: String = galley
+                .text()
+                .chars()
+                .skip(range.start)
+                .take(range.len())
+                .collect();
+
+            ui.ctx().copy_text(text);
+        }
+    }
+
+    /// Draw the collapse chevron and the title on the left of the header, and the address
+    /// on the right of it.
+    ///
+    /// The title gets elided when the two wouldn't fit next to each other, in which case
+    /// this returns true.
+    fn draw_header_contents(
+        &mut self,
+        ui: &mut Ui,
+        node: NodeIndex,
+        block: &N,
+        header_rectangle: Rect,
+        collapsed: bool,
+    ) -> bool {
+        let style = self.style;
+
+        if self.detail == Detail::Outline {
+            return false;
+        }
+
+        // the area inside of the header that we can actually put text in.
+        let mut inner = header_rectangle.shrink2(vec2(style.button_padding.x, 0.0));
+
+        let mut title_max = inner.max.x;
+
+        if self.detail == Detail::Full {
+            inner.min.x = self.draw_chevron(ui, node, inner.left_center(), collapsed);
+        }
+
+        if self.detail == Detail::Full
+            && let Some(address) = block.address()
+        {
+            let address_rect = ui.painter().text(
+                inner.right_center(),
+                Align2::RIGHT_CENTER,
+                format!("{address:#x}"),
+                style.label_font.clone(),
+                style.header_address_color,
+            );
+
+            // leave a gap the size of the padding between the title and the address.
+            title_max = address_rect.min.x - style.button_padding.x;
+        }
+
+        // a collapsed block still gives a hint of what's in it.
+        if self.detail == Detail::Full && collapsed {
+            let summary_rect = ui.painter().text(
+                pos2(title_max, inner.center().y),
+                Align2::RIGHT_CENTER,
+                block.summary(),
+                style.label_font.clone(),
+                style.header_address_color.gamma_multiply(0.7),
+            );
+
+            title_max = summary_rect.min.x - style.button_padding.x;
+        }
+
+        if self.detail == Detail::Full {
+            title_max = self.draw_badges(
+                ui,
+                node,
+                block,
+                inner.x_range(),
+                title_max,
+                inner.center().y,
+            );
+        }
+
+        // block title, could be empty or not.
+        let label = crate::header_label(block, style);
+
+        let mut job =
+            LayoutJob::simple_singleline(label, style.label_font.clone(), style.label_color);
+
+        // anything that doesn't fit on a single row gets cut off with an ellipsis.
+        job.wrap = TextWrapping::truncate_at_width((title_max - inner.min.x).max(0.0));
+
+        let galley = ui.fonts(|f| f.layout_job(job));
+
+        // NOTE: have an option to put the title in the middle of the header rectangle.
+        let label_pos = inner.left_center() - vec2(0.0, galley.size().y / 2.0);
+
+        let elided = galley.elided;
+
+        // the title is elided, but the measurements can be off while fonts are still loading.
+        ui.painter()
+            .with_clip_rect(header_rectangle)
+            .galley(label_pos, galley, style.label_color);
+
+        elided
+    }
+
+    /// Draw the chevron that toggles whether `node` is collapsed, starting at `left`, and
+    /// return where whatever comes after it should start.
+    fn draw_chevron(&mut self, ui: &mut Ui, node: NodeIndex, left: Pos2, collapsed: bool) -> f32 {
+        let style = self.style;
+        let size = style.label_font.size;
+
+        let rect = Rect::from_min_size(left - vec2(0.0, size / 2.0), Vec2::splat(size));
+
+        let id = ui.make_persistent_id(("chevron", node.index()));
+        let response = ui.interact(rect, id, self.sense(egui::Sense::click()));
+
+        if response.clicked() {
+            self.state.toggle_collapsed(node);
+        }
+
+        let color = if response.hovered() {
+            style.label_color
+        } else {
+            style.label_color.gamma_multiply(0.7)
+        };
+
+        // points to the right when collapsed, and down when expanded.
+        let half = size * 0.25;
+        let c = rect.center();
+
+        let points = if collapsed {
+            vec![
+                c + vec2(-half, -half * 1.2),
+                c + vec2(-half, half * 1.2),
+                c + vec2(half, 0.0),
+            ]
+        } else {
+            vec![
+                c + vec2(-half * 1.2, -half),
+                c + vec2(half * 1.2, -half),
+                c + vec2(0.0, half),
+            ]
+        };
+
+        ui.painter()
+            .add(egui::Shape::convex_polygon(points, color, Stroke::NONE));
+
+        rect.max.x + style.button_padding.x / 2.0
+    }
+
+    /// Draw the badges of `block` as pills that end at `max_x`, and return where they start.
+    ///
+    /// The badges that don't fit between `range.min` and `max_x` are dropped, and replaced
+    /// with a single pill counting how many were dropped.
+    fn draw_badges(
+        &mut self,
+        ui: &mut Ui,
+        node: NodeIndex,
+        block: &N,
+        range: Rangef,
+        max_x: f32,
+        center_y: f32,
+    ) -> f32 {
+        let badges = block.badges();
+
+        if badges.is_empty() {
+            return max_x;
+        }
+
+        let style = self.style;
+        let gap = style.button_padding.x / 2.0;
+
+        let pill = |text: String| {
+            let galley =
+                ui.fonts(|f| f.layout_no_wrap(text, style.label_font.clone(), style.label_color));
+            let width = galley.size().x + style.button_padding.x;
+            (galley, width)
+        };
+
+        let mut pills: Vec<_> = badges.iter().map(|b| pill(b.text.clone())).collect();
+
+        let available = max_x - range.min;
+        let width_of =
+            |pills: &[(Arc<Galley>, f32)]| pills.iter().map(|(_, w)| w + gap).sum::<f32>();
+
+        // drop badges from the end until whatever is left fits, along with the "+n" pill.
+        let mut overflow = None;
+
+        while !pills.is_empty() && width_of(&pills) > available {
+            pills.pop();
+
+            let more = pill(format!("+{}", badges.len() - pills.len()));
+            let fits = width_of(&pills) + more.1 + gap <= available;
+
+            overflow = Some(more);
+
+            if fits {
+                break;
+            }
+        }
+
+        let fills = badges.iter().map(|b| b.fill);
+
+        let mut shown: Vec<_> = pills.into_iter().zip(fills.map(Some)).collect();
+        shown.extend(overflow.map(|more| (more, None)));
+
+        let total: f32 = shown.iter().map(|((_, w), _)| w + gap).sum();
+
+        // never go past the start of the header, even if not even the "+n" pill fits.
+        let mut x = (max_x - total + gap).max(range.min);
+        let start = x;
+
+        for (i, ((galley, width), fill)) in shown.into_iter().enumerate() {
+            let height = galley.size().y;
+            let rect = Rect::from_min_size(pos2(x, center_y - height / 2.0), vec2(width, height));
+
+            ui.painter().rect_filled(
+                rect,
+                CornerRadius::same((height / 2.0) as u8),
+                fill.unwrap_or(style.header_fill.gamma_multiply(1.5)),
+            );
+
+            ui.painter().galley(
+                rect.center() - galley.size() / 2.0,
+                galley,
+                style.label_color,
+            );
+
+            // only actual badges can be clicked, not the overflow count.
+            if fill.is_some() {
+                let id = ui.make_persistent_id(("badge", node.index(), i));
+
+                if ui
+                    .interact(rect, id, self.sense(egui::Sense::click()))
+                    .clicked()
+                {
+                    self.response.badge_clicked = Some((node, i));
+                }
+            }
+
+            x += width + gap;
+        }
+
+        start - gap
+    }
+
+    /// Draw the stubs of the external targets as pills, at the place the layout gave them.
+    ///
+    /// `targets` are the indices into [`Self::external_targets`] that were laid out, with
+    /// `galleys` being their labels.
+    fn draw_external_targets(
+        &mut self,
+        ui: &mut Ui,
+        layout: &CfgLayout,
+        targets: &[usize],
+        galleys: &[Arc<Galley>],
+    ) {
+        let style = self.style;
+
+        for ((node, (x, y)), (&target, galley)) in
+            layout.stubs.iter().zip(targets.iter().zip(galleys))
+        {
+            let rect = Rect::from_center_size(pos2(*x as f32, *y as f32), stub_size(galley, style));
+            let source = self.external_targets[target].0;
+
+            let opacity = ui.opacity();
+
+            ui.multiply_opacity(self.focus_opacity(source));
+
+            let id = ui.make_persistent_id(("stub", target));
+            let response = ui.interact(rect, id, self.sense(egui::Sense::click()));
+
+            if response.clicked() {
+                self.response.external_target_clicked = Some(target);
+            }
+
+            let fill = if response.hovered() {
+                style.header_fill.gamma_multiply(1.5)
+            } else {
+                style.header_fill
+            };
+
+            let rounding = CornerRadius::same((rect.height() / 2.0) as u8);
+
+            ui.painter()
+                .rect(rect, rounding, fill, style.stroke, StrokeKind::Inside);
+
+            if self.detail != Detail::Outline {
+                ui.painter().galley(
+                    rect.center() - galley.size() / 2.0,
+                    galley.clone(),
+                    style.label_color,
+                );
+            }
+
+            ui.set_opacity(opacity);
+
+            self.stubs.push(Stub {
+                node: *node,
+                source,
+                rect,
+            });
+        }
+    }
+
+    /// This will get the position at the point of a rect, either the top
+    /// or bottom, where the next port should be placed depending on `count`.
+    fn layout_ports_on_rect(rect: Rect, kind: PortKind, count: usize) -> Vec<Pos2> {
+        let (left, right) = match kind {
+            PortKind::Input => (rect.left_top(), rect.right_top()),
+            PortKind::Output => (rect.left_bottom(), rect.right_bottom()),
+        };
+
+        (0..count)
+            .map(|i| {
+                let t = (i as f32 + 1.0) / (count as f32 + 1.0);
+                left.lerp(right, t)
+            })
+            .collect()
+    }
+
+    /// Assign the ports and the lines between them, unless no block moved and the graph
+    /// didn't change since the last frame.
+    fn assign_ports(&mut self) {
+        let rects = self.sorted_block_rects();
+        let stubs: Vec<(NodeIndex, NodeIndex)> =
+            self.stubs.iter().map(|s| (s.node, s.source)).collect();
+
+        if let Some(cache) = &self.state.ports
+            && cache.fingerprint == self.fingerprint
+            && cache.rects == rects
+            && cache.stubs == stubs
+        {
+            self.port_positions = Arc::clone(&cache.positions);
+            self.port_lines = Arc::clone(&cache.lines);
+            self.lines_by_port = Arc::clone(&cache.lines_by_port);
+
+            if let Some(stats) = &mut self.stats {
+                stats.ports_cached = true;
+            }
+
+            return;
+        }
+
+        let world_rect = rects
+            .iter()
+            .fold(Rect::NOTHING, |bounds, (_, rect)| bounds.union(*rect));
+
+        let offset = self.port_offset(world_rect);
+
+        let mut positions = HashMap::new();
+
+        self.assign_port_positions(offset, &mut positions);
+        self.assign_stub_port_positions(offset, &mut positions);
+
+        // the lines are sorted by where their ports are, which a port that isn't anywhere
+        // would make flip around between frames.
+        positions.retain(|slot, pos| {
+            debug_assert!(pos.is_finite(), "the port {slot:?} is at {pos:?}");
+            pos.is_finite()
+        });
+
+        self.port_positions = Arc::new(positions);
+
+        let lines = self.assign_port_lines();
+
+        // the first line of a port wins, like when they were searched in order.
+        let mut lines_by_port = HashMap::with_capacity(lines.len() * 2);
+
+        for line in &lines {
+            lines_by_port
+                .entry(line.from)
+                .or_insert_with(|| line.clone());
+            lines_by_port.entry(line.to).or_insert_with(|| line.clone());
+        }
+
+        self.port_lines = Arc::new(lines);
+        self.lines_by_port = Arc::new(lines_by_port);
+
+        self.state.ports = Some(PortCache {
+            fingerprint: self.fingerprint,
+            rects,
+            stubs,
+            world_rect,
+            offset,
+            positions: Arc::clone(&self.port_positions),
+            lines: Arc::clone(&self.port_lines),
+            lines_by_port: Arc::clone(&self.lines_by_port),
+        });
+    }
+
+    /// How far the ports sit away from their blocks.
+    ///
+    /// The cell a port is in mustn't be blocked by its block, so the port has to be more than
+    /// half a cell away, which is more than [`PORT_OFFSET`] once [`RouteConfig::max_cells`]
+    /// makes the cells of a large graph bigger.
+    fn port_offset(&self, world: Rect) -> f32 {
+        if !world.is_positive() {
+            return PORT_OFFSET;
+        }
+
+        // the cells could only get bigger by moving the ports out, if they didn't fit into
+        // the margin, which they do by far.
+        let cell = self
+            .route_config
+            .cell_size_for(self.routing_rect(world, PORT_OFFSET));
+
+        PORT_OFFSET.max(cell.y)
+    }
+
+    fn assign_port_positions(&self, offset: f32, positions: &mut HashMap<PortSlot, Pos2>) {
+        for node in self.graph.node_indices() {
+            let graph = &self.graph;
+
+            // get the indegree of hte current node, without the blocks that weren't placed,
+            // whose edges wait for them instead of going to the origin.
+            let inputs = graph
+                .neighbors_directed(node, petgraph::Incoming)
+                .filter(|n| self.block_rects.contains_key(n))
+                .count();
+            // get the outdegree of the current node, stubs count as outgoing edges too.
+            let outputs = graph
+                .neighbors_directed(node, petgraph::Outgoing)
+                .filter(|n| self.block_rects.contains_key(n))
+                .count()
+                + self.stubs.iter().filter(|s| s.source == node).count();
+
+            if let Some(&rect) = self.block_rects.get(&node) {
+                for (i, mut pos) in Self::layout_ports_on_rect(rect, PortKind::Input, inputs)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let port = PortSlot::new(node, i, PortKind::Input);
+                    // we offset so the ports don't overlap with the basic block rectangles.
+                    pos.y += -offset;
+                    positions.insert(port, pos);
+                }
+
+                for (i, mut pos) in Self::layout_ports_on_rect(rect, PortKind::Output, outputs)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let port = PortSlot::new(node, i, PortKind::Output);
+                    // we offset so the ports don't overlap with the basic block rectangles.
+                    pos.y += offset;
+                    positions.insert(port, pos);
+                }
+            }
+        }
+    }
+
+    fn assign_stub_port_positions(&self, offset: f32, positions: &mut HashMap<PortSlot, Pos2>) {
+        for stub in &self.stubs {
+            for mut pos in Self::layout_ports_on_rect(stub.rect, PortKind::Input, 1) {
+                // we offset so the ports don't overlap with the stub.
+                pos.y += -offset;
+                positions.insert(PortSlot::new(stub.node, 0, PortKind::Input), pos);
+            }
+        }
+    }
+
+    fn draw_arrow_tip(
+        &self,
+        ui: &mut egui::Ui,
+        tip: egui::Pos2,
+        dir: Option<egui::Vec2>,
+        selected: bool,
+    ) {
+        let size = self.style.edge.width * 4.0;
+
+        // get the unit direction of the arrow, a direction without a length points down too,
+        // since it would make an arrow without a base.
+        let dir = dir
+            .filter(|dir| dir.is_finite() && dir.length_sq() > 0.0)
+            .unwrap_or(egui::vec2(0.0, 1.0))
+            .normalized();
+
+        // get the base of the triangle.
+        let base = tip - dir * size;
+
+        // set the vector perpendicular to tip->base, half the size of the base.
+        let perp = egui::vec2(-dir.y, dir.x) * (size * 0.5);
+
+        let p1 = base + perp;
+        let p2 = base - perp;
+
+        let (color, edge) = {
+            if selected {
+                (self.style.select.color, self.style.select)
+            } else {
+                (self.style.edge.color, self.style.edge)
+            }
+        };
+
+        ui.painter()
+            .add(egui::Shape::convex_polygon(vec![tip, p1, p2], color, edge));
+    }
+
+    /// Compute the blocks of the slice again, if the graph changed since it was computed.
+    fn update_slice(&mut self) {
+        let fingerprint = self.fingerprint;
+
+        let Some(slice) = self.state.slice.as_mut() else {
+            return;
+        };
+
+        if !self.graph.contains_node(slice.node) {
+            self.state.slice = None;
+            return;
+        }
+
+        if slice.fingerprint != Some(fingerprint) {
+            let mut nodes = HashSet::from([slice.node]);
+            let mut queue = std::collections::VecDeque::from([slice.node]);
+
+            while let Some(node) = queue.pop_front() {
+                for next in self.graph.neighbors_directed(node, slice.direction) {
+                    if nodes.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            slice.nodes = nodes;
+            slice.fingerprint = Some(fingerprint);
+        }
+
+        let mut nodes: Vec<NodeIndex> = slice.nodes.iter().copied().collect();
+        nodes.sort();
+
+        self.response.slice = nodes;
+    }
+
+    /// Whether `node` is one of the blocks that were picked out by an Alt+click.
+    fn in_slice(&self, node: NodeIndex) -> bool {
+        self.state
+            .slice
+            .as_ref()
+            .is_some_and(|slice| slice.nodes.contains(&node))
+    }
+
+    /// Where the port in `slot` gets drawn, which is on the edge of its block, instead of at
+    /// `pos` where its edge was routed from.
+    fn port_pos(&self, slot: PortSlot, mut pos: Pos2) -> Pos2 {
+        let offset = self.state.ports.as_ref().map_or(PORT_OFFSET, |p| p.offset);
+
+        match slot.kind {
+            PortKind::Output => pos.y -= offset - 2.0,
+            PortKind::Input => pos.y += offset,
+        }
+
+        pos
+    }
+
+    /// The points `poly` of `line` gets drawn through, which carry on from where it was
+    /// routed to the ports drawn on the blocks.
+    fn edge_path(&self, poly: &[Pos2], line: &PortLine) -> Vec<Pos2> {
+        let port = |slot: PortSlot| {
+            self.port_positions
+                .get(&slot)
+                .map(|pos| self.port_pos(slot, *pos))
+        };
+
+        port(line.from)
+            .into_iter()
+            .chain(poly.iter().copied())
+            .chain(port(line.to))
+            .collect()
+    }
+
+    /// The edge that starts or ends at `slot`.
+    fn port_line(&self, slot: PortSlot) -> Option<&PortLine> {
+        self.lines_by_port.get(&slot)
+    }
+
+    /// Hover and click the ports, which has to happen before the edges are drawn so that
+    /// they can be highlighted along with the port.
+    fn handle_port_interaction(&mut self, ui: &mut egui::Ui) {
+        let hit_size = self.port_hit_size();
+
+        // the ports are shared with the cache, so this doesn't copy them.
+        let positions = Arc::clone(&self.port_positions);
+
+        for (&slot, &pos) in positions.iter() {
+            let pos = self.port_pos(slot, pos);
+
+            let id = ui.make_persistent_id(("port", slot));
+            let area = Rect::from_center_size(pos, Vec2::splat(hit_size));
+            let response = ui.interact(area, id, self.sense(egui::Sense::click()));
+
+            let line = self.port_line(slot).cloned();
+
+            if response.hovered() {
+                self.response.port_hovered = Some(slot);
+            }
+
+            if response.clicked() {
+                self.state.selected_edge = line.clone();
+            }
+
+            // peek at where the edge of an output port goes.
+            let target = line
+                .filter(|l| l.from == slot)
+                .map(|l| l.to.node)
+                .filter(|n| self.graph.contains_node(*n));
+
+            if let Some(target) = target {
+                response.on_hover_ui_at_pointer(|ui| self.draw_preview(ui, target));
+            }
+        }
+    }
+
+    /// Whether `line` is drawn highlighted, because it leaves or enters the selected block,
+    /// was clicked, one of its ports is hovered or it's inside of the slice.
+    ///
+    /// Exit blocks don't have any lines leaving them, so the lines coming in are what shows
+    /// that they're selected.
+    fn is_line_highlighted(&self, line: &PortLine) -> bool {
+        self.state.selected == Some(line.from.node)
+            || self.state.selected == Some(line.to.node)
+            || (self.in_slice(line.from.node) && self.in_slice(line.to.node))
+            || self.state.selected_edge.as_ref() == Some(line)
+            || self
+                .response
+                .port_hovered
+                .is_some_and(|slot| line.from == slot || line.to == slot)
+    }
+
+    fn draw_ports(&mut self, ui: &mut egui::Ui) {
+        for (&slot, &pos) in self.port_positions.iter() {
+            let pos = self.port_pos(slot, pos);
+
+            let highlighted = self.response.port_hovered == Some(slot)
+                || self
+                    .port_line(slot)
+                    .is_some_and(|line| self.is_line_highlighted(line));
+
+            let opacity = ui.opacity();
+
+            ui.multiply_opacity(self.port_focus_opacity(slot));
+
+            match slot.kind {
+                PortKind::Output => {
+                    let radius = self.style.edge.width * 3.0;
+
+                    let stroke = if highlighted {
+                        self.style.select
+                    } else {
+                        self.style.edge
+                    };
+
+                    ui.painter().circle_stroke(pos, radius, stroke);
+                    ui.painter().circle_filled(pos, radius, self.style.fill);
+                }
+
+                PortKind::Input => {
+                    self.draw_arrow_tip(ui, pos, None, highlighted);
+                }
+            }
+
+            ui.set_opacity(opacity);
+        }
+    }
+
+    /// The ports of stubs are in focus along with the block the stub belongs to.
+    fn port_focus_opacity(&self, slot: PortSlot) -> f32 {
+        let node = self
+            .stubs
+            .iter()
+            .find(|s| s.node == slot.node)
+            .map_or(slot.node, |s| s.source);
+
+        self.focus_opacity(node)
+    }
+
+    /// Draw a smaller, non interactive version of `node`, with only the first few lines of
+    /// its body, e.g. for a tooltip.
+    fn draw_preview(&self, ui: &mut Ui, node: NodeIndex) {
+        const SCALE: f32 = 0.75;
+        const MAX_LINES: usize = 6;
+        const MAX_HEIGHT: f32 = 160.0;
+
+        let style = self.style;
+        let block = &self.graph[node];
+
+        let scaled =
+            |font: &egui::FontId| egui::FontId::new(font.size * SCALE, font.family.clone());
+
+        let lines = block.body_lines();
+
+        let mut text = lines[..lines.len().min(MAX_LINES)].join("\n");
+
+        if lines.len() > MAX_LINES {
+            text.push_str("\n…");
+        }
+
+        let width = style.size.x * SCALE;
+        let padding = style.padding * SCALE;
+        let header_height = style.header_height * SCALE;
+
+        let galley = ui.fonts(|f| {
+            f.layout(
+                text,
+                scaled(&style.text_font),
+                style.text_color,
+                width - padding.x * 2.0,
+            )
+        });
+
+        let mut title = LayoutJob::simple_singleline(
+            crate::header_label(block, style),
+            scaled(&style.label_font),
+            style.label_color,
+        );
+
+        title.wrap = TextWrapping::truncate_at_width(width - padding.x * 2.0);
+
+        let title = ui.fonts(|f| f.layout_job(title));
+
+        // lines that wrap a lot could still make it too tall, so it gets cut off.
+        let height = header_height + galley.size().y + padding.y * 2.0;
+        let size = vec2(width, height.min(MAX_HEIGHT));
+
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+
+        let painter = ui.painter().with_clip_rect(rect);
+
+        let rounding = style.block_rounding_in(rect);
+
+        painter.rect_filled(rect, rounding, style.fill);
+
+        let header = Rect::from_min_size(rect.min, vec2(width, header_height));
+
+        painter
+            .with_clip_rect(header)
+            .rect_filled(rect, rounding, style.header_fill);
+
+        painter.galley(
+            pos2(
+                header.min.x + padding.x,
+                header.center().y - title.size().y / 2.0,
+            ),
+            title,
+            style.label_color,
+        );
+
+        painter.galley(
+            pos2(rect.min.x + padding.x, header.max.y + padding.y),
+            galley,
+            style.text_color,
+        );
+    }
+
+    /// This will assign a port "edge", from one port to another.
+    ///
+    /// For every node in the in the graph connect each outgoing to port to an incoming port.
+    fn assign_port_lines(&self) -> Vec<PortLine> {
+        // the centers get looked up for every sort comparison, so they go into a dense vec
+        // instead of being looked up in the rect map every time.
+        let bound = self
+            .block_rects
+            .keys()
+            .chain(self.stubs.iter().map(|s| &s.node))
+            .map(|n| n.index() + 1)
+            .max()
+            .unwrap_or(0);
+
+        let mut centers = vec![0.0; bound];
+
+        for (node, rect) in &self.block_rects {
+            centers[node.index()] = rect.center().x;
+        }
+
+        for stub in &self.stubs {
+            centers[stub.node.index()] = stub.rect.center().x;
+        }
+
+        // the edges of blocks without a rect are left out, so every node here has a center.
+        let center_x = |n: NodeIndex| centers.get(n.index()).copied().unwrap_or(0.0);
+        let placed = |n: NodeIndex| self.block_rects.contains_key(&n);
+
+        // the ports of every node and kind, from left to right.
+        let mut sorted_ports: HashMap<(NodeIndex, PortKind), Vec<(PortSlot, f32)>> = HashMap::new();
+
+        for (slot, pos) in self.port_positions.iter() {
+            sorted_ports
+                .entry((slot.node, slot.kind))
+                .or_default()
+                .push((*slot, pos.x));
+        }
+
+        // yes, it's super weird to sort f32s, but whatever.
+        for ports in sorted_ports.values_mut() {
+            ports.sort_by(|a, b| a.1.total_cmp(&b.1));
+        }
+
+        let port = |node: NodeIndex, kind: PortKind, n: usize| {
+            sorted_ports
+                .get(&(node, kind))
+                .and_then(|ports| ports.get(n))
+                .map(|(slot, _)| *slot)
+        };
+
+        // every incoming edge gets its own input port, from left to right by where it comes
+        // from, and by the source and then the edge itself when those are in the same place.
+        let mut input_slots: HashMap<EdgeIndex, usize> = HashMap::new();
+
+        for node in self.graph.node_indices() {
+            let mut incoming: Vec<(EdgeIndex, NodeIndex)> = self
+                .graph
+                .edges_directed(node, petgraph::Direction::Incoming)
+                .filter(|e| placed(e.source()))
+                .map(|e| (e.id(), e.source()))
+                .collect();
+
+            incoming.sort_by(|(le, ln), (re, rn)| {
+                center_x(*ln)
+                    .total_cmp(&center_x(*rn))
+                    .then(ln.cmp(rn))
+                    .then(le.cmp(re))
+            });
+
+            input_slots.extend(incoming.into_iter().enumerate().map(|(n, (e, _))| (e, n)));
+        }
+
+        let mut lines = Vec::new();
+
+        for node in self.graph.node_indices() {
+            if !sorted_ports.contains_key(&(node, PortKind::Output)) {
+                continue;
+            }
+
+            // stubs don't have an edge in the graph, but they still need a port.
+            let mut sorted_out_edges: Vec<(Option<EdgeIndex>, NodeIndex)> = self
+                .graph
+                .edges_directed(node, petgraph::Direction::Outgoing)
+                .filter(|e| placed(e.target()))
+                .map(|e| (Some(e.id()), e.target()))
+                .chain(
+                    self.stubs
+                        .iter()
+                        .filter(|s| s.source == node)
+                        .map(|s| (None, s.node)),
+                )
+                .collect();
+
+            // yes, it's super weird to sort f32s, but whatever.
+            sorted_out_edges.sort_by(|(le, ln), (re, rn)| {
+                center_x(*ln)
+                    .total_cmp(&center_x(*rn))
+                    .then(ln.cmp(rn))
+                    .then(le.cmp(re))
+            });
+
+            // we associate each outgoing edge with an outgoing port.
+            for (n, (edge, target_node)) in sorted_out_edges.iter().enumerate() {
+                let Some(from_port) = port(node, PortKind::Output, n) else {
+                    continue;
+                };
+
+                // a stub only has the one input port.
+                let target_port = match edge {
+                    Some(edge) => input_slots.get(edge).copied(),
+                    None => Some(0),
+                };
+
+                let Some(target_port) = target_port else {
+                    continue;
+                };
+
+                let Some(to_port) = port(*target_node, PortKind::Input, target_port) else {
+                    continue;
+                };
+
+                lines.push(PortLine {
+                    from: from_port,
+                    to: to_port,
+                    edge: *edge,
+                });
+            }
+        }
+
+        lines
+    }
+
+    /// A context with every block and stub rect in `rects` as an obstacle.
+    ///
+    /// The field from the last routing is reused while the scene and the config stay the
+    /// same, and only the rects that changed since then are rasterized again.
+    ///
+    /// There's no context for a scene that isn't finite or is empty, since a grid over it
+    /// would have a nonsense number of cells.
+    fn build_route_context(
+        &mut self,
+        scene: egui::Rect,
+        rects: &[(NodeIndex, Rect)],
+    ) -> Option<RouteContext> {
+        if !scene.is_finite() || !scene.is_positive() {
+            return None;
+        }
+
+        let config = self.route_config.clone();
+
+        let context = match self.state.route_field.take() {
+            Some(cache) if cache.scene == scene && cache.config == config => {
+                let mut field = cache.field;
+
+                let old: HashMap<NodeIndex, Rect> = cache.rects.into_iter().collect();
+                let new: HashMap<NodeIndex, Rect> = rects.iter().copied().collect();
+
+                for (node, rect) in &old {
+                    if new.get(node) != Some(rect) {
+                        field.remove_block_rect(*rect, OBSTACLE_RADIUS);
+                    }
+                }
+
+                for (node, rect) in rects {
+                    if old.get(node) != Some(rect) {
+                        field.add_block_rect(*rect, OBSTACLE_RADIUS);
+                    }
+                }
+
+                if let Some(stats) = &mut self.stats {
+                    stats.field_reused = true;
+                }
+
+                RouteContext::from_field(field, config)
+            }
+            _ => {
+                let mut context = RouteContext::new(scene, config);
+
+                // we just want to hard block pathfinding from going through block rects.
+                for (_, rect) in rects {
+                    context.add_obstacle(*rect, OBSTACLE_RADIUS);
+                }
+
+                context
+            }
+        };
+
+        let grid = context.grid();
+
+        // a port on the border of the grid would get clamped, and its path would hug the edge.
+        debug_assert!(
+            self.port_positions
+                .values()
+                .all(|pos| grid.is_interior(grid.to_cell(*pos))),
+            "every port should map into an interior cell of the routing grid"
+        );
+
+        Some(context)
+    }
+
+    /// Every block and stub rect, sorted by node so that it can be compared between frames.
+    fn sorted_block_rects(&self) -> Vec<(NodeIndex, Rect)> {
+        let mut rects: Vec<(NodeIndex, Rect)> = self
+            .block_rects
+            .iter()
+            .map(|(n, r)| (*n, *r))
+            .chain(self.stubs.iter().map(|s| (s.node, s.rect)))
+            .collect();
+
+        rects.sort_by_key(|(n, _)| *n);
+
+        rects
+    }
+
+    /// Start routing every port line around `rects`, with the paths in `previous` to stick
+    /// to, and the edges on the screen first.
+    fn pending_routes(
+        &self,
+        rects: Vec<(NodeIndex, Rect)>,
+        previous: &[(Vec<Pos2>, PortLine)],
+    ) -> PendingRoutes {
+        let previous: HashMap<PortLine, Vec<Pos2>> = previous
+            .iter()
+            .map(|(poly, line)| (line.clone(), poly.clone()))
+            .collect();
+
+        let queue = self
+            .port_lines
+            .iter()
+            .filter_map(|pl| {
+                let from = *self.port_positions.get(&pl.from)?;
+                let to = *self.port_positions.get(&pl.to)?;
+
+                let bounds = previous
+                    .get(pl)
+                    .map_or(Rect::NOTHING, |poly| Rect::from_points(poly));
+
+                Some((pl.clone(), bounds.union(Rect::from_two_pos(from, to))))
+            })
+            .collect();
+
+        let mut pending = PendingRoutes {
+            fingerprint: self.fingerprint,
+            rects,
+            queue,
+            sorted_for: self.response.visible_rect,
+            routed: Vec::new(),
+            previous,
+        };
+
+        pending.sort(self.response.visible_rect);
+
+        pending
+    }
+
+    /// Route the port lines that are left in `pending` around the blocks, preferring to stay
+    /// close to where they were routed before so that edges don't jump around when the
+    /// geometry changes slightly, until the [`Self::routing_budget`] runs out.
+    ///
+    /// Nothing gets routed in a scene there's no grid for, so every line is left where it
+    /// was routed before, or straight between its ports.
+    fn route_port_lines(&mut self, scene_rect: egui::Rect, pending: &mut PendingRoutes) {
+        let start = stopwatch(&self.stats);
+
+        let Some(mut context) = self.build_route_context(scene_rect, &pending.rects) else {
+            return;
+        };
+
+        lap(&mut self.stats, start, |s| &mut s.field);
+
+        let start = stopwatch(&self.stats);
+        let deadline = self.routing_budget.map(|budget| Instant::now() + budget);
+        let routed = pending.routed.len();
+        let mut failed = 0;
 
-            let Some(&to) = self.port_positions.get(&pl.to) else {
+        // at least one edge gets routed every frame, so that it always gets done.
+        while let Some((pl, _)) = pending.queue.pop() {
+            let (Some(&from), Some(&to)) = (
+                self.port_positions.get(&pl.from),
+                self.port_positions.get(&pl.to),
+            ) else {
                 continue;
             };
 
-            let mut astar = AStar::new(&field);
+            let path = match pending.previous.get(&pl) {
+                Some(seed) => context.route_seeded(from, to, seed),
+                None => context.route(from, to),
+            };
+
+            // both ports can be in the same cell for tiny blocks, which gives a path of a
+            // single point, so it's just drawn straight between them instead. an edge
+            // without a path is drawn straight too, so that it doesn't go missing.
+            match path {
+                Ok(poly) if poly.len() >= 2 => pending.routed.push((poly, pl)),
+                Ok(_) => pending.routed.push((vec![from, to], pl)),
+                Err(_) => {
+                    failed += 1;
+                    pending.routed.push((vec![from, to], pl));
+                }
+            }
 
-            if let Some(poly) = astar.find_path(from, to) {
-                routed_polylines.push((poly, pl.clone()));
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
             }
         }
 
-        for (poly, _) in routed_polylines.iter() {
-            ui.painter()
-                .add(egui::Shape::line(poly.clone(), self.style.edge));
+        // seeded routes put the costs they changed back, so the field is as it was built.
+        self.state.route_field = Some(FieldCache {
+            scene: scene_rect,
+            config: self.route_config.clone(),
+            rects: pending.rects.clone(),
+            field: context.into_field(),
+        });
+
+        lap(&mut self.stats, start, |s| &mut s.routing);
+
+        if let Some(stats) = &mut self.stats {
+            stats.edges_routed = pending.routed.len() - routed;
+            stats.edges_failed = failed;
+        }
+    }
+
+    /// Every routed edge of `pending`, along with the ones that are left where they were
+    /// routed before, or straight between their ports.
+    fn pending_lines(&self, pending: &PendingRoutes) -> Vec<(Vec<Pos2>, PortLine)> {
+        let left = pending.queue.iter().filter_map(|(pl, _)| {
+            let poly = match pending.previous.get(pl) {
+                Some(poly) => poly.clone(),
+                None => vec![
+                    *self.port_positions.get(&pl.from)?,
+                    *self.port_positions.get(&pl.to)?,
+                ],
+            };
+
+            Some((poly, pl.clone()))
+        });
+
+        pending.routed.iter().cloned().chain(left).collect()
+    }
+
+    fn draw_edges(&mut self, ui: &mut egui::Ui, scene_rect: egui::Rect) {
+        let id = ui.make_persistent_id("cfg_edge_cache_v1");
+
+        let rects = self.sorted_block_rects();
+
+        // without any blocks there's nothing to route around, nor a scene to route in, so
+        // the cache is left alone for the first frame that has some.
+        if rects.is_empty() || !scene_rect.is_finite() || !scene_rect.is_positive() {
+            self.edge_lines = Arc::new([]);
+            return;
+        }
+
+        let cache = ui.ctx().data_mut(|d| d.get_persisted::<Arc<EdgeCache>>(id));
+
+        // a different graph can have its blocks in the same places, so the lines of the
+        // cache also have to be the ones of this graph for it to be used at all.
+        let cache = cache.filter(|cache| self.lines_match_graph(&cache.lines));
+
+        // we only have to route again if the graph changed or any of the blocks moved or
+        // changed size, and the block that is being dragged only gets routed once it's
+        // dropped.
+        let lines = match cache {
+            Some(cache)
+                if cache.fingerprint == Some(self.fingerprint)
+                    && ((cache.rects == rects && self.state.routing.is_none())
+                        || self.state.dragging.is_some()) =>
+            {
+                if let Some(stats) = &mut self.stats {
+                    stats.edges_cached = true;
+                }
+
+                cache.lines.clone()
+            }
+            previous => {
+                // the edges that are left from the last frame keep going, unless something
+                // moved since then, which starts over from the edges routed so far.
+                let mut pending = match self.state.routing.take() {
+                    Some(mut pending)
+                        if pending.fingerprint == self.fingerprint && pending.rects == rects =>
+                    {
+                        pending.prioritize(self.response.visible_rect);
+                        pending
+                    }
+                    Some(pending) => self.pending_routes(rects, &pending.seeds()),
+                    None => {
+                        let previous = previous.map(|c| c.lines.clone()).unwrap_or_default();
+                        self.pending_routes(rects, &previous)
+                    }
+                };
+
+                self.route_port_lines(scene_rect, &mut pending);
+
+                let lines: Arc<[_]> = self.pending_lines(&pending).into();
+
+                if pending.queue.is_empty() {
+                    ui.ctx().data_mut(|d| {
+                        d.insert_persisted(
+                            id,
+                            Arc::new(EdgeCache {
+                                fingerprint: Some(self.fingerprint),
+                                rects: pending.rects,
+                                lines: lines.clone(),
+                            }),
+                        )
+                    });
+                } else {
+                    self.state.routing = Some(pending);
+                    self.animate(Animation::Routing, 0.0);
+                }
+
+                lines
+            }
+        };
+
+        self.state.cache_ids.insert(id);
+        self.touch_cache(ui.ctx(), id);
+
+        let start = stopwatch(&self.stats);
+
+        let mut highlighted = Vec::new();
+
+        // a line needs two points, which lines that were routed elsewhere might not have.
+        let drawn = || lines.iter().filter(|(poly, _)| poly.len() >= 2);
+
+        // the mesh depends on which lines are highlighted and how much they're in focus,
+        // and on the zoom, since the feathering is a pixel wide.
+        let mut hasher = DefaultHasher::new();
+
+        (ui.ctx().pixels_per_point() * self.state.zoom)
+            .to_bits()
+            .hash(&mut hasher);
+        self.style.edge.hash(&mut hasher);
+        (self.style.taken_edge, self.style.fall_through_edge).hash(&mut hasher);
+        // the edges are drawn on to the ports, which move along with a dragged block.
+        Arc::as_ptr(&self.port_positions).hash(&mut hasher);
+
+        for (i, (poly, pl)) in drawn().enumerate() {
+            let focus = self.edge_focus_opacity(pl);
+            let is_highlighted = self.is_line_highlighted(pl);
+
+            (i, is_highlighted, focus.to_bits()).hash(&mut hasher);
+
+            if is_highlighted {
+                highlighted.push((poly, pl, focus));
+            }
+        }
+
+        let key = hasher.finish();
+
+        // the edges that aren't highlighted don't change until the edges do, so they're
+        // tessellated once into a single mesh, instead of every edge being a shape of its
+        // own that gets tessellated every frame.
+        let mesh = match self.state.edge_mesh.take() {
+            Some(cached) if Arc::ptr_eq(&cached.lines, &lines) && cached.key == key => cached.mesh,
+            _ => Arc::new(self.tessellate_edges(ui, drawn())),
+        };
+
+        self.state.edge_mesh = Some(EdgeMesh {
+            lines: Arc::clone(&lines),
+            key,
+            mesh: Arc::clone(&mesh),
+        });
+
+        ui.painter().add(egui::Shape::Mesh(mesh));
+
+        if let Some(stats) = &mut self.stats {
+            stats.edges_drawn = drawn().count();
+            stats.edge_shapes = 1 + highlighted.len();
+        }
+
+        let opacity = ui.opacity();
+
+        // the highlighted edges go on top of the rest.
+        for (poly, pl, focus) in highlighted {
+            let should_dash = matches!(self.line_kind(pl), Some(EdgeKind::FallThrough));
+
+            ui.multiply_opacity(focus);
+
+            let path = self.edge_path(poly, pl);
+
+            if should_dash {
+                ui.painter().add(egui::Shape::dotted_line(
+                    &path,
+                    self.style.select.color.gamma_multiply(0.5),
+                    12.0,
+                    2.0,
+                ));
+            } else {
+                ui.painter().add(egui::Shape::line(path, self.style.select));
+            }
+
+            ui.set_opacity(opacity);
+        }
+
+        lap(&mut self.stats, start, |s| &mut s.edges);
+
+        self.edge_lines = lines;
+    }
+
+    /// Tessellate every line in `lines` that isn't highlighted into one mesh, in the color of
+    /// its kind with its focus opacity baked in.
+    fn tessellate_edges<'l>(
+        &self,
+        ui: &Ui,
+        lines: impl Iterator<Item = &'l (Vec<Pos2>, PortLine)>,
+    ) -> egui::Mesh {
+        let ctx = ui.ctx();
+
+        // the scene scales the mesh, so it's tessellated for the pixels it ends up on.
+        let mut tessellator = egui::epaint::Tessellator::new(
+            ctx.pixels_per_point() * self.state.zoom,
+            ctx.tessellation_options(|options| *options),
+            ctx.fonts(|fonts| fonts.font_image_size()),
+            Vec::new(),
+        );
+
+        let mut mesh = egui::Mesh::default();
+
+        for (poly, pl) in lines.filter(|(_, pl)| !self.is_line_highlighted(pl)) {
+            let stroke = Stroke {
+                color: self
+                    .style
+                    .edge_color(self.line_kind(pl))
+                    .gamma_multiply(self.edge_focus_opacity(pl)),
+                ..self.style.edge
+            };
+
+            tessellator.tessellate_path(
+                &egui::epaint::PathShape::line(self.edge_path(poly, pl), stroke),
+                &mut mesh,
+            );
+        }
+
+        mesh
+    }
+
+    /// The kind of the edge `line` is for, as long as that edge still connects the blocks of
+    /// the line, since removed edges leave their index to be reused by another one.
+    fn line_kind(&self, line: &PortLine) -> Option<EdgeKind> {
+        let edge = line.edge?;
+
+        (self.graph.edge_endpoints(edge) == Some((line.from.node, line.to.node)))
+            .then(|| self.graph[edge].kind())
+    }
+
+    /// Whether every edge in `lines` is one of the port lines of this frame, which it isn't
+    /// when the lines are left over from another graph.
+    fn lines_match_graph(&self, lines: &[(Vec<Pos2>, PortLine)]) -> bool {
+        lines
+            .iter()
+            .all(|(_, line)| self.port_line(line.from) == Some(line))
+    }
+
+    /// Move the edge cache `id` to the back of the [`CacheRegistry`], and evict the ones at
+    /// the front that don't fit into the [`Self::cache_budget`].
+    fn touch_cache(&mut self, ctx: &egui::Context, id: egui::Id) {
+        let evicted = ctx.data_mut(|d| {
+            let registry = d.get_temp_mut_or_default::<CacheRegistry>(CacheRegistry::id());
+
+            registry.used.retain(|used| *used != id);
+            registry.used.push(id);
+
+            // the cache that was just used always stays.
+            let Some(over) = self
+                .cache_budget
+                .and_then(|budget| registry.used.len().checked_sub(budget.max(1)))
+            else {
+                return Vec::new();
+            };
+
+            let evicted: Vec<egui::Id> = registry.used.drain(..over).collect();
+
+            for id in &evicted {
+                d.remove::<Arc<EdgeCache>>(*id);
+            }
+
+            evicted
+        });
+
+        // the state could've been shown under another id before, whose cache is gone now.
+        for id in evicted {
+            self.state.cache_ids.remove(&id);
         }
+    }
+
+    /// Edges are only in focus when both of their ends are, where the end of an edge going
+    /// to an external target is its source.
+    fn edge_focus_opacity(&self, line: &PortLine) -> f32 {
+        let to = if self.graph.contains_node(line.to.node) {
+            line.to.node
+        } else {
+            line.from.node
+        };
 
-        ui.ctx()
-            .data_mut(|d| d.insert_persisted(id, routed_polylines));
+        self.focus_opacity(line.from.node)
+            .min(self.focus_opacity(to))
     }
 
-    pub fn show(&mut self, ui: &mut Ui, scene_rect: &mut Rect) {
+    pub fn show(&mut self, ui: &mut Ui) -> CfgResponse {
+        // every id of the view is under its own, so that views next to each other don't
+        // share any of their state.
+        ui.push_id(self.id, |ui| self.show_graph(ui)).inner
+    }
+
+    fn show_graph(&mut self, ui: &mut Ui) -> CfgResponse {
+        // a view that is kept around is shown again, and these get filled in every frame.
+        self.block_rects.clear();
+        self.stubs.clear();
+
+        // an empty graph has nothing to lay out or route, and everything that is kept about
+        // the last graph stays for when there is one again.
+        if self.graph.node_count() == 0 {
+            self.show_placeholder(ui);
+            return std::mem::take(&mut self.response);
+        }
+
+        let graph = &self.graph;
+
+        // indices of removed blocks can get reused, so nothing about them can be kept.
+        let (selection, other) = self.state.retain_nodes(|n| graph.contains_node(n));
+
+        self.response.selection_cleared = selection;
+        self.response.state_dropped = other;
+        self.state.fingerprint = Some(self.fingerprint);
+        self.state.animations = Animations::default();
+
+        let style = self.style;
+        let layouts = self.state.galleys.layouts();
+        let start = stopwatch(&self.stats);
+
+        self.state.galleys.update_fonts(ui, style);
+
+        // the sizes that weren't measured up front get measured from the same galleys that
+        // are used for drawing the blocks.
+        let sizes: HashMap<NodeIndex, Vec2> = graph
+            .node_indices()
+            .map(|n| {
+                let measured = self.measured_sizes.and_then(|sizes| sizes.get(&n));
+
+                let body = match measured {
+                    Some(_) => None,
+                    // blocks that weren't on the screen yet haven't been laid out.
+                    None if self.lazy_text => self.state.galleys.cached(ui, n, &graph[n], style),
+                    None => Some(self.state.galleys.get(ui, n, &graph[n], style)),
+                };
+
+                let size = match (measured, body) {
+                    (Some(size), _) => *size,
+                    (None, Some(body)) => {
+                        crate::block_rectangle(&body.galley, body.header_width, style, false).size()
+                    }
+                    (None, None) => ui.fonts(|f| crate::estimate_block_size(&graph[n], style, f)),
+                };
+
+                // collapsed blocks are only their header.
+                let size = if self.state.is_collapsed(n) {
+                    vec2(size.x, style.header_height)
+                } else {
+                    size
+                };
+
+                let width = ports_width(graph, self.external_targets, style, n);
+
+                (n, vec2(size.x.max(width), size.y))
+            })
+            .collect();
+
+        lap(&mut self.stats, start, |s| &mut s.text);
+
+        // the external targets that can actually be drawn, along with their labels.
+        let targets: Vec<usize> = (0..self.external_targets.len())
+            .filter(|&i| graph.contains_node(self.external_targets[i].0))
+            .collect();
+
+        let stub_galleys: Vec<Arc<Galley>> = targets
+            .iter()
+            .map(|&i| {
+                let label = self.external_targets[i].1.clone();
+                ui.fonts(|f| f.layout_no_wrap(label, style.label_font.clone(), style.label_color))
+            })
+            .collect();
+
+        let stubs: Vec<(NodeIndex, Vec2)> = targets
+            .iter()
+            .zip(&stub_galleys)
+            .map(|(&i, galley)| (self.external_targets[i].0, stub_size(galley, style)))
+            .collect();
+
         // calculate the layout of the graph.
         // btw this should be pretty cheap to calculate.
-        let layout = get_cfg_layout(ui, &self.graph, &self.layout_config, &self.style);
-
-        egui::Scene::new()
-            .max_inner_size([layout.width as f32 + 800.0, layout.height as f32 + 800.0])
-            .zoom_range(0.1..=2.0)
-            .show(ui, scene_rect, |ui| {
-                self.assign_and_draw_blocks(ui, &layout);
-                self.assign_port_positions();
-                self.assign_port_lines();
-                self.draw_edges(ui, self.get_world_rect(None));
+        let start = stopwatch(&self.stats);
+
+        let layout = get_cfg_layout(&self.graph, &self.layout_config(), |n, _| sizes[&n], &stubs);
+
+        lap(&mut self.stats, start, |s| &mut s.layout);
+
+        // the scene needs the rect while we need the rest of the state inside of it.
+        let mut scene_rect = self.state.scene_rect;
+
+        self.handle_peek(
+            ui,
+            ui.available_rect_before_wrap(),
+            &layout,
+            &sizes,
+            &mut scene_rect,
+        );
+
+        // the candidate is for the keyboard, using the mouse drops it.
+        if ui.input(|i| i.pointer.any_pressed()) {
+            self.state.candidate = None;
+        }
+
+        // the shortcuts only apply while the pointer is over the view, and nothing else
+        // wants the keyboard.
+        let shortcuts = ui.rect_contains_pointer(ui.available_rect_before_wrap())
+            && !ui.ctx().wants_keyboard_input();
+
+        if shortcuts {
+            self.handle_zoom_keys(ui);
+
+            if self.escape_clears
+                && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape))
+            {
+                self.clear_on_escape();
+            }
+        }
+
+        // a search that was cleared stays hidden until the query changes.
+        match &self.state.dismissed_search {
+            Some(query) if query == self.search => self.search = "",
+            _ => self.state.dismissed_search = None,
+        }
+
+        // the scene fills the rest of the ui, and the zoom is how much of it the rect covers.
+        if let Some(zoom) = self.state.zoom_to.take() {
+            let zoom = zoom.clamp(*self.zoom_range.start(), *self.zoom_range.end());
+            let size = ui.available_size_before_wrap() / zoom;
+
+            scene_rect = Rect::from_center_size(scene_rect.center(), size);
+        }
+
+        // the middle button and holding space always pan, even when starting over a block.
+        // space is a shortcut like any other, so typing a space elsewhere doesn't pan.
+        self.panning =
+            ui.input(|i| i.pointer.middle_down() || (shortcuts && i.key_down(egui::Key::Space)));
+
+        self.touch = ui.input(|i| i.any_touches());
+
+        let touch_zoom = self.handle_touch(ui, ui.available_rect_before_wrap(), &mut scene_rect);
+
+        // the gesture was already handled above, so the scene shouldn't zoom or pan by it again.
+        let zoom_range = match touch_zoom {
+            Some(zoom) => zoom * 0.999..=zoom * 1.001,
+            None => self.zoom_range.clone(),
+        };
+
+        // dragging with a modifier draws a rubber band instead of panning.
+        let pan_buttons = if touch_zoom.is_some() {
+            DragPanButtons::empty()
+        } else if self.state.box_selection.is_some()
+            || ui.input(|i| i.modifiers.shift || i.modifiers.command)
+        {
+            DragPanButtons::all() - DragPanButtons::PRIMARY
+        } else {
+            DragPanButtons::all()
+        };
+
+        let size = vec2(layout.width as f32, layout.height as f32);
+
+        let parent_clip = ui.clip_rect();
+
+        let scene = egui::Scene::new()
+            .drag_pan_buttons(pan_buttons)
+            .max_inner_size(size + self.scene_margin_for(size))
+            .zoom_range(zoom_range)
+            .show(ui, &mut scene_rect, |ui| {
+                let transform = ui.ctx().layer_transform_to_global(ui.layer_id());
+                let zoom = transform.map_or(1.0, |t| t.scaling);
+
+                // the scene clips to all of its rect, even the part the ui it's in doesn't
+                // show, so the glows of blocks at the edge would be painted over other panels.
+                if let Some(transform) = transform {
+                    ui.set_clip_rect(ui.clip_rect().intersect(transform.inverse() * parent_clip));
+                }
+
+                self.state.zoom = zoom;
+                self.detail = Detail::from_zoom(zoom, self.style);
+
+                self.response.heat_range = self.heat.and_then(|heat| {
+                    let mut values = heat.values().copied().filter(|v| v.is_finite());
+                    let first = values.next()?;
+
+                    Some(values.fold(Rangef::point(first), |r, v| {
+                        Rangef::new(r.min.min(v), r.max.max(v))
+                    }))
+                });
+
+                self.update_slice();
+
+                // everything outside of the clip rect can't be seen.
+                self.response.visible_rect = ui.clip_rect();
+
+                // the hovered block from the last frame, since this one hasn't been drawn yet.
+                self.focus = self.focus_set();
+                self.state.hovered = None;
+
+                let drawn_layouts = self.state.galleys.layouts();
+                let start = stopwatch(&self.stats);
+
+                self.assign_and_draw_blocks(ui, &layout, &sizes);
+
+                lap(&mut self.stats, start, |s| &mut s.blocks);
+
+                // blocks that just got laid out replace their estimated size next frame.
+                if self.lazy_text && self.state.galleys.layouts() != drawn_layouts {
+                    self.animate(Animation::LazyText, 0.0);
+                }
+
+                // flashing a block that wasn't drawn doesn't do anything.
+                let drawn = &self.block_rects;
+                self.state.flashes.retain(|n, _| drawn.contains_key(n));
+
+                self.handle_candidate_keys(ui);
+                self.draw_candidate(ui);
+
+                self.response.search_matches.sort_by_key(|(n, _)| *n);
+
+                self.draw_external_targets(ui, &layout, &targets, &stub_galleys);
+                let start = stopwatch(&self.stats);
+
+                self.assign_ports();
+
+                lap(&mut self.stats, start, |s| &mut s.ports);
+                self.handle_port_interaction(ui);
+                self.draw_edges(ui, self.get_routing_rect());
+                self.update_spatial_index();
                 self.draw_ports(ui);
+
+                // the background of the scene is what gets dragged for panning.
+                let background = ui.response();
+
+                self.handle_box_selection(ui, &background);
+                self.handle_edge_double_click(&background);
+                self.handle_background_context_menu(&background);
             });
+
+        // the scene only gets the click when no block was hit, and dragging it pans instead.
+        // a click on an edge could be the first of a double click, so it isn't a click on
+        // the background either.
+        let on_edge = scene
+            .response
+            .interact_pointer_pos()
+            .and_then(|pos| self.edge_at(pos))
+            .is_some();
+
+        if scene.response.clicked() && self.deselect_on_background && !on_edge {
+            let multi_selected = !self.state.multi_selection.is_empty();
+
+            self.state.multi_selection.clear();
+            self.state.selected_edge = None;
+            self.state.slice = None;
+
+            self.response.selection_cleared |=
+                self.state.selected.take().is_some() || multi_selected;
+        }
+
+        // the scene fits the rect into the ui, so the rect can just be everything drawn.
+        if std::mem::take(&mut self.state.fit)
+            && let Some(bounds) = self.state.world_rect()
+            && bounds.is_positive()
+        {
+            scene_rect = bounds.expand(self.layout_config().spacing() as f32);
+        }
+
+        // keep the zoom, but move the block to the center.
+        if let Some(node) = self.state.center_on.take()
+            && let Some(rect) = self.node_rect(node)
+        {
+            scene_rect = scene_rect.translate(rect.center() - scene_rect.center());
+        }
+
+        self.state.scene_rect = scene_rect;
+
+        self.update_viewport();
+
+        // every animation asks for its repaint here, so there's one request for the soonest.
+        if let Some(after) = self.state.animations.repaint_after {
+            ui.ctx().request_repaint_after_secs(after);
+        }
+
+        if let Some(mut stats) = self.stats.take() {
+            stats.nodes_drawn = self.block_rects.len();
+            stats.galleys_laid_out = self.state.galleys.layouts().saturating_sub(layouts);
+            self.response.stats = Some(stats);
+        }
+
+        std::mem::take(&mut self.response)
     }
 }
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use egui::text::Fonts;
+use egui::{Color32, Galley, Ui};
+use petgraph::graph::NodeIndex;
+
+use crate::BlockLike;
+use crate::style::{BlockWidth, NodeStyle};
+
+/// Everything a body galley depends on, so that we know when it has to be laid out again.
+#[derive(Clone, Debug, PartialEq)]
+struct GalleyKey {
+    /// See [`BlockLike::content_hash`].
+    content: u64,
+    /// See [`NodeStyle::geometry_hash`].
+    geometry: u64,
+    /// The plain body is laid out in this color, so it's the only color that matters.
+    color: Color32,
+    pixels_per_point: f32,
+    /// See [`fonts_hash`].
+    fonts: u64,
+}
+
+/// The body of a block, laid out.
+#[derive(Clone, Debug)]
+pub(super) struct BodyLayout {
+    pub(super) galley: Arc<Galley>,
+    /// The width the header needs, see [`crate::header_width`].
+    ///
+    /// This is only measured for [`BlockWidth::FitContent`], since it's unused otherwise.
+    pub(super) header_width: f32,
+}
+
+/// The body galley of every block, kept between frames.
+#[derive(Clone, Debug, Default)]
+pub(super) struct GalleyCache {
+    galleys: HashMap<NodeIndex, (GalleyKey, BodyLayout)>,
+    /// The body lines of every block joined into one string, along with the hash of the
+    /// content they were joined for.
+    texts: HashMap<NodeIndex, (u64, Arc<str>)>,
+    /// How many galleys were laid out, because they weren't in the cache.
+    layouts: usize,
+    /// The [`fonts_hash`] of this frame, which is only hashed once for every block.
+    fonts: u64,
+}
+
+impl GalleyCache {
+    pub(super) fn layouts(&self) -> usize {
+        self.layouts
+    }
+
+    /// Hash the fonts of this frame, so that every galley measured with other fonts gets
+    /// laid out again.
+    pub(super) fn update_fonts(&mut self, ui: &Ui, style: &NodeStyle) {
+        self.fonts = ui.fonts(|f| fonts_hash(f, style));
+    }
+
+    /// The body of `block` if it's already laid out for its current content and style.
+    pub(super) fn cached<N: BlockLike>(
+        &self,
+        ui: &Ui,
+        node: NodeIndex,
+        block: &N,
+        style: &NodeStyle,
+    ) -> Option<BodyLayout> {
+        let key = self.key(ui, block, style);
+
+        self.galleys
+            .get(&node)
+            .and_then(|(cached, body)| (*cached == key).then(|| body.clone()))
+    }
+
+    /// Get the body of `block`, only laying it out again if its content or style changed.
+    pub(super) fn get<N: BlockLike>(
+        &mut self,
+        ui: &Ui,
+        node: NodeIndex,
+        block: &N,
+        style: &NodeStyle,
+    ) -> BodyLayout {
+        let key = self.key(ui, block, style);
+
+        if let Some((cached, body)) = self.galleys.get(&node)
+            && *cached == key
+        {
+            return body.clone();
+        }
+
+        let galley = crate::layout_body_galley(ui, block, style);
+
+        let header_width = match style.width {
+            BlockWidth::Fixed => 0.0,
+            BlockWidth::FitContent { .. } => {
+                ui.fonts(|f| crate::block_header_width(f, block, style))
+            }
+        };
+
+        let body = BodyLayout {
+            galley,
+            header_width,
+        };
+
+        self.layouts += 1;
+        self.galleys.insert(node, (key, body.clone()));
+
+        body
+    }
+
+    /// The body lines of `block` joined by newlines, which are only joined again once its
+    /// content changed.
+    pub(super) fn body_text<N: BlockLike>(&mut self, node: NodeIndex, block: &N) -> Arc<str> {
+        let key = block.content_hash();
+
+        if let Some((cached, text)) = self.texts.get(&node)
+            && *cached == key
+        {
+            return text.clone();
+        }
+
+        let text: Arc<str> = block.body_lines().join("\n").into();
+        self.texts.insert(node, (key, text.clone()));
+
+        text
+    }
+
+    fn key<N: BlockLike>(&self, ui: &Ui, block: &N, style: &NodeStyle) -> GalleyKey {
+        GalleyKey {
+            content: block.content_hash(),
+            geometry: style.geometry_hash(),
+            color: style.text_color,
+            pixels_per_point: ui.ctx().pixels_per_point(),
+            fonts: self.fonts,
+        }
+    }
+
+    /// Forget the galleys of every node that `keep` returns false for.
+    pub(super) fn retain(&mut self, keep: impl Fn(NodeIndex) -> bool) {
+        self.galleys.retain(|node, _| keep(*node));
+        self.texts.retain(|node, _| keep(*node));
+    }
+}
+
+/// A hash of how big the fonts of `style` are, which changes once other fonts are installed
+/// with [`egui::Context::set_fonts`].
+///
+/// Only a few glyphs are measured, since hashing the font files every frame would be too slow.
+fn fonts_hash(fonts: &Fonts, style: &NodeStyle) -> u64 {
+    hash(&|h| {
+        for font in [&style.text_font, &style.label_font] {
+            fonts.row_height(font).to_bits().hash(h);
+
+            for c in ['0', 'W', 'm'] {
+                fonts.glyph_width(font, c).to_bits().hash(h);
+            }
+        }
+    })
+}
+
+fn hash(value: &dyn Fn(&mut DefaultHasher)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value(&mut hasher);
+    hasher.finish()
+}
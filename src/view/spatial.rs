@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use egui::{Pos2, Rect, Vec2};
+use petgraph::graph::NodeIndex;
+
+use super::{PortLine, PortSlot, distance_to_polyline};
+
+/// What is under a point of the scene, see [`super::CfgViewState::hit_test`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hit {
+    Node(NodeIndex),
+    Edge(PortLine),
+    Port(PortSlot),
+    Background,
+}
+
+/// Something in a bucket, as its index into the lists of the [`SpatialIndex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Entry {
+    Node(usize),
+    Port(usize),
+    /// An edge, and the segment of it that starts at that point.
+    Segment(usize, usize),
+}
+
+/// A uniform grid over the blocks, ports and edges as they were drawn last, so that finding
+/// what is at a point only looks at what is in the buckets around it.
+///
+/// The buckets are about as big as the biggest block, so a block is in at most four of them.
+#[derive(Clone, Debug)]
+pub(super) struct SpatialIndex {
+    bucket: f32,
+    buckets: HashMap<(i32, i32), Vec<Entry>>,
+    /// Every block rect, sorted by node.
+    pub(super) rects: Vec<(NodeIndex, Rect)>,
+    ports: Vec<(PortSlot, Pos2)>,
+    pub(super) edges: Arc<[(Vec<Pos2>, PortLine)]>,
+    /// How far away from an edge still hits it, which changes with the zoom.
+    pub(super) edge_tolerance: f32,
+    /// How big the area around a port is that hits it, which changes with the zoom.
+    pub(super) port_size: f32,
+}
+
+impl SpatialIndex {
+    pub(super) fn new(
+        rects: Vec<(NodeIndex, Rect)>,
+        mut ports: Vec<(PortSlot, Pos2)>,
+        edges: Arc<[(Vec<Pos2>, PortLine)]>,
+    ) -> Self {
+        let bucket = rects
+            .iter()
+            .map(|(_, rect)| rect.width().max(rect.height()))
+            .fold(0.0, f32::max)
+            .max(32.0);
+
+        ports.sort_by_key(|(slot, _)| (slot.node, slot.kind as u8, slot.slot));
+
+        let mut index = Self {
+            bucket,
+            buckets: HashMap::new(),
+            rects: Vec::new(),
+            ports: Vec::new(),
+            edges: Arc::new([]),
+            edge_tolerance: 0.0,
+            port_size: 0.0,
+        };
+
+        for (i, (_, rect)) in rects.iter().enumerate() {
+            index.insert(*rect, Entry::Node(i));
+        }
+
+        for (i, (_, pos)) in ports.iter().enumerate() {
+            index.insert(Rect::from_center_size(*pos, Vec2::ZERO), Entry::Port(i));
+        }
+
+        for (i, (poly, _)) in edges.iter().enumerate() {
+            for (j, segment) in poly.windows(2).enumerate() {
+                index.insert(
+                    Rect::from_two_pos(segment[0], segment[1]),
+                    Entry::Segment(i, j),
+                );
+            }
+        }
+
+        index.rects = rects;
+        index.ports = ports;
+        index.edges = edges;
+
+        index
+    }
+
+    /// The buckets that `rect` touches.
+    fn cells(&self, rect: Rect) -> impl Iterator<Item = (i32, i32)> + use<> {
+        let cell = |v: f32| (v / self.bucket).floor() as i32;
+
+        let (left, right) = (cell(rect.min.x), cell(rect.max.x));
+        let (top, bottom) = (cell(rect.min.y), cell(rect.max.y));
+
+        (left..=right).flat_map(move |x| (top..=bottom).map(move |y| (x, y)))
+    }
+
+    fn insert(&mut self, rect: Rect, entry: Entry) {
+        for cell in self.cells(rect) {
+            self.buckets.entry(cell).or_default().push(entry);
+        }
+    }
+
+    /// Everything in the buckets that `area` touches, once each.
+    fn candidates(&self, area: Rect) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .cells(area)
+            .filter_map(|cell| self.buckets.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+
+        entries.sort_unstable();
+        entries.dedup();
+
+        entries
+    }
+
+    /// Every block that touches `area`.
+    pub(super) fn nodes_in(&self, area: Rect) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.blocks_in(area).map(|(node, _)| node)
+    }
+
+    /// Every block that touches `area`, along with its rect.
+    pub(super) fn blocks_in(&self, area: Rect) -> impl Iterator<Item = (NodeIndex, Rect)> + '_ {
+        self.candidates(area)
+            .into_iter()
+            .filter_map(move |entry| match entry {
+                Entry::Node(i) if self.rects[i].1.intersects(area) => Some(self.rects[i]),
+                _ => None,
+            })
+    }
+
+    /// The port whose area `pos` is in, the closest one if there are several.
+    fn port_at(&self, pos: Pos2) -> Option<PortSlot> {
+        let area = Rect::from_center_size(pos, Vec2::splat(self.port_size));
+
+        self.candidates(area)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Port(i) if area.contains(self.ports[i].1) => Some(self.ports[i]),
+                _ => None,
+            })
+            .min_by(|a, b| a.1.distance(pos).total_cmp(&b.1.distance(pos)))
+            .map(|(slot, _)| slot)
+    }
+
+    fn node_at(&self, pos: Pos2) -> Option<NodeIndex> {
+        self.candidates(Rect::from_center_size(pos, Vec2::ZERO))
+            .into_iter()
+            .find_map(|entry| match entry {
+                Entry::Node(i) if self.rects[i].1.contains(pos) => Some(self.rects[i].0),
+                _ => None,
+            })
+    }
+
+    /// The edge closest to `pos`, if it's within [`Self::edge_tolerance`] of it.
+    pub(super) fn edge_at(&self, pos: Pos2) -> Option<&PortLine> {
+        let area = Rect::from_center_size(pos, Vec2::splat(self.edge_tolerance * 2.0));
+
+        self.candidates(area)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Segment(i, j) => {
+                    let (poly, line) = &self.edges[i];
+                    Some((distance_to_polyline(pos, &poly[j..j + 2]), line))
+                }
+                _ => None,
+            })
+            .filter(|(distance, _)| *distance <= self.edge_tolerance)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, line)| line)
+    }
+
+    /// What is at `pos`, where the ports are on top of the blocks and the blocks are on top
+    /// of the edges.
+    pub(super) fn hit(&self, pos: Pos2) -> Hit {
+        if let Some(slot) = self.port_at(pos) {
+            Hit::Port(slot)
+        } else if let Some(node) = self.node_at(pos) {
+            Hit::Node(node)
+        } else if let Some(line) = self.edge_at(pos) {
+            Hit::Edge(line.clone())
+        } else {
+            Hit::Background
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use egui::{Context, Id, Mesh, Pos2, Rect, Vec2};
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+
+use super::galleys::GalleyCache;
+use super::spatial::{Hit, SpatialIndex};
+use super::{Animation, FlashStyle, PortLine, PortSlot};
+use crate::CfgFingerprint;
+use crate::route::{CostField, RouteConfig};
+
+/// Text selected by dragging over the body of a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct TextSelection {
+    pub(super) node: NodeIndex,
+    /// The char index in the body where the drag started.
+    pub(super) anchor: usize,
+    /// The char index in the body where the drag is now.
+    pub(super) cursor: usize,
+}
+
+impl TextSelection {
+    /// The selected chars, no matter which way the drag went.
+    pub(super) fn range(&self) -> Range<usize> {
+        self.anchor.min(self.cursor)..self.anchor.max(self.cursor)
+    }
+}
+
+/// Goes up for every [`CfgViewState`], so that each one gets its own id.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// How much [`CfgViewState::zoom_in`] and [`CfgViewState::zoom_out`] change the zoom by.
+const ZOOM_STEP: f32 = 1.25;
+
+/// A rubber band being dragged over the background to select blocks.
+#[derive(Clone, Debug)]
+pub(super) struct BoxSelection {
+    /// Where the drag started, in scene coordinates.
+    pub(super) origin: Pos2,
+    /// The blocks that were selected before the drag, which stay selected.
+    pub(super) base: HashSet<NodeIndex>,
+}
+
+/// Every block that can reach a block, or can be reached from it.
+#[derive(Clone, Debug)]
+pub(super) struct Slice {
+    pub(super) node: NodeIndex,
+    /// [`Direction::Incoming`] for the predecessors, [`Direction::Outgoing`] for the
+    /// successors.
+    pub(super) direction: Direction,
+    /// The fingerprint of the graph that `nodes` was computed for.
+    pub(super) fingerprint: Option<CfgFingerprint>,
+    pub(super) nodes: HashSet<NodeIndex>,
+}
+
+/// A block pulsing to draw attention to it.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Flash {
+    pub(super) style: FlashStyle,
+    /// When the block was first drawn flashing.
+    pub(super) start: Option<f64>,
+}
+
+/// The view zooming to a block while the [`super::CfgView::peek_zoom`] key is held.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Peek {
+    /// The scene rect from before the peek, which it goes back to.
+    pub(super) restore: Rect,
+    pub(super) from: Rect,
+    pub(super) to: Rect,
+    /// When the transition from `from` to `to` started.
+    pub(super) start: f64,
+    /// Whether the transition is going back to `restore`.
+    pub(super) returning: bool,
+    /// The fingerprint of the graph when the peek started.
+    pub(super) fingerprint: CfgFingerprint,
+}
+
+/// How long the pointer has been over a block, for [`super::HoverConfig`].
+#[derive(Clone, Copy, Debug)]
+pub(super) struct HoverTrack {
+    pub(super) node: NodeIndex,
+    /// When the pointer got onto the block.
+    pub(super) since: f64,
+    /// When the pointer was last seen on the block.
+    pub(super) seen: f64,
+}
+
+/// The ports and the lines between them, along with the geometry they were assigned for.
+#[derive(Clone, Debug)]
+pub(super) struct PortCache {
+    pub(super) fingerprint: CfgFingerprint,
+    /// Every block and stub rect, sorted by node.
+    pub(super) rects: Vec<(NodeIndex, Rect)>,
+    /// Every stub and the block it goes out of.
+    pub(super) stubs: Vec<(NodeIndex, NodeIndex)>,
+    /// The bounds of every rect in `rects`.
+    pub(super) world_rect: Rect,
+    /// How far the ports are from their blocks.
+    pub(super) offset: f32,
+    pub(super) positions: Arc<HashMap<PortSlot, Pos2>>,
+    pub(super) lines: Arc<Vec<PortLine>>,
+    /// The line that starts or ends at every port.
+    pub(super) lines_by_port: Arc<HashMap<PortSlot, PortLine>>,
+}
+
+/// The edges that aren't highlighted, tessellated into a single mesh, along with the lines
+/// it was made of and a hash of everything else that went into it.
+#[derive(Clone, Debug)]
+pub(super) struct EdgeMesh {
+    pub(super) lines: Arc<[(Vec<Pos2>, PortLine)]>,
+    pub(super) key: u64,
+    pub(super) mesh: Arc<Mesh>,
+}
+
+/// The cost field edges were last routed over, along with what it was built for.
+#[derive(Clone, Debug)]
+pub(super) struct FieldCache {
+    pub(super) scene: Rect,
+    pub(super) config: RouteConfig,
+    /// Every block and stub rect that is an obstacle in the field, sorted by node.
+    pub(super) rects: Vec<(NodeIndex, Rect)>,
+    pub(super) field: CostField,
+}
+
+/// The edges that are still being routed over several frames, see
+/// [`super::CfgView::routing_budget`].
+#[derive(Clone, Debug)]
+pub(super) struct PendingRoutes {
+    pub(super) fingerprint: CfgFingerprint,
+    /// Every block and stub rect the edges are routed around, sorted by node.
+    pub(super) rects: Vec<(NodeIndex, Rect)>,
+    /// The edges that are left, along with the bounds of where they go, with the one to
+    /// route next at the end.
+    pub(super) queue: Vec<(PortLine, Rect)>,
+    /// The visible rect the queue was sorted for.
+    pub(super) sorted_for: Rect,
+    pub(super) routed: Vec<(Vec<Pos2>, PortLine)>,
+    /// Where every edge was routed before, which the new routes stick to and which the
+    /// edges that are left are drawn along until they're routed.
+    pub(super) previous: HashMap<PortLine, Vec<Pos2>>,
+}
+
+impl PendingRoutes {
+    /// Sort the queue again if the view moved or zoomed by more than a quarter of it since
+    /// it was last sorted, so that the edges on the screen get routed first.
+    pub(super) fn prioritize(&mut self, visible: Rect) {
+        let moved = (visible.center() - self.sorted_for.center()).abs();
+        let quarter = self.sorted_for.size() / 4.0;
+        let zoomed = visible.area() / self.sorted_for.area().max(f32::EPSILON);
+
+        if moved.x <= quarter.x && moved.y <= quarter.y && (0.5..=2.0).contains(&zoomed) {
+            return;
+        }
+
+        self.sort(visible);
+    }
+
+    /// The edges that were routed so far, along with where the rest was routed before, for
+    /// starting over once something moved.
+    pub(super) fn seeds(&self) -> Vec<(Vec<Pos2>, PortLine)> {
+        let left = self
+            .queue
+            .iter()
+            .filter_map(|(pl, _)| Some((self.previous.get(pl)?.clone(), pl.clone())));
+
+        self.routed.iter().cloned().chain(left).collect()
+    }
+
+    pub(super) fn sort(&mut self, visible: Rect) {
+        let center = visible.center();
+
+        // the edges on the screen come first, then the rest by how close they are to it.
+        self.queue.sort_by_cached_key(|(_, bounds)| {
+            let distance = bounds.distance_to_pos(center);
+            std::cmp::Reverse((!bounds.intersects(visible), distance.to_bits()))
+        });
+
+        self.sorted_for = visible;
+    }
+}
+
+/// The animations that are running this frame, and when the soonest of them needs a repaint.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Animations {
+    pub(super) running: Vec<Animation>,
+    /// In how many seconds the view has to be repainted, if any animation asked for it.
+    pub(super) repaint_after: Option<f32>,
+}
+
+impl Animations {
+    /// Keep `animation` running, with a repaint in `after` seconds unless something else
+    /// already repaints for it.
+    pub(super) fn run(&mut self, animation: Animation, after: Option<f32>) {
+        if !self.running.contains(&animation) {
+            self.running.push(animation);
+        }
+
+        if let Some(after) = after {
+            let soonest = self
+                .repaint_after
+                .map_or(after, |soonest| soonest.min(after));
+            self.repaint_after = Some(soonest.max(0.0));
+        }
+    }
+}
+
+/// The state of a [`super::CfgView`] that has to outlive a single frame.
+///
+/// The view itself is rebuilt every frame, so this is owned by the app and handed to it.
+#[derive(Clone, Debug)]
+pub struct CfgViewState {
+    /// The part of the graph that is currently visible.
+    pub scene_rect: Rect,
+    /// The block that was clicked last, if any.
+    pub selected: Option<NodeIndex>,
+    /// The blocks that were selected together, by dragging a rubber band around them.
+    pub multi_selection: HashSet<NodeIndex>,
+    /// The edge whose port was clicked last, if any.
+    pub selected_edge: Option<PortLine>,
+    /// The block that was hovered in the last frame, if any.
+    pub(super) hovered: Option<NodeIndex>,
+    /// Whether blocks are collapsed, unless they're in `toggled`.
+    collapsed_by_default: bool,
+    /// The blocks that were toggled since the last [`Self::collapse_all`] or [`Self::expand_all`].
+    toggled: HashSet<NodeIndex>,
+    /// Where the center of every dragged block is, wherever the layout puts it.
+    pinned: HashMap<NodeIndex, Pos2>,
+    /// The block that is being dragged by its header, if any.
+    pub(super) dragging: Option<NodeIndex>,
+    pub(super) galleys: GalleyCache,
+    pub(super) text_selection: Option<TextSelection>,
+    pub(super) box_selection: Option<BoxSelection>,
+    pub(super) slice: Option<Slice>,
+    /// The successor or predecessor of the selected block that Tab cycled to, which gets
+    /// selected on Enter.
+    pub(super) candidate: Option<NodeIndex>,
+    /// The block to center the view on, once it has been drawn.
+    pub(super) center_on: Option<NodeIndex>,
+    /// The blocks that are flashing.
+    pub(super) flashes: HashMap<NodeIndex, Flash>,
+    /// The zoom the view should change to the next time it gets shown.
+    pub(super) zoom_to: Option<f32>,
+    /// The zoom the view was shown at last.
+    pub(super) zoom: f32,
+    /// Whether the view should fit the whole graph the next time it gets shown.
+    pub(super) fit: bool,
+    /// The part of the scene that was visible last frame.
+    pub(super) visible_rect: Option<Rect>,
+    /// Where the background context menu was opened, while it's open.
+    pub(super) context_menu_pos: Option<Pos2>,
+    /// The search query whose highlights were cleared with Escape.
+    pub(super) dismissed_search: Option<String>,
+    pub(super) peek: Option<Peek>,
+    pub(super) hover_track: Option<HoverTrack>,
+    pub(super) animations: Animations,
+    pub(super) ports: Option<PortCache>,
+    pub(super) edge_mesh: Option<EdgeMesh>,
+    pub(super) route_field: Option<FieldCache>,
+    pub(super) routing: Option<PendingRoutes>,
+    /// The blocks, ports and edges as they were drawn last, for [`Self::hit_test`].
+    pub(super) spatial: Option<SpatialIndex>,
+    /// Everything the view stored in the memory of the context, see [`Self::clear_caches`].
+    pub(super) cache_ids: HashSet<Id>,
+    /// The fingerprint of the graph the last time it was shown.
+    pub(super) fingerprint: Option<CfgFingerprint>,
+    /// Goes up with every [`Self::mark_dirty`], which changes the fingerprint.
+    pub(super) generation: u64,
+    /// What the ids of the view are made from, unless [`super::CfgView::id_salt`] is set.
+    pub(super) id: Id,
+}
+
+impl CfgViewState {
+    pub fn new(scene_rect: Rect) -> Self {
+        Self {
+            scene_rect,
+            selected: None,
+            multi_selection: HashSet::new(),
+            selected_edge: None,
+            hovered: None,
+            collapsed_by_default: false,
+            toggled: HashSet::new(),
+            pinned: HashMap::new(),
+            dragging: None,
+            galleys: GalleyCache::default(),
+            text_selection: None,
+            box_selection: None,
+            slice: None,
+            candidate: None,
+            center_on: None,
+            flashes: HashMap::new(),
+            zoom_to: None,
+            zoom: 1.0,
+            fit: false,
+            visible_rect: None,
+            context_menu_pos: None,
+            dismissed_search: None,
+            peek: None,
+            hover_track: None,
+            animations: Animations::default(),
+            ports: None,
+            edge_mesh: None,
+            route_field: None,
+            routing: None,
+            spatial: None,
+            cache_ids: HashSet::new(),
+            fingerprint: None,
+            generation: 0,
+            id: Id::new(("cfg_view", NEXT_ID.fetch_add(1, Ordering::Relaxed))),
+        }
+    }
+
+    /// Whether `node` is the [`Self::selected`] block, or in the [`Self::multi_selection`].
+    pub fn is_selected(&self, node: NodeIndex) -> bool {
+        self.selected == Some(node) || self.multi_selection.contains(&node)
+    }
+
+    /// Center the view on `node` the next time it gets shown, without changing the zoom.
+    pub fn center_on(&mut self, node: NodeIndex) {
+        self.center_on = Some(node);
+    }
+
+    /// Zoom the view to `zoom` the next time it gets shown, keeping the same center.
+    ///
+    /// 1.0 is 100%, and the zoom is always kept within [`super::CfgView::zoom_range`].
+    pub fn zoom_to(&mut self, zoom: f32) {
+        self.zoom_to = Some(zoom);
+    }
+
+    /// The zoom the view was shown at last, where 1.0 is 100%.
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Zoom in by one step about the center of the view.
+    pub fn zoom_in(&mut self) {
+        self.zoom_to(self.zoom_to.unwrap_or(self.zoom) * ZOOM_STEP);
+    }
+
+    /// Zoom out by one step about the center of the view.
+    pub fn zoom_out(&mut self) {
+        self.zoom_to(self.zoom_to.unwrap_or(self.zoom) / ZOOM_STEP);
+    }
+
+    /// Go back to 100%.
+    pub fn reset_zoom(&mut self) {
+        self.zoom_to(1.0);
+    }
+
+    /// Pan and zoom so that the whole graph fits, the next time the view gets shown.
+    pub fn fit(&mut self) {
+        self.fit = true;
+        self.zoom_to = None;
+    }
+
+    /// Stop highlighting the predecessors or successors that were Alt+clicked.
+    pub fn clear_slice(&mut self) {
+        self.slice = None;
+    }
+
+    /// Make the glow around `node` pulse, whether it's selected or not.
+    ///
+    /// Flashing it again starts over, and nothing happens if it isn't in the graph.
+    pub fn flash(&mut self, node: NodeIndex, style: FlashStyle) {
+        self.flashes.insert(node, Flash { style, start: None });
+    }
+
+    /// The animations that were running the last time the view was shown.
+    ///
+    /// The view only asks for repaints while this isn't empty, so a graph that nothing
+    /// happens to settles and stops being repainted.
+    pub fn animations(&self) -> &[Animation] {
+        &self.animations.running
+    }
+
+    /// The fingerprint of the graph the last time it was shown, which everything that is
+    /// cached about it is kept for.
+    pub fn fingerprint(&self) -> Option<CfgFingerprint> {
+        self.fingerprint
+    }
+
+    /// The bounds of every block and external target stub, as they were drawn last.
+    ///
+    /// This is only computed again when something moved, or the graph changed.
+    pub fn world_rect(&self) -> Option<Rect> {
+        self.ports.as_ref().map(|ports| ports.world_rect)
+    }
+
+    /// The rect of every block as it was drawn last, sorted by node.
+    pub fn drawn_blocks(&self) -> &[(NodeIndex, Rect)] {
+        self.spatial.as_ref().map_or(&[], |index| &index.rects)
+    }
+
+    /// Every edge as it was drawn last, along the points of its path.
+    pub fn drawn_edges(&self) -> &[(Vec<Pos2>, PortLine)] {
+        self.spatial.as_ref().map_or(&[], |index| &index.edges)
+    }
+
+    /// What is at `pos` in the scene, in the same coordinates as [`Self::scene_rect`], as
+    /// it was drawn last.
+    ///
+    /// Ports are on top of blocks, which are on top of edges, and edges get a few points on
+    /// the screen around them to hit, the same as when clicking them in the view.
+    pub fn hit_test(&self, pos: Pos2) -> Hit {
+        self.spatial
+            .as_ref()
+            .map_or(Hit::Background, |index| index.hit(pos))
+    }
+
+    /// Throw away everything cached about the graph, e.g. because a block was changed in a
+    /// way that its [`crate::BlockLike::content_hash`] doesn't catch.
+    pub fn mark_dirty(&mut self) {
+        self.generation += 1;
+        self.galleys = GalleyCache::default();
+    }
+
+    /// Drop everything that is cached for the view, both here and in the memory of `ctx`,
+    /// e.g. to free it all once the view is closed.
+    ///
+    /// Nothing else changes, and the caches are just built again when the view is shown.
+    pub fn clear_caches(&mut self, ctx: &Context) {
+        self.galleys = GalleyCache::default();
+        self.ports = None;
+        self.edge_mesh = None;
+        self.route_field = None;
+        self.routing = None;
+        self.spatial = None;
+
+        ctx.data_mut(|d| {
+            for id in self.cache_ids.drain() {
+                super::evict_cache(d, id);
+            }
+        });
+    }
+
+    /// Whether `node` is only drawn as its header.
+    pub fn is_collapsed(&self, node: NodeIndex) -> bool {
+        self.collapsed_by_default != self.toggled.contains(&node)
+    }
+
+    pub fn set_collapsed(&mut self, node: NodeIndex, collapsed: bool) {
+        if self.is_collapsed(node) != collapsed {
+            self.toggle_collapsed(node);
+        }
+    }
+
+    pub fn toggle_collapsed(&mut self, node: NodeIndex) {
+        if !self.toggled.remove(&node) {
+            self.toggled.insert(node);
+        }
+    }
+
+    /// Collapse every block, including the ones that get added to the graph later.
+    pub fn collapse_all(&mut self) {
+        self.collapsed_by_default = true;
+        self.toggled.clear();
+    }
+
+    /// Expand every block, including the ones that get added to the graph later.
+    pub fn expand_all(&mut self) {
+        self.collapsed_by_default = false;
+        self.toggled.clear();
+    }
+
+    /// The center of every block that was dragged, in the same coordinates as
+    /// [`Self::scene_rect`].
+    ///
+    /// These stay where they are when the graph gets laid out again, so they can be saved
+    /// and restored with [`Self::pin`].
+    pub fn pinned_positions(&self) -> &HashMap<NodeIndex, Pos2> {
+        &self.pinned
+    }
+
+    /// Keep the center of `node` at `pos`, wherever the layout puts it.
+    pub fn pin(&mut self, node: NodeIndex, pos: Pos2) {
+        self.pinned.insert(node, pos);
+    }
+
+    /// Whether `node` has a pinned position, because it was dragged or pinned.
+    pub fn is_pinned(&self, node: NodeIndex) -> bool {
+        self.pinned.contains_key(&node)
+    }
+
+    /// Pin `node` where it was drawn last, or put it back where the layout puts it.
+    ///
+    /// A block that wasn't drawn yet has nowhere to be pinned, so it isn't.
+    pub fn set_pinned(&mut self, node: NodeIndex, pinned: bool) {
+        if pinned {
+            let drawn = self.drawn_blocks().iter().find(|(n, _)| *n == node);
+
+            if let Some(&(_, rect)) = drawn {
+                self.pinned.entry(node).or_insert(rect.center());
+            }
+        } else {
+            self.pinned.remove(&node);
+        }
+    }
+
+    /// Put every dragged block back where the layout puts it.
+    pub fn reset_positions(&mut self) {
+        self.pinned.clear();
+        self.dragging = None;
+    }
+
+    /// The pinned center of `node`, where a position that isn't finite counts as none at all.
+    pub(super) fn pinned_pos(&self, node: NodeIndex) -> Option<Pos2> {
+        self.pinned
+            .get(&node)
+            .copied()
+            .filter(|pos| pos.is_finite())
+    }
+
+    /// Move `node` by `delta` from `center`, or from where it was pinned.
+    pub(super) fn move_by(&mut self, node: NodeIndex, center: Pos2, delta: Vec2) {
+        if delta.is_finite() && center.is_finite() {
+            *self.pinned.entry(node).or_insert(center) += delta;
+        }
+    }
+
+    /// Round the pinned center of `node` to a multiple of `grid`.
+    pub(super) fn snap(&mut self, node: NodeIndex, grid: f32) {
+        if let Some(pos) = self.pinned.get_mut(&node) {
+            *pos = (*pos / grid).round() * grid;
+        }
+    }
+
+    /// Drop everything about the blocks that `keep` returns false for, e.g. because they
+    /// were removed from the graph.
+    ///
+    /// This returns whether the selection had to be dropped, and whether anything else did.
+    pub(super) fn retain_nodes(&mut self, keep: impl Fn(NodeIndex) -> bool) -> (bool, bool) {
+        let mut selection = false;
+
+        if self.selected.is_some_and(|n| !keep(n)) {
+            self.selected = None;
+            selection = true;
+        }
+
+        let count = self.multi_selection.len();
+        self.multi_selection.retain(|n| keep(*n));
+        selection |= self.multi_selection.len() != count;
+
+        let counts = (self.pinned.len(), self.toggled.len(), self.flashes.len());
+
+        self.pinned.retain(|n, _| keep(*n));
+        self.toggled.retain(|n| keep(*n));
+        self.flashes.retain(|n, _| keep(*n));
+
+        let mut other = counts != (self.pinned.len(), self.toggled.len(), self.flashes.len());
+
+        // an edge can go to a stub, which isn't in the graph, but it always comes from a block.
+        let mut drop = |dropped: bool| {
+            other |= dropped;
+            dropped
+        };
+
+        if drop(
+            self.selected_edge
+                .as_ref()
+                .is_some_and(|e| !keep(e.from.node)),
+        ) {
+            self.selected_edge = None;
+        }
+
+        if drop(self.candidate.is_some_and(|n| !keep(n))) {
+            self.candidate = None;
+        }
+
+        if drop(self.slice.as_ref().is_some_and(|s| !keep(s.node))) {
+            self.slice = None;
+        }
+
+        if drop(self.text_selection.is_some_and(|s| !keep(s.node))) {
+            self.text_selection = None;
+        }
+
+        if drop(self.dragging.is_some_and(|n| !keep(n))) {
+            self.dragging = None;
+        }
+
+        if self.hovered.is_some_and(|n| !keep(n)) {
+            self.hovered = None;
+        }
+
+        if self.center_on.is_some_and(|n| !keep(n)) {
+            self.center_on = None;
+        }
+
+        if self.hover_track.is_some_and(|t| !keep(t.node)) {
+            self.hover_track = None;
+        }
+
+        self.galleys.retain(&keep);
+
+        (selection, other)
+    }
+
+    /// How many body galleys had to be laid out so far, e.g. because a block was new or
+    /// its content changed.
+    ///
+    /// Every other frame reuses the galleys from before, so a static graph stops adding
+    /// to this after the first frame.
+    pub fn galley_layouts(&self) -> usize {
+        self.galleys.layouts()
+    }
+}
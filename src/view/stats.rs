@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use egui::{Context, Grid, Ui, Window};
+
+/// How long every phase of a single [`super::CfgView::show`] took, and how much of it was
+/// cached, see [`super::CfgView::collect_stats`].
+#[derive(Clone, Debug, Default)]
+pub struct CfgStats {
+    /// Measuring the blocks, which lays out the body text of those that aren't cached.
+    pub text: Duration,
+    pub layout: Duration,
+    /// Drawing the blocks, along with handling their interaction.
+    pub blocks: Duration,
+    /// Assigning the ports and the lines between them.
+    pub ports: Duration,
+    /// Building the cost field that edges get routed over.
+    pub field: Duration,
+    pub routing: Duration,
+    /// Drawing the edges, whether they were routed this frame or not.
+    pub edges: Duration,
+    pub nodes_drawn: usize,
+    /// How many edges were drawn.
+    pub edges_drawn: usize,
+    /// How many shapes the edges were painted with, which is far less than
+    /// [`Self::edges_drawn`], since only the highlighted edges get shapes of their own.
+    pub edge_shapes: usize,
+    /// How many edges were routed, which is zero when the routed edges were cached.
+    pub edges_routed: usize,
+    /// How many of the routed edges had no path, and were drawn straight between their
+    /// ports instead.
+    pub edges_failed: usize,
+    /// How many body galleys had to be laid out, because they weren't cached.
+    pub galleys_laid_out: usize,
+    /// Whether the ports from the last frame were reused.
+    pub ports_cached: bool,
+    /// Whether the routed edges from the last frame were reused.
+    pub edges_cached: bool,
+    /// Whether the cost field from the last routing was reused, when edges were routed.
+    pub field_reused: bool,
+}
+
+impl CfgStats {
+    /// The time of every phase together.
+    pub fn total(&self) -> Duration {
+        self.text + self.layout + self.blocks + self.ports + self.field + self.routing + self.edges
+    }
+}
+
+/// A window showing the [`CfgStats`] of a view, for profiling it.
+pub struct CfgStatsWindow<'a> {
+    title: &'a str,
+    stats: &'a CfgStats,
+}
+
+impl<'a> CfgStatsWindow<'a> {
+    pub fn new(stats: &'a CfgStats) -> Self {
+        Self {
+            title: "CFG stats",
+            stats,
+        }
+    }
+
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = title;
+        self
+    }
+
+    pub fn show(self, ctx: &Context) {
+        Window::new(self.title)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui));
+    }
+
+    fn contents(&self, ui: &mut Ui) {
+        let stats = self.stats;
+
+        let millis = |duration: Duration| format!("{:.2} ms", duration.as_secs_f64() * 1000.0);
+        let cached = |cached: bool| if cached { "cached" } else { "rebuilt" };
+
+        Grid::new("cfg_stats")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                let rows = [
+                    ("text", millis(stats.text)),
+                    ("layout", millis(stats.layout)),
+                    ("blocks", millis(stats.blocks)),
+                    ("ports", millis(stats.ports)),
+                    ("field", millis(stats.field)),
+                    ("routing", millis(stats.routing)),
+                    ("edges", millis(stats.edges)),
+                    ("total", millis(stats.total())),
+                    ("nodes drawn", stats.nodes_drawn.to_string()),
+                    ("edges drawn", stats.edges_drawn.to_string()),
+                    ("edge shapes", stats.edge_shapes.to_string()),
+                    ("edges routed", stats.edges_routed.to_string()),
+                    ("edges failed", stats.edges_failed.to_string()),
+                    ("galleys laid out", stats.galleys_laid_out.to_string()),
+                    ("port cache", cached(stats.ports_cached).to_owned()),
+                    ("edge cache", cached(stats.edges_cached).to_owned()),
+                    ("field cache", cached(stats.field_reused).to_owned()),
+                ];
+
+                for (name, value) in rows {
+                    ui.label(name);
+                    ui.monospace(value);
+                    ui.end_row();
+                }
+            });
+    }
+}
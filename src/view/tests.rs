@@ -0,0 +1,1345 @@
+use egui::epaint::{ClippedShape, ColorMode, Shape};
+use egui::{FullOutput, RawInput};
+
+use super::*;
+use crate::style::BlockWidth;
+
+#[derive(Clone)]
+struct Block {
+    title: String,
+    lines: Vec<String>,
+}
+
+impl BlockLike for Block {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn body_lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+fn block(title: &str, lines: &[&str]) -> Block {
+    Block {
+        title: title.into(),
+        lines: lines.iter().map(|line| line.to_string()).collect(),
+    }
+}
+
+/// A diamond with a loop on the left side.
+fn graph() -> StableGraph<Block, EdgeKind> {
+    let mut graph = StableGraph::new();
+
+    let entry = graph.add_node(block("entry", &["cmp rax, 1", "jne 0x20"]));
+    let left = graph.add_node(block("left", &["add rax, 1", "jmp 0x30"]));
+    let right = graph.add_node(block("right", &["sub rax, 1"]));
+    let exit = graph.add_node(block("exit", &["ret"]));
+
+    graph.add_edge(entry, left, EdgeKind::Taken);
+    graph.add_edge(entry, right, EdgeKind::FallThrough);
+    graph.add_edge(left, exit, EdgeKind::Unconditional);
+    graph.add_edge(right, exit, EdgeKind::Unconditional);
+    graph.add_edge(left, left, EdgeKind::Taken);
+
+    graph
+}
+
+/// A headless egui context, along with the state of the view that is shown in it.
+struct Harness {
+    ctx: egui::Context,
+    state: CfgViewState,
+    frame: u32,
+}
+
+impl Harness {
+    fn new() -> Self {
+        Self {
+            ctx: egui::Context::default(),
+            state: CfgViewState::new(Rect::from_min_size(
+                pos2(-500.0, -500.0),
+                vec2(1000.0, 1000.0),
+            )),
+            frame: 0,
+        }
+    }
+
+    /// Run one frame with `show` in the central panel.
+    fn run(&mut self, mut show: impl FnMut(&mut Ui, &mut CfgViewState)) -> FullOutput {
+        let input = frame_input(self.frame, Vec::new());
+
+        self.frame += 1;
+
+        let state = &mut self.state;
+
+        self.ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| show(ui, state));
+        })
+    }
+
+    /// Show `graph` for one frame, with the default layout.
+    fn show(
+        &mut self,
+        graph: &StableGraph<Block, EdgeKind>,
+        style: &NodeStyle,
+    ) -> (CfgResponse, FullOutput) {
+        let mut response = None;
+
+        let output = self.run(|ui, state| {
+            response =
+                Some(CfgView::new(graph.clone(), LayoutConfig::default(), state, style).show(ui));
+        });
+
+        (response.expect("the view was shown"), output)
+    }
+}
+
+/// The input of the `frame`th frame, with `events` happening in it.
+fn frame_input(frame: u32, events: Vec<egui::Event>) -> RawInput {
+    RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(1024.0, 768.0))),
+        time: Some(frame as f64 / 60.0),
+        events,
+        ..Default::default()
+    }
+}
+
+/// Every shape that was painted, with the clip rect it was painted with.
+fn shapes(output: &FullOutput) -> Vec<(Rect, &Shape)> {
+    fn flatten<'a>(clip: Rect, shape: &'a Shape, shapes: &mut Vec<(Rect, &'a Shape)>) {
+        match shape {
+            Shape::Vec(inner) => inner.iter().for_each(|shape| flatten(clip, shape, shapes)),
+            shape => shapes.push((clip, shape)),
+        }
+    }
+
+    let mut shapes = Vec::new();
+
+    for ClippedShape { clip_rect, shape } in &output.shapes {
+        flatten(*clip_rect, shape, &mut shapes);
+    }
+
+    shapes
+}
+
+#[test]
+fn long_tokens_stay_within_the_block() {
+    let style = NodeStyle::default();
+    let token = "A".repeat(400);
+
+    let mut graph: StableGraph<Block, EdgeKind> = StableGraph::new();
+    let node = graph.add_node(block(&"T".repeat(200), &[&token, "ret"]));
+
+    // a size that is way off, like one measured before the fonts were loaded.
+    let sizes = HashMap::from([(node, vec2(80.0, 40.0))]);
+
+    let mut harness = Harness::new();
+    let mut output = None;
+
+    for _ in 0..3 {
+        output = Some(harness.run(|ui, state| {
+            CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                .measured_sizes(&sizes)
+                .show(ui);
+        }));
+    }
+
+    let output = output.unwrap();
+    let shapes = shapes(&output);
+
+    // the body is the only rect painted with the fill, on the screen after the scene moved it.
+    let block_rect = shapes
+        .iter()
+        .find_map(|(_, shape)| match shape {
+            Shape::Rect(rect) if rect.fill == style.fill => Some(rect.rect),
+            _ => None,
+        })
+        .expect("the block was painted");
+
+    let text: Vec<Rect> = shapes
+        .iter()
+        .filter(|(_, shape)| matches!(shape, Shape::Text(_)))
+        .map(|(clip, shape)| shape.visual_bounding_rect().intersect(*clip))
+        .filter(|rect| rect.is_positive())
+        .collect();
+
+    assert!(!text.is_empty(), "no text was painted");
+
+    for rect in text {
+        assert!(
+            block_rect.expand(0.5).contains_rect(rect),
+            "text painted at {rect:?} outside of the block at {block_rect:?}"
+        );
+    }
+}
+
+#[test]
+fn long_titles_fit_before_being_elided() {
+    let style = NodeStyle {
+        width: BlockWidth::FitContent {
+            min_width: 80.0,
+            max_width: 400.0,
+        },
+        ..Default::default()
+    };
+
+    let title = "a_rather_long_function_name_for_one_ret";
+
+    let mut graph: StableGraph<Block, EdgeKind> = StableGraph::new();
+    graph.add_node(block(title, &["ret"]));
+
+    let mut harness = Harness::new();
+    let mut output = None;
+
+    for _ in 0..3 {
+        output = Some(harness.run(|ui, state| {
+            CfgView::new(graph.clone(), LayoutConfig::default(), state, &style).show(ui);
+        }));
+    }
+
+    let output = output.unwrap();
+
+    let galley = shapes(&output)
+        .into_iter()
+        .find_map(|(_, shape)| match shape {
+            Shape::Text(text) if text.galley.text().starts_with("a_rather") => {
+                Some(text.galley.clone())
+            }
+            _ => None,
+        })
+        .expect("the title was painted");
+
+    assert!(!galley.elided, "the title was elided");
+    assert_eq!(galley.text(), title);
+
+    let width = harness.state.drawn_blocks()[0].1.width();
+    assert!(width <= 400.0, "the block is {width} wide");
+}
+
+#[test]
+fn dragged_blocks_stay_put_when_the_graph_changes() {
+    let style = NodeStyle::default();
+    let mut graph = graph();
+    let (left, right) = (NodeIndex::new(1), NodeIndex::new(2));
+
+    let mut harness = Harness::new();
+    let Harness { ctx, state, frame } = &mut harness;
+
+    // shows `graph`, and returns where `node` was drawn on the screen and in the scene.
+    let mut run = |graph: &StableGraph<Block, EdgeKind>, node, events| {
+        let _ = ctx.run(frame_input(*frame, events), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                    .draggable_nodes(true)
+                    .show(ui);
+            });
+        });
+
+        *frame += 1;
+
+        let rect = state
+            .drawn_blocks()
+            .iter()
+            .find(|(n, _)| *n == node)
+            .map(|(_, rect)| *rect)
+            .expect("the block was drawn");
+
+        let transform = ctx.memory(|m| m.to_global.values().next().copied());
+
+        (transform.expect("the scene has a transform") * rect, rect)
+    };
+
+    let (screen, start) = run(&graph, right, Vec::new());
+    let (_, before) = run(&graph, left, Vec::new());
+
+    // grab the header of the block and drag it off to the side.
+    let grab = screen.left_top() + vec2(10.0, style.header_height * 0.5);
+    let button = |pos, pressed| egui::Event::PointerButton {
+        pos,
+        button: egui::PointerButton::Primary,
+        pressed,
+        modifiers: egui::Modifiers::NONE,
+    };
+
+    run(&graph, right, vec![egui::Event::PointerMoved(grab)]);
+    run(&graph, right, vec![button(grab, true)]);
+
+    for step in 1..=4 {
+        let pos = grab + vec2(20.0 * step as f32, 0.0);
+        run(&graph, right, vec![egui::Event::PointerMoved(pos)]);
+    }
+
+    let drop = grab + vec2(80.0, 0.0);
+    let (_, dropped) = run(&graph, right, vec![button(drop, false)]);
+
+    assert!(
+        dropped.center().x > start.center().x,
+        "the block wasn't dragged"
+    );
+
+    // a block in front of the entry moves everything the layout places down.
+    let first = graph.add_node(block("first", &["nop"]));
+    graph.add_edge(first, NodeIndex::new(0), EdgeKind::Unconditional);
+
+    run(&graph, right, Vec::new());
+    let (_, after) = run(&graph, left, Vec::new());
+    assert_ne!(before, after, "the layout didn't change");
+
+    let (_, pinned) = run(&graph, right, Vec::new());
+    assert_eq!(pinned, dropped);
+    assert!(state.is_pinned(right));
+}
+
+#[test]
+fn pinning_a_block_keeps_it_where_it_was_drawn() {
+    let style = NodeStyle::default();
+    let mut graph = graph();
+    let right = NodeIndex::new(2);
+
+    let mut harness = Harness::new();
+
+    let drawn = |harness: &mut Harness, graph: &StableGraph<Block, EdgeKind>| {
+        harness.show(graph, &style);
+
+        let blocks = harness.state.drawn_blocks();
+        blocks
+            .iter()
+            .find(|(n, _)| *n == right)
+            .map(|(_, rect)| *rect)
+    };
+
+    let before = drawn(&mut harness, &graph);
+
+    harness.state.set_pinned(right, true);
+    assert_eq!(
+        harness.state.pinned_positions()[&right],
+        before.unwrap().center()
+    );
+
+    // a block in front of the entry moves everything the layout places down.
+    let first = graph.add_node(block("first", &["nop"]));
+    graph.add_edge(first, NodeIndex::new(0), EdgeKind::Unconditional);
+
+    assert_eq!(drawn(&mut harness, &graph), before);
+
+    harness.state.set_pinned(right, false);
+    assert_ne!(drawn(&mut harness, &graph), before);
+}
+
+#[test]
+fn clicking_an_edge_keeps_the_selection() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let left = NodeIndex::new(1);
+
+    let mut harness = Harness::new();
+
+    // shows the graph with `events`, and returns the transform of the scene.
+    let run = |harness: &mut Harness, events: Vec<egui::Event>| {
+        let Harness { ctx, state, frame } = harness;
+
+        let _ = ctx.run(frame_input(*frame, events), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                CfgView::new(graph.clone(), LayoutConfig::default(), state, &style).show(ui);
+            });
+        });
+
+        *frame += 1;
+
+        ctx.memory(|m| m.to_global.values().next().copied())
+            .expect("the scene has a transform")
+    };
+
+    run(&mut harness, Vec::new());
+    let transform = run(&mut harness, Vec::new());
+
+    // the middle of the longest edge is far enough away from the blocks and ports.
+    let (poly, _) = harness
+        .state
+        .drawn_edges()
+        .iter()
+        .max_by(|(a, _), (b, _)| {
+            let length = |poly: &[Pos2]| poly.windows(2).map(|w| w[0].distance(w[1])).sum::<f32>();
+            length(a).total_cmp(&length(b))
+        })
+        .expect("the edges were drawn")
+        .clone();
+
+    let middle = poly[poly.len() / 2];
+    assert!(matches!(harness.state.hit_test(middle), Hit::Edge(_)));
+
+    let on_edge = transform * middle;
+
+    let click = |pos| {
+        [true, false].map(|pressed| egui::Event::PointerButton {
+            pos,
+            button: egui::PointerButton::Primary,
+            pressed,
+            modifiers: egui::Modifiers::NONE,
+        })
+    };
+
+    harness.state.selected = Some(left);
+
+    run(&mut harness, vec![egui::Event::PointerMoved(on_edge)]);
+    run(&mut harness, click(on_edge).into());
+    run(&mut harness, Vec::new());
+    assert_eq!(harness.state.selected, Some(left));
+
+    // the background around everything still clears it.
+    let world = harness.state.world_rect().expect("the blocks were drawn");
+    let background = transform * (world.left_top() - vec2(40.0, 40.0));
+
+    run(&mut harness, vec![egui::Event::PointerMoved(background)]);
+    run(&mut harness, click(background).into());
+    run(&mut harness, Vec::new());
+    assert_eq!(harness.state.selected, None);
+}
+
+#[test]
+fn removing_the_selected_block_clears_it() {
+    let style = NodeStyle::default();
+    let mut graph = graph();
+    let left = NodeIndex::new(1);
+
+    let mut harness = Harness::new();
+    harness.show(&graph, &style);
+
+    harness.state.selected = Some(left);
+    harness.state.pin(left, pos2(5.0, 5.0));
+    harness.state.flash(left, FlashStyle::default());
+
+    let (response, _) = harness.show(&graph, &style);
+    assert!(!response.selection_cleared && !response.state_dropped);
+
+    graph.remove_node(left);
+
+    let (response, _) = harness.show(&graph, &style);
+    assert!(response.selection_cleared && response.state_dropped);
+    assert_eq!(harness.state.selected, None);
+
+    // it's only reported once.
+    let (response, _) = harness.show(&graph, &style);
+    assert!(!response.selection_cleared && !response.state_dropped);
+}
+
+#[test]
+fn static_edges_are_one_retained_mesh() {
+    let style = NodeStyle::default();
+    let graph = graph();
+
+    let mut harness = Harness::new();
+    let mut stats = None;
+    let mut meshes = Vec::new();
+
+    for _ in 0..3 {
+        harness.run(|ui, state| {
+            let response = CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                .collect_stats(true)
+                .show(ui);
+
+            stats = response.stats;
+        });
+
+        meshes.push(Arc::clone(&harness.state.edge_mesh.as_ref().unwrap().mesh));
+    }
+
+    let stats = stats.unwrap();
+
+    assert_eq!(stats.edges_drawn, graph.edge_count());
+    assert_eq!(stats.edge_shapes, 1);
+    assert!(
+        Arc::ptr_eq(&meshes[1], &meshes[2]),
+        "the mesh was tessellated again"
+    );
+}
+
+#[test]
+fn only_geometry_changes_lay_out_again() {
+    let graph = graph();
+    let mut harness = Harness::new();
+
+    let frame = |harness: &mut Harness, style: &NodeStyle| {
+        let before = harness.state.galley_layouts();
+        let mut stats = None;
+
+        harness.run(|ui, state| {
+            stats = CfgView::new(graph.clone(), LayoutConfig::default(), state, style)
+                .collect_stats(true)
+                .show(ui)
+                .stats;
+        });
+
+        (harness.state.galley_layouts() - before, stats.unwrap())
+    };
+
+    let mut style = NodeStyle::default();
+
+    for _ in 0..2 {
+        frame(&mut harness, &style);
+    }
+
+    // a color only gets painted differently.
+    style.fill = Color32::RED;
+
+    let (laid_out, stats) = frame(&mut harness, &style);
+    assert_eq!(laid_out, 0);
+    assert!(stats.ports_cached && stats.edges_cached);
+
+    // a bigger font makes every block bigger.
+    style.text_font.size += 4.0;
+
+    let (laid_out, stats) = frame(&mut harness, &style);
+    assert_eq!(laid_out, graph.node_count());
+    assert!(!stats.ports_cached && !stats.edges_cached);
+}
+
+#[test]
+fn swapping_graphs_only_draws_the_new_edges() {
+    let style = NodeStyle::default();
+    let first = graph();
+
+    // as many blocks as the first one, so every index is reused, but other edges.
+    let mut second = StableGraph::new();
+    let nodes: Vec<_> = (0..4)
+        .map(|i| second.add_node(block(&format!("chain {i}"), &["nop"])))
+        .collect();
+
+    for pair in nodes.windows(2) {
+        second.add_edge(pair[0], pair[1], EdgeKind::Unconditional);
+    }
+
+    second.add_edge(nodes[0], nodes[3], EdgeKind::Taken);
+
+    let mut harness = Harness::new();
+
+    for _ in 0..3 {
+        harness.show(&first, &style);
+    }
+
+    for _ in 0..3 {
+        harness.show(&second, &style);
+    }
+
+    let mut drawn: Vec<_> = harness
+        .state
+        .drawn_edges()
+        .iter()
+        .map(|(_, line)| (line.from.node, line.to.node, line.edge))
+        .collect();
+
+    let mut edges: Vec<_> = second
+        .edge_indices()
+        .map(|edge| {
+            let (from, to) = second.edge_endpoints(edge).unwrap();
+            (from, to, Some(edge))
+        })
+        .collect();
+
+    drawn.sort();
+    edges.sort();
+
+    assert_eq!(drawn, edges);
+}
+
+#[test]
+fn empty_and_single_block_graphs_show() {
+    let style = NodeStyle::default();
+    let mut graph: StableGraph<Block, EdgeKind> = StableGraph::new();
+    let mut harness = Harness::new();
+
+    for _ in 0..2 {
+        harness.show(&graph, &style);
+    }
+
+    assert!(harness.state.drawn_blocks().is_empty());
+    assert!(harness.state.drawn_edges().is_empty());
+
+    let node = graph.add_node(block("only", &["ret"]));
+
+    for _ in 0..2 {
+        harness.show(&graph, &style);
+    }
+
+    let blocks = harness.state.drawn_blocks();
+
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].0, node);
+    assert!(blocks[0].1.is_finite() && blocks[0].1.is_positive());
+    assert!(harness.state.drawn_edges().is_empty());
+}
+
+#[test]
+fn views_next_to_each_other_glow_on_their_own() {
+    let style = NodeStyle::default();
+    let (first, mut second) = (graph(), graph());
+
+    // the same indices, but other blocks.
+    for node in second.node_indices().collect::<Vec<_>>() {
+        second[node].title = format!("other {}", node.index());
+    }
+
+    let scene = Rect::from_min_size(pos2(-500.0, -500.0), vec2(1000.0, 1000.0));
+    let mut harness = Harness::new();
+    let mut other = CfgViewState::new(scene);
+
+    let frame = |harness: &mut Harness, other: &mut CfgViewState| {
+        harness.run(|ui, state| {
+            ui.columns(2, |columns| {
+                CfgView::new(first.clone(), LayoutConfig::default(), state, &style)
+                    .show(&mut columns[0]);
+                CfgView::new(second.clone(), LayoutConfig::default(), other, &style)
+                    .show(&mut columns[1]);
+            });
+        });
+    };
+
+    frame(&mut harness, &mut other);
+
+    harness.state.selected = Some(NodeIndex::new(1));
+
+    for _ in 0..3 {
+        frame(&mut harness, &mut other);
+
+        assert!(harness.state.animations().contains(&Animation::Glow));
+        assert!(!other.animations().contains(&Animation::Glow));
+    }
+
+    for _ in 0..60 {
+        frame(&mut harness, &mut other);
+    }
+
+    assert!(!harness.state.animations().contains(&Animation::Glow));
+
+    // the other one starts glowing from nothing, not from where the first one is.
+    other.selected = Some(NodeIndex::new(1));
+
+    frame(&mut harness, &mut other);
+
+    assert!(other.animations().contains(&Animation::Glow));
+}
+
+#[test]
+fn routing_without_any_blocks_leaves_the_caches_alone() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let mut harness = Harness::new();
+
+    harness.run(|ui, state| {
+        // nothing was laid out yet, so there are no rects and no world to route in.
+        let mut view = CfgView::new(graph.clone(), LayoutConfig::default(), state, &style);
+
+        for scene in [Rect::NOTHING, Rect::NAN, Rect::ZERO] {
+            view.draw_edges(ui, scene);
+
+            let mut pending = view.pending_routes(Vec::new(), &[]);
+            view.route_port_lines(scene, &mut pending);
+
+            assert!(pending.routed.is_empty());
+        }
+
+        let id = ui.make_persistent_id("cfg_edge_cache_v1");
+        let cache = ui.data_mut(|d| d.get_persisted::<Arc<EdgeCache>>(id));
+
+        assert!(cache.is_none());
+    });
+
+    assert!(harness.state.route_field.is_none());
+    assert!(harness.state.routing.is_none());
+    assert!(harness.state.cache_ids.is_empty());
+}
+
+#[test]
+fn evicted_caches_are_forgotten_by_the_state() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let mut harness = Harness::new();
+
+    // the same state under two ids, with room for the edges of only one of them.
+    for salt in [0, 1] {
+        harness.run(|ui, state| {
+            CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                .id_salt(salt)
+                .cache_budget(1)
+                .show(ui);
+        });
+    }
+
+    assert_eq!(harness.state.cache_ids.len(), 1);
+}
+
+#[test]
+fn every_edge_into_a_join_gets_its_own_port() {
+    let style = NodeStyle::default();
+    let mut graph = StableGraph::new();
+
+    let entry = graph.add_node(block("entry", &["jmp rax"]));
+    let join = graph.add_node(block("join", &["ret"]));
+
+    for i in 0..4 {
+        let pred = graph.add_node(block(&format!("case {i}"), &["nop"]));
+        graph.add_edge(entry, pred, EdgeKind::Unconditional);
+        graph.add_edge(pred, join, EdgeKind::Unconditional);
+    }
+
+    // a branch whose both sides go to the join, so two of its edges come from the same place.
+    graph.add_edge(entry, join, EdgeKind::Taken);
+    graph.add_edge(entry, join, EdgeKind::FallThrough);
+
+    let mut harness = Harness::new();
+
+    for _ in 0..2 {
+        harness.show(&graph, &style);
+    }
+
+    let ports = harness
+        .state
+        .ports
+        .as_ref()
+        .expect("the ports were assigned");
+
+    let incoming: Vec<&PortLine> = ports
+        .lines
+        .iter()
+        .filter(|line| line.to.node == join)
+        .collect();
+
+    assert_eq!(incoming.len(), 6);
+
+    let slots: HashSet<PortSlot> = incoming.iter().map(|line| line.to).collect();
+    let edges: HashSet<EdgeIndex> = incoming.iter().filter_map(|line| line.edge).collect();
+
+    assert_eq!(slots.len(), incoming.len(), "two edges share an input port");
+    assert_eq!(edges.len(), incoming.len());
+}
+
+#[test]
+fn rounding_never_exceeds_half_a_collapsed_block() {
+    let style = NodeStyle {
+        header_height: 40.0,
+        header_rounding: CornerRadius::same(20),
+        // more than fits, like a theme that was scaled up.
+        body_rounding: CornerRadius::same(30),
+        ..Default::default()
+    };
+
+    let graph = graph();
+    let mut harness = Harness::new();
+    harness.state.collapse_all();
+
+    let mut output = None;
+
+    for _ in 0..2 {
+        output = Some(harness.show(&graph, &style).1);
+    }
+
+    for (node, rect) in harness.state.drawn_blocks() {
+        assert!((rect.height() - 40.0).abs() < 0.5, "{node:?} is {rect:?}");
+
+        let rounding = style.block_rounding_in(*rect);
+
+        for corner in [rounding.nw, rounding.ne, rounding.sw, rounding.se] {
+            assert!(corner <= 20, "{node:?} has a corner of {corner}");
+        }
+    }
+
+    // a corner rounded more than half of the painted rect would poke out of it.
+    let output = output.unwrap();
+    let painted = shapes(&output);
+
+    for (_, shape) in painted {
+        if let Shape::Rect(rect) = shape {
+            let limit = rect.rect.size().min_elem() / 2.0 + 0.5;
+            let radius = rect.corner_radius;
+
+            for corner in [radius.nw, radius.ne, radius.sw, radius.se] {
+                assert!(corner as f32 <= limit, "{corner} on {:?}", rect.rect);
+            }
+        }
+    }
+}
+
+#[test]
+fn edges_route_between_blocks_that_are_asked_to_touch() {
+    let style = NodeStyle::default();
+    let mut graph = StableGraph::new();
+
+    let entry = graph.add_node(block("entry", &["jmp [rax*8+table]"]));
+    let exit = graph.add_node(block("exit", &["ret"]));
+
+    // a whole layer of blocks next to each other, with edges going in between them.
+    for i in 0..6 {
+        let case = graph.add_node(block(&format!("case {i}"), &["nop"]));
+        graph.add_edge(entry, case, EdgeKind::Unconditional);
+        graph.add_edge(case, exit, EdgeKind::Unconditional);
+    }
+
+    graph.add_edge(entry, exit, EdgeKind::FallThrough);
+
+    let layout = LayoutConfig {
+        vertex_spacing: 1.0,
+        ..Default::default()
+    };
+
+    // cells large enough that the default least spacing isn't enough for their falloff.
+    let route = RouteConfig {
+        cell_size: Vec2::splat(6.0),
+        ..Default::default()
+    };
+
+    let mut harness = Harness::new();
+
+    for _ in 0..10 {
+        harness.run(|ui, state| {
+            CfgView::new(graph.clone(), layout.clone(), state, &style)
+                .route_config(route.clone())
+                .show(ui);
+        });
+    }
+
+    assert!(
+        harness.state.routing.is_none(),
+        "the edges are still being routed"
+    );
+
+    let routed: HashSet<EdgeIndex> = harness
+        .state
+        .drawn_edges()
+        .iter()
+        .filter(|(poly, _)| poly.len() >= 2)
+        .filter_map(|(_, line)| line.edge)
+        .collect();
+
+    assert_eq!(routed.len(), graph.edge_count());
+
+    // and the blocks of the layer are far enough apart for an edge to fit through.
+    let mut cases: Vec<Rect> = harness
+        .state
+        .drawn_blocks()
+        .iter()
+        .filter(|(node, _)| ![entry, exit].contains(node))
+        .map(|(_, rect)| *rect)
+        .collect();
+
+    cases.sort_by(|a, b| a.min.x.total_cmp(&b.min.x));
+
+    let falloff = OBSTACLE_RADIUS * route.cell_size.max_elem();
+
+    for pair in cases.windows(2) {
+        let gap = pair[1].min.x - pair[0].max.x;
+        assert!(gap >= falloff - 0.5, "a gap of {gap} between {pair:?}");
+    }
+}
+
+#[test]
+fn tiny_adjacent_blocks_have_no_degenerate_edges() {
+    let style = NodeStyle::default();
+    let mut graph = StableGraph::new();
+
+    let from = graph.add_node(block("a", &[]));
+    let to = graph.add_node(block("b", &[]));
+    graph.add_edge(from, to, EdgeKind::Unconditional);
+    graph.add_edge(to, from, EdgeKind::Taken);
+
+    // small enough that both ports of an edge end up in the same cell.
+    let sizes = HashMap::from([(from, vec2(2.0, 2.0)), (to, vec2(2.0, 2.0))]);
+
+    let layout = LayoutConfig {
+        vertex_spacing: 0.0,
+        min_vertex_spacing: 0.0,
+        ..Default::default()
+    };
+
+    let mut harness = Harness::new();
+    let mut output = None;
+
+    for _ in 0..3 {
+        output = Some(harness.run(|ui, state| {
+            state.selected_edge = state.drawn_edges().first().map(|(_, line)| line.clone());
+
+            CfgView::new(graph.clone(), layout.clone(), state, &style)
+                .measured_sizes(&sizes)
+                .show(ui);
+        }));
+    }
+
+    let edges = harness.state.drawn_edges();
+    assert_eq!(edges.len(), graph.edge_count());
+
+    for (poly, line) in edges {
+        assert!(poly.len() >= 2, "{line:?} is drawn along {poly:?}");
+        assert!(
+            poly.iter().all(|p| p.is_finite()),
+            "{line:?} is drawn along {poly:?}"
+        );
+    }
+
+    let output = output.unwrap();
+
+    // text without any glyphs has no bounds to begin with, but nothing else can be NaN.
+    for (_, shape) in shapes(&output) {
+        if matches!(shape, Shape::Text(_)) {
+            continue;
+        }
+
+        let bounds = shape.visual_bounding_rect();
+        assert!(
+            !bounds.any_nan(),
+            "{shape:?} has a bounding rect of {bounds:?}"
+        );
+    }
+}
+
+#[test]
+fn reused_edge_indices_are_drawn_for_their_new_endpoints() {
+    let style = NodeStyle::default();
+    let mut graph = graph();
+    let mut harness = Harness::new();
+
+    for _ in 0..3 {
+        harness.show(&graph, &style);
+    }
+
+    let (entry, left, right) = (NodeIndex::new(0), NodeIndex::new(1), NodeIndex::new(2));
+
+    let taken = graph.find_edge(entry, left).unwrap();
+    let fall_through = graph.find_edge(entry, right).unwrap();
+
+    graph.remove_edge(taken);
+    graph.remove_edge(fall_through);
+
+    // the removed indices get handed out again, for the other side of the branch.
+    let swapped = [
+        graph.add_edge(entry, right, EdgeKind::Taken),
+        graph.add_edge(entry, left, EdgeKind::FallThrough),
+    ];
+
+    assert!(swapped.contains(&taken) && swapped.contains(&fall_through));
+
+    for _ in 0..3 {
+        harness.show(&graph, &style);
+    }
+
+    let edges = harness.state.drawn_edges();
+    assert_eq!(edges.len(), graph.edge_count());
+
+    for (_, line) in edges {
+        let edge = line.edge.expect("every line is for an edge");
+
+        assert_eq!(
+            graph.edge_endpoints(edge),
+            Some((line.from.node, line.to.node)),
+            "{line:?} is drawn for another edge"
+        );
+    }
+}
+
+#[test]
+fn selecting_an_exit_block_highlights_the_edges_into_it() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let exit = NodeIndex::new(3);
+
+    let mut harness = Harness::new();
+    harness.show(&graph, &style);
+    harness.state.selected = Some(exit);
+
+    let (mut stats, mut output) = (None, None);
+
+    for _ in 0..2 {
+        output = Some(harness.run(|ui, state| {
+            stats = CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                .collect_stats(true)
+                .show(ui)
+                .stats;
+        }));
+    }
+
+    let (stats, output) = (stats.unwrap(), output.unwrap());
+
+    // every edge that isn't highlighted goes into a single mesh, and the rest on top.
+    assert!(stats.edge_shapes > 2, "{stats:?}");
+
+    let selected = shapes(&output)
+        .into_iter()
+        .filter(|(_, shape)| match shape {
+            Shape::Path(path) => path.stroke.color == ColorMode::Solid(style.select.color),
+            _ => false,
+        })
+        .count();
+
+    assert!(
+        selected >= 2,
+        "only {selected} edges have the selected stroke"
+    );
+}
+
+#[test]
+fn swapping_fonts_measures_the_blocks_again() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let mut harness = Harness::new();
+
+    let heights = |harness: &mut Harness| {
+        for _ in 0..3 {
+            harness.show(&graph, &style);
+        }
+
+        let mut blocks = harness.state.drawn_blocks().to_vec();
+        blocks.sort_by_key(|(node, _)| *node);
+
+        blocks
+            .into_iter()
+            .map(|(_, rect)| rect.height())
+            .collect::<Vec<_>>()
+    };
+
+    let before = heights(&mut harness);
+
+    // the body text is monospace, and the icon font in front of it has taller rows.
+    let mut fonts = egui::FontDefinitions::default();
+    let monospace = fonts
+        .families
+        .get_mut(&egui::FontFamily::Monospace)
+        .unwrap();
+    monospace.insert(0, "emoji-icon-font".into());
+
+    harness.ctx.set_fonts(fonts);
+
+    let after = heights(&mut harness);
+
+    assert_eq!(before.len(), after.len());
+    assert_ne!(
+        before, after,
+        "the blocks weren't measured with the new font"
+    );
+}
+
+#[test]
+fn blocks_without_a_rect_have_their_edges_left_out() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let left = NodeIndex::new(1);
+
+    let mut harness = Harness::new();
+    harness.show(&graph, &style);
+
+    harness.run(|ui, state| {
+        let mut view = CfgView::new(graph.clone(), LayoutConfig::default(), state, &style);
+        view.show(ui);
+
+        // as if the block was culled before it was measured.
+        view.block_rects.remove(&left);
+        view.assign_ports();
+
+        assert!(
+            view.port_positions.keys().all(|slot| slot.node != left),
+            "the block without a rect has ports"
+        );
+
+        assert!(
+            view.port_lines
+                .iter()
+                .all(|line| line.from.node != left && line.to.node != left),
+            "an edge of the block without a rect wasn't left out"
+        );
+
+        let scene = view.get_routing_rect();
+        view.draw_edges(ui, scene);
+
+        assert!(!view.edge_lines.is_empty());
+
+        for (poly, line) in view.edge_lines.iter() {
+            assert!(
+                poly.iter().all(|p| *p != Pos2::ZERO),
+                "{line:?} goes to the origin along {poly:?}"
+            );
+        }
+    });
+}
+
+#[test]
+fn a_retained_view_keeps_the_same_port_lines() {
+    let style = NodeStyle::default();
+    let ctx = egui::Context::default();
+    let mut state = CfgViewState::new(Rect::from_min_size(
+        pos2(-500.0, -500.0),
+        vec2(1000.0, 1000.0),
+    ));
+
+    let graph = graph();
+    let exit = NodeIndex::new(3);
+    let targets = [(exit, "exit(0)".to_string()), (exit, "abort".to_string())];
+
+    // the same view, shown over and over instead of rebuilt every frame.
+    let mut view =
+        CfgView::new(graph, LayoutConfig::default(), &mut state, &style).external_targets(&targets);
+    let mut counts = Vec::new();
+
+    for frame in 0..3 {
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(1024.0, 768.0))),
+            time: Some(frame as f64 / 60.0),
+            ..Default::default()
+        };
+
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| view.show(ui));
+        });
+
+        counts.push((
+            view.port_lines.len(),
+            view.edge_lines.len(),
+            view.stubs.len(),
+            view.block_rects.len(),
+        ));
+    }
+
+    // every stub has a line of its own, besides the edges of the graph.
+    let lines = view.graph.edge_count() + targets.len();
+    let blocks = view.graph.node_count();
+
+    assert_eq!(counts, vec![(lines, lines, targets.len(), blocks); 3]);
+}
+
+#[test]
+fn ports_map_into_the_interior_of_coarse_grids() {
+    let style = NodeStyle::default();
+
+    // the back edges put input ports on the top of the topmost block, right at the edge of
+    // everything that is laid out.
+    let mut diamond = graph();
+    diamond.add_edge(NodeIndex::new(3), NodeIndex::new(0), EdgeKind::Taken);
+
+    let mut chain = StableGraph::new();
+    let blocks: Vec<NodeIndex> = (0..41)
+        .map(|i| chain.add_node(block(&format!("block_{i}"), &["nop"])))
+        .collect();
+
+    for pair in blocks.windows(2) {
+        chain.add_edge(pair[0], pair[1], EdgeKind::Unconditional);
+    }
+
+    chain.add_edge(blocks[40], blocks[0], EdgeKind::Taken);
+
+    // cells scaled way up for a tiny budget, and no margin at all around the blocks.
+    let configs = [
+        RouteConfig {
+            max_cells: Some(50),
+            margin_cells: 1,
+            ..Default::default()
+        },
+        RouteConfig {
+            margin_cells: 0,
+            ..Default::default()
+        },
+    ];
+
+    for graph in [&diamond, &chain] {
+        for config in &configs {
+            let mut harness = Harness::new();
+
+            // routing asserts that every port is in an interior cell of the grid.
+            for _ in 0..3 {
+                harness.run(|ui, state| {
+                    CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                        .route_config(config.clone())
+                        .show(ui);
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn ports_are_drawn_on_their_blocks_when_cells_get_big() {
+    let style = NodeStyle::default();
+    let graph = graph();
+
+    // a tiny budget scales the cells up, which moves the ports far out to be routed.
+    let config = RouteConfig {
+        max_cells: Some(50),
+        ..Default::default()
+    };
+
+    let mut harness = Harness::new();
+
+    for _ in 0..3 {
+        harness.run(|ui, state| {
+            let mut view = CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                .route_config(config.clone());
+            view.show(ui);
+
+            let offset = view.state.ports.as_ref().map(|p| p.offset);
+            assert!(offset.is_some_and(|offset| offset > PORT_OFFSET));
+
+            for (&slot, &pos) in view.port_positions.iter() {
+                let rect = view.block_rects[&slot.node];
+                let drawn = view.port_pos(slot, pos);
+
+                let edge = match slot.kind {
+                    PortKind::Input => rect.top(),
+                    PortKind::Output => rect.bottom() + 2.0,
+                };
+
+                assert!(
+                    (drawn.y - edge).abs() < 0.01,
+                    "{slot:?} is drawn at {drawn:?}"
+                );
+            }
+
+            // the edges are drawn all the way to the ports.
+            for (poly, line) in view.edge_lines.iter() {
+                let path = view.edge_path(poly, line);
+
+                assert_eq!(
+                    path.first(),
+                    Some(&view.port_pos(line.from, view.port_positions[&line.from]))
+                );
+                assert_eq!(
+                    path.last(),
+                    Some(&view.port_pos(line.to, view.port_positions[&line.to]))
+                );
+            }
+        });
+    }
+}
+
+#[test]
+fn edges_without_a_path_are_drawn_straight() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let (entry, left, right) = (NodeIndex::new(0), NodeIndex::new(1), NodeIndex::new(2));
+
+    let mut harness = Harness::new();
+    harness.show(&graph, &style);
+
+    harness.run(|ui, state| {
+        let mut view = CfgView::new(graph.clone(), LayoutConfig::default(), state, &style);
+        view.show(ui);
+
+        let line = view
+            .port_lines
+            .iter()
+            .find(|line| line.from.node == entry && line.to.node == left)
+            .cloned()
+            .expect("the edge has a line");
+
+        // the goal is inside of another block, so there's no path to it.
+        let from = view.port_positions[&line.from];
+        let to = view.block_rects[&right].center();
+
+        Arc::make_mut(&mut view.port_positions).insert(line.to, to);
+
+        // as if nothing was routed yet.
+        ui.ctx().data_mut(|d| d.remove_by_type::<Arc<EdgeCache>>());
+        view.stats = Some(CfgStats::default());
+
+        let scene = view.get_routing_rect();
+        view.draw_edges(ui, scene);
+
+        let stats = view.stats.as_ref().unwrap();
+        assert_eq!(stats.edges_failed, 1);
+        assert_eq!(stats.edges_routed, view.port_lines.len());
+
+        let (poly, _) = view
+            .edge_lines
+            .iter()
+            .find(|(_, l)| *l == line)
+            .expect("the edge without a path was left out");
+
+        assert_eq!(poly, &vec![from, to]);
+    });
+}
+
+#[test]
+fn zoomed_out_blocks_lay_out_no_body_text() {
+    let style = NodeStyle::default();
+    let graph = graph();
+
+    // measured up front, so that only drawing the blocks could lay out their text.
+    let sizes: HashMap<NodeIndex, Vec2> = graph
+        .node_indices()
+        .map(|n| (n, vec2(160.0, 60.0)))
+        .collect();
+
+    let mut harness = Harness::new();
+
+    let frames = |harness: &mut Harness, zoom: f32| {
+        harness.state.zoom_to(zoom);
+
+        for _ in 0..2 {
+            harness.run(|ui, state| {
+                CfgView::new(graph.clone(), LayoutConfig::default(), state, &style)
+                    .measured_sizes(&sizes)
+                    .show(ui);
+            });
+        }
+
+        harness.state.galley_layouts()
+    };
+
+    for zoom in [style.lod_text_zoom * 0.9, style.lod_title_zoom * 0.9] {
+        assert_eq!(frames(&mut harness, zoom), 0, "text laid out at {zoom}");
+    }
+
+    assert_eq!(frames(&mut harness, 1.0), graph.node_count());
+}
+
+#[test]
+fn clicking_a_stub_reports_its_target() {
+    let style = NodeStyle::default();
+    let graph = graph();
+    let exit = NodeIndex::new(3);
+    let targets = [(exit, "exit(0)".to_string()), (exit, "abort".to_string())];
+
+    let mut harness = Harness::new();
+    let Harness { ctx, state, frame } = &mut harness;
+
+    // the same view over every frame, which has to keep one stub for every target.
+    let mut view =
+        CfgView::new(graph, LayoutConfig::default(), state, &style).external_targets(&targets);
+
+    let mut run = |events: Vec<egui::Event>| {
+        let mut response = None;
+
+        let _ = ctx.run(frame_input(*frame, events), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| response = Some(view.show(ui)));
+        });
+
+        *frame += 1;
+
+        assert_eq!(view.stubs.len(), targets.len());
+
+        // the area to hit the port in the middle of the top of the stub covers its middle,
+        // so the stub gets clicked next to its right end instead.
+        let stub = view.stubs[1].rect;
+        let pos = stub.right_center() - vec2(2.0, 0.0);
+
+        // where that is on the screen, through the transform of the scene.
+        let transform = ctx.memory(|m| m.to_global.values().next().copied());
+        let pos = transform.expect("the scene has a transform") * pos;
+
+        (response.unwrap(), pos)
+    };
+
+    let (_, pos) = run(Vec::new());
+    let (_, pos) = run(vec![egui::Event::PointerMoved(pos)]);
+
+    let button = |pressed| egui::Event::PointerButton {
+        pos,
+        button: egui::PointerButton::Primary,
+        pressed,
+        modifiers: egui::Modifiers::NONE,
+    };
+
+    let (response, _) = run(vec![button(true)]);
+    assert_eq!(response.external_target_clicked, None);
+
+    let (response, _) = run(vec![button(false)]);
+    assert_eq!(response.external_target_clicked, Some(1));
+
+    // it's only reported in the frame it was clicked.
+    let (response, _) = run(Vec::new());
+    assert_eq!(response.external_target_clicked, None);
+}
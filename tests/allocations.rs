@@ -0,0 +1,80 @@
+//! Routing the same paths again over a field that didn't change shouldn't allocate at all,
+//! which is checked by counting every allocation made while doing so.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use egui::{Rect, pos2, vec2};
+use egui_cfg::route::{AStar, CostField, Grid};
+
+struct Counting;
+
+thread_local! {
+    /// How many allocations this thread made while counting, if it's counting.
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get().map(|n| n + 1)));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get().map(|n| n + 1)));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// How many allocations `f` makes on this thread.
+fn allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|count| count.replace(None)).unwrap_or(0)
+}
+
+#[test]
+fn routing_a_static_field_again_does_not_allocate() {
+    let scene = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+    let mut field = CostField::new(Grid::splat(scene, 3.0));
+
+    // a few rows of blocks, like the layers of a graph.
+    for row in 0..4 {
+        for column in 0..4 {
+            let min = pos2(60.0 + column as f32 * 180.0, 50.0 + row as f32 * 140.0);
+            field.add_block_rect(Rect::from_min_size(min, vec2(120.0, 60.0)), 5.0);
+        }
+    }
+
+    let edges: Vec<_> = (0..3)
+        .flat_map(|row| (0..4).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            let from = pos2(120.0 + column as f32 * 180.0, 120.0 + row as f32 * 140.0);
+            let to = pos2(120.0 + ((column + 1) % 4) as f32 * 180.0, from.y + 60.0);
+            (from, to)
+        })
+        .collect();
+
+    let mut astar = AStar::new(&field);
+    let mut path = Vec::new();
+
+    let route_all = |astar: &mut AStar, path: &mut Vec<_>| {
+        for &(from, to) in &edges {
+            assert!(astar.find_path_into(from, to, path), "{from:?} -> {to:?}");
+        }
+    };
+
+    // the first pass grows every buffer to the size the searches need.
+    route_all(&mut astar, &mut path);
+
+    let count = allocations(|| route_all(&mut astar, &mut path));
+
+    assert_eq!(count, 0, "routing again made {count} allocations");
+}